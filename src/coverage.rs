@@ -0,0 +1,304 @@
+//! Statement and function coverage, for `sand test --coverage`
+//!
+//! Coverage is recorded by the position of statements and functions while interpreting, and
+//! reported per line for the file and every file it includes, so the parts which never ran show
+//! up too. Literal values standing alone as a statement have no position, and aren't counted.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::parser::parse_file;
+use crate::tokenizer::tokenize_str;
+use crate::types::*;
+use crate::FilePos;
+
+/// How many times statements and functions ran, by their position
+#[derive(Debug, Default)]
+pub struct Coverage {
+    statements: HashMap<FilePos, u64>,
+    functions: HashMap<FilePos, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn statement(&mut self, pos: &FilePos) {
+        *self.statements.entry(pos.clone()).or_default() += 1;
+    }
+
+    pub fn function(&mut self, pos: &FilePos) {
+        *self.functions.entry(pos.clone()).or_default() += 1;
+    }
+
+    /// The coverage of every file of the program, the file itself first and then the files it
+    /// includes
+    pub fn report(&self, tree: &Statements) -> Vec<FileCoverage> {
+        let mut collector = Collector::default();
+        collector.statements(tree);
+        let mut visited = HashSet::new();
+        let mut i = 0;
+        while i < collector.includes.len() {
+            let file = collector.includes[i].clone();
+            i += 1;
+            if !visited.insert(file.clone()) {
+                continue;
+            }
+            // Files which can't be parsed failed the program when it included them
+            let tree = fs::read_to_string(&file)
+                .ok()
+                .and_then(|source| tokenize_str(&source, FilePos::new(&file, 1, 1)).ok())
+                .and_then(|tokens| parse_file(tokens).ok());
+            if let Some(tree) = tree {
+                collector.statements(&tree);
+            }
+        }
+
+        let mut files = Vec::new();
+        for pos in &collector.statement_positions {
+            let hits = self.statements.get(pos).copied().unwrap_or(0);
+            let line = file_coverage(&mut files, &pos.file)
+                .lines
+                .entry(pos.row)
+                .or_default();
+            *line = (*line).max(hits);
+        }
+        for (name, pos) in &collector.functions {
+            let hits = self.functions.get(pos).copied().unwrap_or(0);
+            file_coverage(&mut files, &pos.file)
+                .functions
+                .push(Function {
+                    name: name.clone(),
+                    row: pos.row,
+                    hits,
+                });
+        }
+        files
+    }
+}
+
+fn file_coverage<'a>(files: &'a mut Vec<FileCoverage>, file: &Path) -> &'a mut FileCoverage {
+    match files.iter().position(|other| other.file == file) {
+        Some(i) => &mut files[i],
+        None => {
+            files.push(FileCoverage {
+                file: file.to_path_buf(),
+                lines: BTreeMap::new(),
+                functions: Vec::new(),
+            });
+            files.last_mut().unwrap()
+        }
+    }
+}
+
+/// A function of a file, with how many times it was called
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub row: usize,
+    pub hits: u64,
+}
+
+/// The coverage of a file, by line
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCoverage {
+    pub file: PathBuf,
+    /// How many times the statement of a row which ran the most ran, for every row with
+    /// statements
+    pub lines: BTreeMap<usize, u64>,
+    pub functions: Vec<Function>,
+}
+
+impl FileCoverage {
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    pub fn functions_hit(&self) -> usize {
+        self.functions.iter().filter(|fun| fun.hits > 0).count()
+    }
+}
+
+/// The coverage of files in the lcov tracefile format
+pub fn lcov(files: &[FileCoverage]) -> String {
+    let mut out = String::new();
+    for file in files {
+        out.push_str(&format!("TN:\nSF:{}\n", file.file.display()));
+        for fun in &file.functions {
+            out.push_str(&format!("FN:{},{}\n", fun.row, fun.name));
+        }
+        for fun in &file.functions {
+            out.push_str(&format!("FNDA:{},{}\n", fun.hits, fun.name));
+        }
+        out.push_str(&format!(
+            "FNF:{}\nFNH:{}\n",
+            file.functions.len(),
+            file.functions_hit()
+        ));
+        for (row, hits) in &file.lines {
+            out.push_str(&format!("DA:{},{}\n", row, hits));
+        }
+        out.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            file.lines.len(),
+            file.lines_hit()
+        ));
+    }
+    out
+}
+
+/// Finds the statements and functions which can be covered
+#[derive(Default)]
+struct Collector {
+    statement_positions: Vec<FilePos>,
+    /// Functions by the name they are bound to, or their position if they aren't bound
+    functions: Vec<(String, FilePos)>,
+    includes: Vec<PathBuf>,
+}
+
+impl Collector {
+    fn statements(&mut self, statements: &Statements) {
+        for statement in &statements.0 {
+            if let Some(pos) = statement.get_pos() {
+                self.statement_positions.push(pos.clone());
+            }
+            match statement {
+                Statement::Assignment { var, val, .. } => {
+                    let name = match var {
+                        Reference::Var(var) => Some(var.name.clone()),
+                        Reference::Member { set, field, .. } => match set.as_ref() {
+                            Value::Ref(Reference::Var(set)) => {
+                                Some(format!("{}.{}", set.name, field.name))
+                            }
+                            _ => None,
+                        },
+                    };
+                    self.value(val, name);
+                }
+                Statement::Type { name, members, .. } => {
+                    for (member, val) in members {
+                        self.value(val, Some(format!("{}.{}", name.name, member.name)));
+                    }
+                }
+                Statement::Destructure { val, .. }
+                | Statement::Return { val, .. }
+                | Statement::Value(val) => self.value(val, None),
+                Statement::While { cond, body, .. } => {
+                    self.value(cond, None);
+                    self.statements(body);
+                }
+                Statement::For { iterable, body, .. } => {
+                    self.value(iterable, None);
+                    self.statements(body);
+                }
+                Statement::Include(file) => self.includes.push(file.clone()),
+                Statement::Break(..) | Statement::Continue(..) => {}
+            }
+        }
+    }
+
+    fn value(&mut self, val: &Value, name: Option<String>) {
+        match val {
+            Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+                let name =
+                    name.unwrap_or_else(|| format!("<function>:{}:{}", fun.pos.row, fun.pos.col));
+                self.functions.push((name, fun.pos.clone()));
+                for default in fun.args.iter().filter_map(|arg| arg.default.as_ref()) {
+                    self.value(default, None);
+                }
+                self.statements(&fun.body);
+            }
+            Value::Lit(..) | Value::Ref(Reference::Var(..)) => {}
+            Value::Ref(Reference::Member { set, .. }) => self.value(set, None),
+            Value::FunCall { fun, params, .. } => {
+                self.value(fun, None);
+                params.iter().for_each(|param| self.value(param, None));
+            }
+            Value::Try { val, .. } | Value::Propagate { val, .. } => self.value(val, None),
+            Value::Format { parts, .. } => {
+                for part in parts {
+                    if let FormatPart::Value { val, .. } = part {
+                        self.value(val, None);
+                    }
+                }
+            }
+            Value::Match { val, arms, .. } => {
+                self.value(val, None);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.value(guard, None);
+                    }
+                    self.statements(&arm.body);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::interpret_main;
+    use crate::intrinsics::init_scope;
+
+    use super::*;
+
+    #[test]
+    fn cover_program() {
+        let str = "let unused = () { 1 };
+let sign = (n) {
+    match n {
+        0 => n,
+        n => n - 1
+    }
+};
+let main = (args) {
+    let a = sign(2);
+    sign(3)
+};";
+        let file = Path::new("test.sand");
+        let tree = parse_file(tokenize_str(str, FilePos::new(file, 1, 1)).unwrap()).unwrap();
+        let mut scope = init_scope();
+        scope.state.borrow_mut().coverage = Some(Coverage::new());
+        assert_eq!(interpret_main(tree.clone(), &mut scope).unwrap(), 2);
+
+        let coverage = scope.state.borrow_mut().coverage.take().unwrap();
+        let files = coverage.report(&tree);
+        assert_eq!(files.len(), 1);
+        let lines: Vec<_> = files[0]
+            .lines
+            .iter()
+            .map(|(row, hits)| (*row, *hits))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (1, 1),
+                (2, 1),
+                (3, 2),
+                (4, 0),
+                (5, 2),
+                (8, 1),
+                (9, 1),
+                (10, 1)
+            ]
+        );
+        let functions: Vec<_> = files[0]
+            .functions
+            .iter()
+            .map(|fun| (fun.name.as_str(), fun.row, fun.hits))
+            .collect();
+        assert_eq!(
+            functions,
+            [("unused", 1, 0), ("sign", 2, 2), ("main", 8, 1)]
+        );
+
+        let lcov = lcov(&files);
+        assert!(lcov.starts_with("TN:\nSF:test.sand\nFN:1,unused\nFN:2,sign\n"));
+        assert!(lcov.contains("FNDA:0,unused\n"));
+        assert!(lcov.contains("FNF:3\nFNH:2\n"));
+        assert!(lcov.contains("DA:4,0\n"));
+        assert!(lcov.ends_with("LF:8\nLH:7\nend_of_record\n"));
+    }
+}
@@ -0,0 +1,476 @@
+//! A command line debugger for sand, for `sand debug`
+//!
+//! The program pauses before its first statement, or at its first breakpoint when it has some.
+//! While it is paused, commands read a line at a time step through the program, show where it
+//! is and evaluate values in the scope it is paused in. Stepping goes a line at a time, so a
+//! line with several statements only pauses once each time it runs.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::interpreter::{repr, Debugger, ErrorKind, Interpret, InterpretingError, Scope};
+use crate::intrinsics::init_scope;
+use crate::parser::parse_file;
+use crate::tokenizer::tokenize_str;
+use crate::types::*;
+use crate::{FilePos, SandError};
+
+const HELP: &str = "\
+Commands:
+    c, continue          Run until the next breakpoint
+    s, step              Run until the next line, stepping into calls
+    n, next              Run until the next line of this function, stepping over calls
+    o, out               Run until the function returns
+    b, break [file:]row  Set a breakpoint, or list them without a row
+    d, delete [file:]row Remove a breakpoint
+    l, locals            Show the variables in scope, without functions and built-ins
+    bt, stack            Show the call stack
+    w, where             Show where the program is paused
+    p, print <value>     Evaluate a value in the paused scope, which can also be `let x = val`
+    q, quit              Stop the program
+    h, help              Show this";
+
+/// A line to pause at, where the file can be any number of trailing components of its path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub file: PathBuf,
+    pub row: usize,
+}
+
+impl Breakpoint {
+    fn matches(&self, pos: &FilePos) -> bool {
+        pos.row == self.row && normalize(&pos.file).ends_with(normalize(&self.file))
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (file, row) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Expected a breakpoint like `file.sand:12`, not `{}`", s))?;
+        let row = row
+            .parse()
+            .map_err(|_| format!("Expected the row of the breakpoint, not `{}`", row))?;
+        Ok(Self {
+            file: PathBuf::from(file),
+            row,
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.row)
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// How far to run before pausing again
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Continue,
+    StepIn,
+    /// Pause at the next line of a call stack this deep or shallower
+    StepOver(usize),
+    /// Pause at the next line of a call stack shallower than this
+    StepOut(usize),
+}
+
+/// A debugger taking commands from `input` and writing to `output`
+pub struct Repl<R, W> {
+    input: R,
+    output: W,
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    /// The file, row, call stack depth and column of the statement before, to pause once per
+    /// line
+    line: Option<(PathBuf, usize, usize, usize)>,
+    /// The lines of the files paused in
+    sources: HashMap<PathBuf, Vec<String>>,
+    builtins: HashSet<String>,
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    pub fn new(input: R, output: W, breakpoints: Vec<Breakpoint>) -> Self {
+        let mode = match breakpoints.is_empty() {
+            true => Mode::StepIn,
+            false => Mode::Continue,
+        };
+        Self {
+            input,
+            output,
+            breakpoints,
+            mode,
+            line: None,
+            sources: HashMap::new(),
+            builtins: init_scope().iter().map(|(name, _)| name.clone()).collect(),
+        }
+    }
+
+    /// Takes commands until one resumes the program, returning whether it should keep running
+    fn pause(&mut self, pos: &FilePos, scope: &mut Scope) -> io::Result<bool> {
+        writeln!(self.output, "Paused at {}", pos)?;
+        self.show_source(pos, 0)?;
+        let depth = scope.state.borrow().stack.len();
+        loop {
+            write!(self.output, "(sand) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                writeln!(self.output)?;
+                return Ok(false);
+            }
+            let line = line.trim();
+            let (command, arg) = match line.split_once(char::is_whitespace) {
+                Some((command, arg)) => (command, arg.trim()),
+                None => (line, ""),
+            };
+            match command {
+                "" => {}
+                "c" | "continue" => return self.resume(Mode::Continue),
+                "s" | "step" => return self.resume(Mode::StepIn),
+                "n" | "next" => return self.resume(Mode::StepOver(depth)),
+                "o" | "out" => return self.resume(Mode::StepOut(depth)),
+                "b" | "break" if arg.is_empty() => {
+                    for breakpoint in &self.breakpoints {
+                        writeln!(self.output, "{}", breakpoint)?;
+                    }
+                }
+                "b" | "break" => match self.breakpoint(arg, pos) {
+                    Ok(breakpoint) => {
+                        writeln!(self.output, "Breakpoint at {}", breakpoint)?;
+                        self.breakpoints.push(breakpoint);
+                    }
+                    Err(err) => writeln!(self.output, "{}", err)?,
+                },
+                "d" | "delete" => match self.breakpoint(arg, pos) {
+                    Ok(breakpoint) if self.breakpoints.contains(&breakpoint) => {
+                        self.breakpoints.retain(|other| *other != breakpoint);
+                        writeln!(self.output, "Removed the breakpoint at {}", breakpoint)?;
+                    }
+                    Ok(breakpoint) => writeln!(self.output, "No breakpoint at {}", breakpoint)?,
+                    Err(err) => writeln!(self.output, "{}", err)?,
+                },
+                "l" | "locals" => self.show_locals(scope, pos)?,
+                "bt" | "stack" => self.show_stack(scope, pos)?,
+                "w" | "where" => {
+                    writeln!(self.output, "Paused at {}", pos)?;
+                    self.show_source(pos, 2)?;
+                }
+                "p" | "print" => self.evaluate(arg, scope, pos)?,
+                "q" | "quit" => return Ok(false),
+                "h" | "help" => writeln!(self.output, "{}", HELP)?,
+                command => writeln!(
+                    self.output,
+                    "Unknown command `{}`, `help` lists the commands",
+                    command
+                )?,
+            }
+        }
+    }
+
+    fn resume(&mut self, mode: Mode) -> io::Result<bool> {
+        self.mode = mode;
+        Ok(true)
+    }
+
+    /// Parses a breakpoint, which is in the file paused in without a file
+    fn breakpoint(&self, arg: &str, pos: &FilePos) -> Result<Breakpoint, String> {
+        match arg.parse() {
+            Ok(row) => Ok(Breakpoint {
+                file: pos.file.clone(),
+                row,
+            }),
+            Err(..) => arg.parse(),
+        }
+    }
+
+    /// Shows the row of `pos` with `context` rows around it
+    fn show_source(&mut self, pos: &FilePos, context: usize) -> io::Result<()> {
+        let lines = self.sources.entry(pos.file.clone()).or_insert_with(|| {
+            fs::read_to_string(&pos.file)
+                .map(|source| source.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        let first = pos.row.saturating_sub(context).max(1);
+        for row in first..=pos.row + context {
+            if let Some(line) = lines.get(row - 1) {
+                let marker = if row == pos.row { ">" } else { " " };
+                writeln!(self.output, "{} {:>4} | {}", marker, row, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_locals(&mut self, scope: &mut Scope, pos: &FilePos) -> io::Result<()> {
+        let mut vars: Vec<_> = scope
+            .iter()
+            .filter(|(name, val)| {
+                !self.builtins.contains(*name) && !matches!(val, Literal::Fun(..))
+            })
+            .map(|(name, val)| (name.clone(), val.clone()))
+            .collect();
+        vars.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, val) in vars {
+            let val = show(&val, scope, pos);
+            writeln!(self.output, "{} = {}", name, val)?;
+        }
+        Ok(())
+    }
+
+    /// Shows the functions of the call stack with where they are, innermost first
+    fn show_stack(&mut self, scope: &Scope, pos: &FilePos) -> io::Result<()> {
+        let stack = scope.stack();
+        let names = stack
+            .iter()
+            .rev()
+            .map(|frame| frame.name.as_deref().unwrap_or("<function>"))
+            .chain(["<file>"]);
+        let positions = std::iter::once(pos).chain(stack.iter().rev().map(|frame| &frame.pos));
+        for (i, (name, pos)) in names.zip(positions).enumerate() {
+            writeln!(self.output, "#{} {} at {}", i, name, pos)?;
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, source: &str, scope: &mut Scope, pos: &FilePos) -> io::Result<()> {
+        let start = FilePos::new(Path::new("<debugger>"), 1, 1);
+        let tree = match tokenize_str(source, start) {
+            Ok(tokens) if tokens.is_empty() => {
+                return writeln!(self.output, "Expected a value to evaluate")
+            }
+            Ok(tokens) => parse_file(tokens),
+            Err(err) => Err(SandError::from(err)),
+        };
+        let tree = match tree {
+            Ok(tree) => tree,
+            Err(err) => return writeln!(self.output, "{}", err),
+        };
+        match tree.interpret(scope) {
+            Ok(val) => {
+                let val = show(&val, scope, pos);
+                writeln!(self.output, "{}", val)
+            }
+            Err(err) => writeln!(self.output, "{}", SandError::from(err)),
+        }
+    }
+}
+
+/// Shows a value like sand code would write it, or its type if it can't be converted
+fn show(val: &Literal, scope: &mut Scope, pos: &FilePos) -> String {
+    repr(val, scope, pos).unwrap_or_else(|_| format!("<{}>", val.type_name()))
+}
+
+impl<R: BufRead, W: Write> Debugger for Repl<R, W> {
+    fn statement(&mut self, pos: &FilePos, scope: &mut Scope) -> Result<(), InterpretingError> {
+        let depth = scope.state.borrow().stack.len();
+        // Statements further along the line of the one before are part of the same evaluation
+        // of the line, while a loop going around again starts over at a statement before it
+        let same_line = matches!(
+            &self.line,
+            Some((file, row, line_depth, col))
+                if file == &pos.file && *row == pos.row && *line_depth == depth && *col < pos.col
+        );
+        self.line = Some((pos.file.clone(), pos.row, depth, pos.col));
+        if same_line {
+            return Ok(());
+        }
+
+        let pause = match self.mode {
+            Mode::Continue => false,
+            Mode::StepIn => true,
+            Mode::StepOver(max) => depth <= max,
+            Mode::StepOut(max) => depth < max,
+        };
+        if !pause
+            && !self
+                .breakpoints
+                .iter()
+                .any(|breakpoint| breakpoint.matches(pos))
+        {
+            return Ok(());
+        }
+        match self.pause(pos, scope) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(InterpretingError {
+                kind: ErrorKind::Panic,
+                ..InterpretingError::new("Stopped by the debugger", pos)
+            }),
+            Err(err) => Err(InterpretingError::new(
+                &format!(
+                    "The debugger cannot read or write commands because: {}",
+                    err
+                ),
+                pos,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::interpreter::interpret_main;
+
+    use super::*;
+
+    /// Output which can still be read after the debugger owning it is gone
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const PROGRAM: &str = "let double = (n) {
+    let m = n * 2;
+    m
+};
+let main = (args) {
+    let x = double(3);
+    let y = double(x);
+    y
+};";
+
+    fn debug(commands: &str, breakpoints: &[&str]) -> (Result<i32, InterpretingError>, String) {
+        debug_program(PROGRAM, commands, breakpoints)
+    }
+
+    fn debug_program(
+        program: &str,
+        commands: &str,
+        breakpoints: &[&str],
+    ) -> (Result<i32, InterpretingError>, String) {
+        let tokens = tokenize_str(program, FilePos::new(Path::new("test.sand"), 1, 1)).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let output = Output::default();
+        let breakpoints = breakpoints.iter().map(|b| b.parse().unwrap()).collect();
+        let input = io::Cursor::new(commands.as_bytes().to_vec());
+        let repl = Repl::new(input, output.clone(), breakpoints);
+        let mut scope = init_scope();
+        scope.state.borrow_mut().debugger = Some(Box::new(repl));
+        let res = interpret_main(tree, &mut scope);
+        let output = String::from_utf8(output.0.take()).unwrap();
+        (res, output)
+    }
+
+    fn paused_at(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .filter_map(|line| line.split("Paused at ").nth(1))
+            .collect()
+    }
+
+    #[test]
+    fn debug_steps() {
+        let (res, output) = debug("s\ns\ns\nn\ns\ns\nn\no\nc\n", &[]);
+        assert_eq!(res.unwrap(), 12);
+        assert_eq!(
+            paused_at(&output),
+            [
+                "test.sand:1:1",
+                "test.sand:5:1",
+                "test.sand:6:5",
+                "test.sand:2:5",
+                "test.sand:3:5",
+                "test.sand:7:5",
+                "test.sand:2:5",
+                "test.sand:3:5",
+                "test.sand:8:5",
+            ]
+        );
+    }
+
+    #[test]
+    fn debug_breakpoints() {
+        let (res, output) = debug("p n\nbt\no\nd 2\nc\n", &["test.sand:2"]);
+        assert_eq!(res.unwrap(), 12);
+        assert_eq!(paused_at(&output), ["test.sand:2:5", "test.sand:7:5"]);
+        assert!(output.contains("(sand) 3\n"));
+        assert!(output.contains(
+            "#0 double at test.sand:2:5\n#1 main at test.sand:6:13\n#2 <file> at test.sand:5:12\n"
+        ));
+        assert!(output.contains("Removed the breakpoint at test.sand:2"));
+    }
+
+    #[test]
+    fn debug_loops() {
+        let program = "let main = (args) {
+    let sum = 0;
+    for x in [1, 2, 3] {
+        let sum = sum + x
+    };
+    for x in [4, 5, 6] { let sum = sum + x; let sum = sum };
+    sum
+};";
+        // A breakpoint in a loop body pauses on every iteration
+        let (res, output) = debug_program(program, "p x\nc\np x\nc\np x\nc\n", &["test.sand:4"]);
+        assert_eq!(res.unwrap(), 21);
+        assert_eq!(paused_at(&output), ["test.sand:4:9"; 3]);
+        assert!(output.contains("(sand) 1\n") && output.contains("(sand) 3\n"));
+
+        // So does stepping, while a line pauses once per evaluation, so the first iteration is
+        // part of the line of the `for`
+        let (res, output) = debug_program(program, "s\np x\ns\np x\ns\nc\n", &["test.sand:6"]);
+        assert_eq!(res.unwrap(), 21);
+        assert_eq!(
+            paused_at(&output),
+            [
+                "test.sand:6:5",
+                "test.sand:6:26",
+                "test.sand:6:26",
+                "test.sand:7:5"
+            ]
+        );
+        assert!(output.contains("(sand) 5\n") && output.contains("(sand) 6\n"));
+    }
+
+    #[test]
+    fn debug_scope() {
+        let (res, output) = debug("p let n = 10\np let x =\nl\nq\n", &["test.sand:3"]);
+        let err = res.unwrap_err();
+        assert_eq!(err.msg, "Stopped by the debugger");
+        assert_eq!(err.kind, ErrorKind::Panic);
+        assert!(output.contains("(sand) Nope\n(sand) "));
+        assert!(output.contains("Expected a value after `=`"));
+        // `args` is whatever the interpreter passes `main`, which isn't up to the debugger
+        let variables: Vec<_> = output
+            .lines()
+            .filter(|line| line.starts_with("m = ") || line.starts_with("n = "))
+            .collect();
+        assert_eq!(variables, ["m = 6", "n = 10"]);
+    }
+
+    #[test]
+    fn parse_breakpoints() {
+        let breakpoint: Breakpoint = "dir/a.sand:12".parse().unwrap();
+        assert_eq!(breakpoint.file, Path::new("dir/a.sand"));
+        assert_eq!(breakpoint.row, 12);
+        assert!("a.sand".parse::<Breakpoint>().is_err());
+        assert!("a.sand:x".parse::<Breakpoint>().is_err());
+
+        let breakpoint: Breakpoint = "./a.sand:12".parse().unwrap();
+        assert!(breakpoint.matches(&FilePos::new(Path::new("/src/a.sand"), 12, 1)));
+        assert!(!breakpoint.matches(&FilePos::new(Path::new("/src/ba.sand"), 12, 1)));
+        assert!(!breakpoint.matches(&FilePos::new(Path::new("/src/a.sand"), 13, 1)));
+    }
+}
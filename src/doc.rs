@@ -0,0 +1,471 @@
+//! Documentation of sand files and the built-in sets, for `sand doc`
+//!
+//! The comment lines right above a top-level binding, a set member or a type document it. When
+//! the first line is like `# name: (num){num}`, it is the signature of the binding rather than a
+//! part of its description.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::intrinsics::{init_scope, DOCS};
+use crate::parser::parse_file;
+use crate::tokenizer::{tokenize_lossless, tokenize_str, SourceTokens, TriviaKind};
+use crate::types::*;
+use crate::{FilePos, SandError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(format!("Unknown format `{}`, expected `md` or `html`", s)),
+        }
+    }
+}
+
+/// A documented binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The name, with the set or type it is a member of, like `Main.STDOUT`
+    pub name: String,
+    /// The parameters, if it is a function or a type
+    pub params: Option<Vec<String>>,
+    /// The signature from its comment, like `(num){num}`
+    pub signature: Option<String>,
+    pub description: String,
+}
+
+impl Entry {
+    fn new(name: String, params: Option<Vec<String>>, doc: Option<&Vec<String>>) -> Self {
+        let lines = doc.map(Vec::as_slice).unwrap_or_default();
+        let short = name.rsplit('.').next().unwrap_or(&name);
+        let signature = lines.first().and_then(|first| {
+            let rest = first
+                .strip_prefix(name.as_str())
+                .or_else(|| first.strip_prefix(short))?;
+            let rest = rest.trim_start_matches(':').trim();
+            rest.starts_with('(').then(|| rest.to_string())
+        });
+        let description = match signature {
+            Some(..) => &lines[1..],
+            None => lines,
+        };
+        Self {
+            name,
+            params,
+            signature,
+            description: description.join("\n").trim().to_string(),
+        }
+    }
+
+    /// How the binding is used, like `max(self, n)`
+    fn heading(&self) -> String {
+        match &self.params {
+            Some(params) => format!("{}({})", self.name, params.join(", ")),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The documented bindings of a file, or of a built-in set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub entries: Vec<Entry>,
+}
+
+/// The documentation of a file, the files it includes and the built-in sets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Docs {
+    pub title: String,
+    /// The file and the files it includes, in the order they are included
+    pub files: Vec<Section>,
+    pub builtins: Vec<Section>,
+}
+
+/// Documents a file, following its includes
+pub fn document(file: &Path) -> Result<Docs, SandError> {
+    let root = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    let dir = root.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let mut files = Vec::new();
+    document_file(&root, &dir, &mut HashSet::new(), &mut files)?;
+    let title = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.display().to_string());
+    Ok(Docs {
+        title,
+        files,
+        builtins: document_builtins(),
+    })
+}
+
+fn document_file(
+    path: &Path,
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    sections: &mut Vec<Section>,
+) -> Result<(), SandError> {
+    if !visited.insert(path.to_path_buf()) {
+        return Ok(());
+    }
+    let pos = FilePos::new(path, 1, 1);
+    let source = fs::read_to_string(path).map_err(|err| {
+        SandError::new(
+            &format!("Cannot read `{}` because: {}", path.display(), err),
+            &pos,
+        )
+    })?;
+    let comments = doc_comments(&tokenize_lossless(&source, pos.clone())?);
+    let tree = parse_file(tokenize_str(&source, pos)?)?;
+
+    let mut entries = Vec::new();
+    let mut includes = Vec::new();
+    for statement in &tree.0 {
+        match statement {
+            Statement::Assignment { var, val, pos } => {
+                let name = match var {
+                    Reference::Var(var) => var.name.clone(),
+                    Reference::Member { set, field, .. } => match set.as_ref() {
+                        Value::Ref(Reference::Var(set)) => format!("{}.{}", set.name, field.name),
+                        _ => continue,
+                    },
+                };
+                entries.push(Entry::new(name, params(val), comments.get(&pos.row)));
+            }
+            Statement::Type {
+                name,
+                fields,
+                members,
+                pos,
+            } => {
+                let fields = fields.iter().map(Param::to_string).collect();
+                entries.push(Entry::new(
+                    name.name.clone(),
+                    Some(fields),
+                    comments.get(&pos.row),
+                ));
+                for (member, val) in members {
+                    entries.push(Entry::new(
+                        format!("{}.{}", name.name, member.name),
+                        params(val),
+                        comments.get(&member.pos.row),
+                    ));
+                }
+            }
+            Statement::Include(path) => includes.push(path.clone()),
+            _ => {}
+        }
+    }
+
+    let title = relative(path, dir).display().to_string();
+    sections.push(Section { title, entries });
+    for include in includes {
+        document_file(&include, dir, visited, sections)?;
+    }
+    Ok(())
+}
+
+/// The path from the directory to the file, like `../std.sand`
+fn relative(path: &Path, dir: &Path) -> PathBuf {
+    let path: Vec<_> = path.components().collect();
+    let dir: Vec<_> = dir.components().collect();
+    let common = path.iter().zip(&dir).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf = dir[common..].iter().map(|_| "..").collect();
+    relative.extend(&path[common..]);
+    relative
+}
+
+fn params(val: &Value) -> Option<Vec<String>> {
+    match val {
+        Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+            Some(fun.args.iter().map(Param::to_string).collect())
+        }
+        _ => None,
+    }
+}
+
+/// The comment lines on their own lines right above a token, by the row of the token, without
+/// the `#` and the space after it
+fn doc_comments(tokens: &SourceTokens) -> HashMap<usize, Vec<String>> {
+    let mut comments = HashMap::new();
+    for token in &tokens.tokens {
+        let leading = &token.leading;
+        let mut lines = Vec::new();
+        let mut i = leading.len();
+        // Every comment must be followed by a single newline, and start its line
+        while i >= 2
+            && leading[i - 1].kind == TriviaKind::Whitespace
+            && leading[i - 1].text.matches('\n').count() == 1
+            && leading[i - 2].kind == TriviaKind::Comment
+        {
+            let comment = &leading[i - 2];
+            let own_line = match i {
+                2 => comment.span.offset == 0,
+                _ => leading[i - 3].text.contains('\n') || leading[i - 3].span.offset == 0,
+            };
+            if !own_line {
+                break;
+            }
+            let text = comment.text.trim_start_matches('#');
+            lines.push(
+                text.strip_prefix(' ')
+                    .unwrap_or(text)
+                    .trim_end()
+                    .to_string(),
+            );
+            i -= 2;
+        }
+        if !lines.is_empty() {
+            lines.reverse();
+            comments.insert(token.pos.row, lines);
+        }
+    }
+    comments
+}
+
+/// Documents the sets of the global scope, from the descriptions in `intrinsics::DOCS`
+pub fn document_builtins() -> Vec<Section> {
+    let docs: HashMap<&str, (&str, &str)> = DOCS
+        .iter()
+        .map(|(name, signature, description)| (*name, (*signature, *description)))
+        .collect();
+    let scope = init_scope();
+    let mut sets: Vec<_> = scope
+        .iter()
+        .filter_map(|(name, val)| match val {
+            Literal::Set(members) => Some((name, members)),
+            _ => None,
+        })
+        .collect();
+    sets.sort_by_key(|(name, _)| *name);
+
+    sets.into_iter()
+        .map(|(set, members)| {
+            let mut members: Vec<_> = members.iter().collect();
+            members.sort_by_key(|(name, _)| *name);
+            let entries = members
+                .into_iter()
+                .map(|(member, val)| {
+                    let name = format!("{}.{}", set, member);
+                    let params = match val {
+                        Literal::Fun(Callable::Intr(intrinsic)) => {
+                            Some(intrinsic.args.iter().map(|arg| arg.name.clone()).collect())
+                        }
+                        _ => None,
+                    };
+                    let (signature, description) = docs.get(name.as_str()).copied().unzip();
+                    Entry {
+                        name,
+                        params,
+                        signature: signature.map(str::to_string),
+                        description: description.unwrap_or_default().to_string(),
+                    }
+                })
+                .collect();
+            Section {
+                title: set.clone(),
+                entries,
+            }
+        })
+        .collect()
+}
+
+impl Docs {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Markdown => self.markdown(),
+            Format::Html => self.html(),
+        }
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut out = format!("# {}\n", self.title);
+        for (heading, sections) in [("Files", &self.files), ("Built-ins", &self.builtins)] {
+            out.push_str(&format!("\n## {}\n", heading));
+            for section in sections {
+                out.push_str(&format!("\n### {}\n", section.title));
+                for entry in &section.entries {
+                    out.push_str(&format!("\n#### `{}`\n", entry.heading()));
+                    if let Some(signature) = &entry.signature {
+                        out.push_str(&format!("\n`{}`\n", signature));
+                    }
+                    if !entry.description.is_empty() {
+                        out.push_str(&format!("\n{}\n", entry.description));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    pub fn html(&self) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>body {{ font-family: sans-serif; max-width: 50em; margin: auto; }}</style>\n\
+             </head>\n<body>\n<h1>{}</h1>\n",
+            escape(&self.title),
+            escape(&self.title)
+        );
+        for (heading, sections) in [("Files", &self.files), ("Built-ins", &self.builtins)] {
+            out.push_str(&format!("<h2>{}</h2>\n", heading));
+            for section in sections {
+                out.push_str(&format!("<h3>{}</h3>\n", escape(&section.title)));
+                for entry in &section.entries {
+                    out.push_str(&format!(
+                        "<h4 id=\"{}\"><code>{}</code></h4>\n",
+                        escape(&entry.name),
+                        escape(&entry.heading())
+                    ));
+                    if let Some(signature) = &entry.signature {
+                        out.push_str(&format!("<p><code>{}</code></p>\n", escape(signature)));
+                    }
+                    for paragraph in entry.description.split("\n\n") {
+                        if !paragraph.trim().is_empty() {
+                            out.push_str(&format!("<p>{}</p>\n", inline_code(paragraph.trim())));
+                        }
+                    }
+                }
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes the text, turning the parts between backticks into code
+fn inline_code(text: &str) -> String {
+    text.split('`')
+        .enumerate()
+        .map(|(i, part)| match i % 2 {
+            0 => escape(part),
+            _ => format!("<code>{}</code>", escape(part)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry<'a>(docs: &'a Docs, name: &str) -> &'a Entry {
+        docs.files
+            .iter()
+            .chain(&docs.builtins)
+            .flat_map(|section| &section.entries)
+            .find(|entry| entry.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn document_files() {
+        let docs = document(Path::new("examples/hello-world.sand")).unwrap();
+        assert_eq!(docs.title, "hello-world.sand");
+        let titles: Vec<_> = docs.files.iter().map(|file| file.title.as_str()).collect();
+        assert_eq!(titles[0], "hello-world.sand");
+        assert_eq!(titles[1], "../std.sand");
+
+        let print = entry(&docs, "print");
+        assert_eq!(print.params, Some(vec!["s".to_string()]));
+        assert_eq!(print.signature.as_deref(), Some("(Printable){nope}"));
+        assert_eq!(print.description, "");
+        // Separated by a blank line, so it documents nothing
+        assert_eq!(entry(&docs, "abs").signature.as_deref(), Some("(num){num}"));
+        assert_eq!(entry(&docs, "Main.STDOUT").params, None);
+    }
+
+    #[test]
+    fn document_comments() {
+        let file = std::env::temp_dir().join("sand_doc_comments.sand");
+        fs::write(
+            &file,
+            "# A point\n#\n# Made of `x` and `y`\ntype Point = (x, y) {\n    \
+             # dist: (Point){num}\n    let dist = (self) { self.x };\n};\n\
+             let a = 1; # Not about b\nlet b = 2;\n",
+        )
+        .unwrap();
+        let docs = document(&file).unwrap();
+        let point = entry(&docs, "Point");
+        assert_eq!(point.params, Some(vec!["x".to_string(), "y".to_string()]));
+        assert_eq!(point.signature, None);
+        assert_eq!(point.description, "A point\n\nMade of `x` and `y`");
+        let dist = entry(&docs, "Point.dist");
+        assert_eq!(dist.signature.as_deref(), Some("(Point){num}"));
+        assert_eq!(entry(&docs, "b").description, "");
+
+        let html = docs.html();
+        assert!(html.contains("<p>A point</p>\n<p>Made of <code>x</code> and <code>y</code></p>"));
+        assert!(docs
+            .markdown()
+            .contains("\n#### `Point.dist(self)`\n\n`(Point){num}`\n"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn document_every_intrinsic() {
+        let builtins = document_builtins();
+        let names: HashSet<_> = builtins
+            .iter()
+            .flat_map(|section| &section.entries)
+            .map(|entry| entry.name.as_str())
+            .collect();
+        // Every registered intrinsic has an entry in `DOCS`, and every entry an intrinsic
+        let documented: HashSet<_> = DOCS.iter().map(|(name, _, _)| *name).collect();
+        assert_eq!(names, documented);
+        for entry in builtins.iter().flat_map(|section| &section.entries) {
+            assert!(!entry.description.is_empty(), "{}", entry.name);
+            // The signature takes as many parameters as the intrinsic
+            let signature = entry.signature.as_deref().unwrap();
+            let params = entry.params.as_ref().map_or(0, Vec::len);
+            assert_eq!(signature_params(signature), params, "{}", entry.name);
+        }
+    }
+
+    /// How many parameters a signature like `(str, [str]){str}` has
+    fn signature_params(signature: &str) -> usize {
+        let mut depth = 0;
+        let mut params = 0;
+        for c in signature.chars() {
+            if depth == 1 && params == 0 && !c.is_whitespace() && c != ')' {
+                params = 1;
+            }
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                ',' if depth == 1 => params += 1,
+                _ => (),
+            }
+        }
+        params
+    }
+
+    #[test]
+    fn escape_html() {
+        assert_eq!(
+            inline_code("a < b & `x > \"y\"`"),
+            "a &lt; b &amp; <code>x &gt; &quot;y&quot;</code>"
+        );
+    }
+}
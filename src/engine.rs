@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use crate::host::{function, method, HostFn};
+use crate::interpreter::{call, lookup, Interpret, InterpretingError, Limits, Scope};
+use crate::intrinsics::{init_scope, init_scope_with, name_intrinsic, Capabilities};
+use crate::parser::parse_file;
+use crate::tokenizer::tokenize_str;
+use crate::types::*;
+use crate::{FilePos, SandError};
+
+/// A sand interpreter with its own global scope, which persists between evaluations
+///
+/// Engines run on any thread, whatever the size of its stack: calls which run low on stack
+/// continue on stacks allocated on the heap, up to `Limits::call_depth` nested calls.
+///
+/// ```
+/// use sand::Engine;
+///
+/// let mut engine = Engine::new();
+/// engine.set("width", 3.0);
+/// engine.eval("let area = width * width").unwrap();
+/// let area: f64 = engine.get("area").unwrap();
+/// assert_eq!(area, 9.0);
+/// ```
+pub struct Engine {
+    scope: Scope,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    /// Creates an engine with the built-in sets, like `Main` and `Num`, in scope
+    pub fn new() -> Self {
+        Self {
+            scope: init_scope(),
+        }
+    }
+
+    /// The global scope, for everything the other methods don't cover
+    pub fn scope(&mut self) -> &mut Scope {
+        &mut self.scope
+    }
+
+    /// Creates an engine without the intrinsics `capabilities` don't allow
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self {
+            scope: init_scope_with(capabilities),
+        }
+    }
+
+    /// Sets the limits on the resources each evaluation or call can use
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.scope.state.borrow_mut().limits = limits;
+    }
+
+    /// Evaluates sand code in the global scope, and returns the value of its last statement
+    pub fn eval(&mut self, src: &str) -> Result<Literal, SandError> {
+        self.eval_at(src, FilePos::new(Path::new("eval"), 1, 1))
+    }
+
+    /// Evaluates a sand file in the global scope, and returns the value of its last statement
+    ///
+    /// Unlike `sand <file> run`, this doesn't call the `main` function of the file.
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Literal, SandError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path).map_err(|err| {
+            SandError::from(InterpretingError::new(
+                &format!("Cannot read `{}` because:\n{}", path.display(), err),
+                &FilePos::new(path, 0, 0),
+            ))
+        })?;
+        self.eval_at(&src, FilePos::new(path, 1, 1))
+    }
+
+    fn eval_at(&mut self, src: &str, pos: FilePos) -> Result<Literal, SandError> {
+        self.scope.state.borrow_mut().restart();
+        let tokens = tokenize_str(src, pos)?;
+        let tree = parse_file(tokens)?;
+        Ok(tree.interpret(&mut self.scope)?)
+    }
+
+    /// Gets the global `name` as a Rust value
+    pub fn get<T: TryFrom<Literal, Error = TypeError>>(&self, name: &str) -> Result<T, SandError> {
+        let lit = lookup(&self.scope, name).map_err(|err| err.at(&FilePos::internal()))?;
+        Ok(T::try_from(lit).map_err(|err| TypeError {
+            pos: FilePos::internal(),
+            ..err
+        })?)
+    }
+
+    /// Sets the global `name` to a Rust value
+    pub fn set<T: Into<Literal>>(&mut self, name: &str, val: T) {
+        self.scope.insert(name.to_string(), val.into());
+    }
+
+    /// Calls the global function `name` with the arguments `args`
+    pub fn call(&mut self, name: &str, args: Vec<Literal>) -> Result<Literal, SandError> {
+        let fun = lookup(&self.scope, name).map_err(|err| err.at(&FilePos::internal()))?;
+        self.scope.state.borrow_mut().restart();
+        Ok(call(
+            fun,
+            Some(name.to_string()),
+            None,
+            args,
+            &mut self.scope,
+            &FilePos::internal(),
+        )?)
+    }
+
+    /// Defines the global function `name`, which calls the Rust function `fun` with the
+    /// arguments for the parameters `args`
+    ///
+    /// See [`function`] for how the arguments and return value are converted. Errors returned
+    /// by `fun` are raised at the call site, like the errors of intrinsics.
+    pub fn register<Args, F: HostFn<Args>>(&mut self, name: &str, args: &[&str], fun: F) {
+        let mut fun = function(args, fun);
+        name_intrinsic(&mut fun, name);
+        self.set(name, fun);
+    }
+
+    /// Defines the method `name` of the type `type_name`, like `Num`, which calls the Rust
+    /// function `fun` with the receiver and the arguments for the parameters `args`
+    pub fn register_method<Args, F: HostFn<Args>>(
+        &mut self,
+        type_name: &str,
+        name: &str,
+        args: &[&str],
+        fun: F,
+    ) {
+        let mut set = match self.scope.get(type_name) {
+            Some(Literal::Set(set)) => set.clone(),
+            _ => HashMap::new(),
+        };
+        let mut fun = method(args, fun);
+        name_intrinsic(&mut fun, &format!("{}.{}", type_name, name));
+        set.insert(name.to_string(), fun);
+        self.set(type_name, set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_eval() {
+        let mut engine = Engine::new();
+        assert_eq!(engine.eval("1 + 2").unwrap(), Literal::Num(3.0));
+        // The scope persists between evaluations
+        engine.eval("let double = (n) { n * 2 }").unwrap();
+        assert_eq!(engine.eval("double(4)").unwrap(), Literal::Num(8.0));
+    }
+
+    #[test]
+    fn engine_eval_errors() {
+        let mut engine = Engine::new();
+        for src in ["\"unterminated", "let = 5", "let x", "1.add(\"x\")"] {
+            assert!(engine.eval(src).is_err(), "{}", src);
+        }
+        let err = engine.eval_file("does/not/exist.sand").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Cannot read `does/not/exist.sand`"));
+    }
+
+    #[test]
+    fn engine_eval_file() {
+        let mut engine = Engine::new();
+        engine.eval_file("std.sand").unwrap();
+        assert_eq!(engine.eval("5.max(7)").unwrap(), Literal::Num(7.0));
+    }
+
+    #[test]
+    fn engine_get_set() {
+        let mut engine = Engine::new();
+        engine.set("name", "sand");
+        engine.set("nums", vec![1.0, 2.0]);
+        let mut set = HashMap::new();
+        set.insert("x".to_string(), true);
+        engine.set("flags", set);
+        engine.eval(r#"let greeting = "hi {name}""#).unwrap();
+        assert_eq!(engine.get::<String>("greeting").unwrap(), "hi sand");
+        assert_eq!(engine.get::<Vec<f64>>("nums").unwrap(), vec![1.0, 2.0]);
+        assert!(engine.get::<HashMap<String, bool>>("flags").unwrap()["x"]);
+        assert!(engine.get::<f64>("name").is_err());
+        assert!(engine.get::<f64>("missing").is_err());
+    }
+
+    #[test]
+    fn engine_register() {
+        let mut engine = Engine::new();
+        engine.register("hypot", &["a", "b"], |a: f64, b: f64| {
+            (a * a + b * b).sqrt()
+        });
+        assert_eq!(engine.eval("hypot(3, 4)").unwrap(), Literal::Num(5.0));
+        let err = engine.eval("hypot(3)").unwrap_err();
+        assert!(err.to_string().contains("`hypot` takes 2 arguments"));
+        let err = engine.eval("\n  hypot(3, True)").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("eval:2:3: INTERPRETING_ERROR: Literal is not a number"));
+        assert_eq!(
+            engine
+                .call("hypot", vec![Literal::from(6.0), Literal::from(8.0)])
+                .unwrap(),
+            Literal::Num(10.0)
+        );
+    }
+
+    #[test]
+    fn engine_register_method() {
+        let mut engine = Engine::new();
+        engine.register_method("Num", "double", &[], |n: f64| n * 2.0);
+        assert_eq!(engine.eval("21.double").unwrap(), Literal::Num(42.0));
+        assert_eq!(engine.eval("1 + 2.double").unwrap(), Literal::Num(5.0));
+        // Registering a method keeps the other methods of the type
+        assert_eq!(engine.eval("2.add(3)").unwrap(), Literal::Num(5.0));
+    }
+
+    #[test]
+    fn engine_capabilities() {
+        let mut engine = Engine::with_capabilities(Capabilities::none());
+        for (src, msg) in [
+            ("Main.write(1, 5)", "has no member `write`"),
+            ("Main.dump()", "has no member `dump`"),
+            (r#"Fs.read("std.sand")"#, "Variable `Fs` not in scope"),
+            (r#"Process.env("HOME")"#, "Variable `Process` not in scope"),
+        ] {
+            let err = engine.eval(src).unwrap_err();
+            assert!(err.to_string().ends_with(msg), "{}", err);
+        }
+        let mut engine = Engine::new();
+        let std = engine.eval(r#"Fs.read("std.sand")"#).unwrap();
+        assert!(std.as_str().unwrap().contains("let print"));
+        // Even with every capability, programs can't change files or run commands
+        for src in [r#"Fs.write("a", "b")"#, r#"Process.run("ls", [])"#] {
+            assert!(engine.eval(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn engine_small_stack() {
+        // Far less stack than the default recursion limit needs without growing it
+        let thread = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let mut engine = Engine::new();
+                engine
+                    .eval("let sum = (n) { match n { 0 => 0, n => n + sum(n - 1) } }")
+                    .unwrap();
+                engine.eval("sum(999)").unwrap() == Literal::Num(499500.0)
+            });
+        assert!(thread.unwrap().join().unwrap());
+    }
+
+    #[test]
+    fn engine_limits_per_eval() {
+        let mut engine = Engine::new();
+        engine.set_limits(Limits {
+            steps: Some(200),
+            ..Limits::default()
+        });
+        engine
+            .eval("let f = (n) { match n { 0 => 0, n => f(n - 1) } }")
+            .unwrap();
+        // Each evaluation gets all of the steps
+        for _ in 0..3 {
+            engine.eval("f(5)").unwrap();
+        }
+        assert!(engine.eval("f(100)").is_err());
+    }
+}
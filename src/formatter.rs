@@ -0,0 +1,623 @@
+//! The canonical style of sand code, which `sand fmt` rewrites files in
+//!
+//! Files are formatted from their tokens, so only the whitespace between tokens changes, along
+//! with how literals are written. Blocks and match arms which span several lines get a line per
+//! statement or arm, while ones written on a single line stay on it. Comments and single empty
+//! lines between statements are kept.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::path::Path;
+
+use crate::parser::KEYWORDS;
+use crate::tokenizer::*;
+use crate::FilePos;
+
+const INDENT: &str = "    ";
+
+/// Operators written with more than one character, which are tokenized one character at a time
+const OPERATORS: &[&str] = &["...", "==", "!=", "<=", ">=", "&&", "||", "**", "=>"];
+
+/// Formats the source of a file, failing if it can't be tokenized
+pub fn format_str(source: &str, file: &Path) -> Result<String, TokenError> {
+    let start = FilePos::new(file, 1, 1);
+    let (tokens, comments) = tokenize_with_comments(source, start.clone())?;
+    let count = comments.len();
+    let mut formatter = Formatter::new(comments);
+    formatter.block_body(&tokens, usize::MAX);
+    let formatted = formatter.out;
+
+    // Formatting must never change what the file means
+    let (new_tokens, new_comments) = tokenize_with_comments(&formatted, start.clone())?;
+    if !same_tokens(&tokens, &new_tokens) || new_comments.len() != count {
+        return Err(TokenError::new(
+            "Formatting would change the tokens of the file",
+            &start,
+        ));
+    }
+    Ok(formatted)
+}
+
+/// A token, or the characters of an operator joined together
+enum Item<'a> {
+    Token(&'a Token),
+    Op {
+        op: String,
+        pos: FilePos,
+        /// `-` and `!` in front of a value, rather than between two
+        unary: bool,
+    },
+}
+
+impl Item<'_> {
+    fn pos(&self) -> &FilePos {
+        match self {
+            Self::Token(token) => &token.pos,
+            Self::Op { pos, .. } => pos,
+        }
+    }
+
+    fn end_row(&self) -> usize {
+        match self {
+            Self::Token(token) => token.end_row(),
+            Self::Op { pos, .. } => pos.row,
+        }
+    }
+
+    fn is_op(&self, text: &str) -> bool {
+        matches!(self, Self::Op { op, .. } if op == text)
+    }
+
+    fn is_group(&self, group: GroupType) -> bool {
+        matches!(self, Self::Token(Token { r#type: TokenType::Group { r#type, .. }, .. }) if *r#type == group)
+    }
+
+    /// Whether a value can end with this item, so an operator after it is binary
+    fn ends_value(&self) -> bool {
+        match self {
+            Self::Token(Token {
+                r#type: TokenType::String(word),
+                ..
+            }) => {
+                !KEYWORDS.contains(&word.as_str())
+                    || ["Nope", "True", "False"].contains(&word.as_str())
+            }
+            Self::Token(..) => true,
+            Self::Op { op, .. } => op == "?",
+        }
+    }
+
+    /// The column after the item, if it is known from the token alone
+    fn end_col(&self) -> Option<usize> {
+        match self {
+            Self::Token(Token {
+                r#type: TokenType::String(word),
+                pos,
+            }) => Some(pos.col + word.chars().count()),
+            Self::Token(Token {
+                r#type: TokenType::Group { end, .. },
+                ..
+            }) => Some(end.col + 1),
+            Self::Op { op, pos, .. } => Some(pos.col + op.chars().count()),
+            Self::Token(..) => None,
+        }
+    }
+}
+
+/// Joins the characters of operators like `==` which are written without spaces between them
+fn items(tokens: &[Token]) -> Vec<Item<'_>> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if let TokenType::Char(c) = token.r#type {
+            let op = OPERATORS
+                .iter()
+                .find(|op| written_together(&tokens[i..], op))
+                .map(|op| op.to_string())
+                .unwrap_or_else(|| c.to_string());
+            let unary = (op == "-" || op == "!") && !items.last().is_some_and(Item::ends_value);
+            i += op.chars().count();
+            items.push(Item::Op {
+                op,
+                pos: token.pos.clone(),
+                unary,
+            });
+        } else {
+            items.push(Item::Token(token));
+            i += 1;
+        }
+    }
+    items
+}
+
+fn written_together(tokens: &[Token], op: &str) -> bool {
+    let start = &tokens[0].pos;
+    op.chars().enumerate().all(|(i, c)| {
+        tokens.get(i).is_some_and(|token| {
+            token.r#type == TokenType::Char(c)
+                && token.pos.row == start.row
+                && token.pos.col == start.col + i
+        })
+    })
+}
+
+/// Whether there is a space between two items of a line
+fn spaced(prev: Option<&Item>, item: &Item, next: Option<&Item>) -> bool {
+    let prev = match prev {
+        Some(prev) => prev,
+        None => return false,
+    };
+    if [",", ";", ".", "?", ":"].iter().any(|op| item.is_op(op)) {
+        return false;
+    }
+    if let Item::Op { op, unary, .. } = prev {
+        if op == "." || op == "..." || *unary {
+            return false;
+        }
+    }
+    // Calls, except for functions passed without parenthesis like `5.times (n) { ... }`, unless
+    // the function is written right after the value it is passed to
+    if item.is_group(GroupType::Paren) && prev.ends_value() {
+        let together = prev.end_col() == Some(item.pos().col) && prev.end_row() == item.pos().row;
+        let function = next.is_some_and(|next| next.is_group(GroupType::Curly));
+        return function && !together;
+    }
+    true
+}
+
+/// Splits tokens at a separating character, which each part but maybe the last ends with
+fn split(tokens: &[Token], sep: char) -> Vec<(&[Token], Option<&Token>)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.r#type == TokenType::Char(sep) {
+            parts.push((&tokens[start..i], Some(token)));
+            start = i + 1;
+        }
+    }
+    if start < tokens.len() {
+        parts.push((&tokens[start..], None));
+    }
+    parts
+}
+
+/// Whether any of the tokens, or the tokens in their groups, is a `;`
+fn has_semicolon(tokens: &[Token]) -> bool {
+    tokens.iter().any(|token| match &token.r#type {
+        TokenType::Char(';') => true,
+        TokenType::Group { tokens, .. } => has_semicolon(tokens),
+        _ => false,
+    })
+}
+
+fn same_tokens(a: &[Token], b: &[Token]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| match (&a.r#type, &b.r#type) {
+            (
+                TokenType::Group {
+                    r#type: a_type,
+                    tokens: a,
+                    ..
+                },
+                TokenType::Group {
+                    r#type: b_type,
+                    tokens: b,
+                    ..
+                },
+            ) => a_type == b_type && same_tokens(a, b),
+            (TokenType::FormatStr(a), TokenType::FormatStr(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|parts| match parts {
+                        (
+                            StrPart::Value {
+                                tokens: a,
+                                spec: a_spec,
+                            },
+                            StrPart::Value {
+                                tokens: b,
+                                spec: b_spec,
+                            },
+                        ) => a_spec == b_spec && same_tokens(a, b),
+                        (a, b) => a == b,
+                    })
+            }
+            (a, b) => a == b,
+        })
+}
+
+fn escape(str: &str) -> String {
+    str.chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '"' => "\\\"".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            '\r' => "\\r".to_string(),
+            '{' => "\\{".to_string(),
+            '}' => "\\}".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// How a token which isn't a group is written
+fn literal(token: &Token) -> String {
+    match &token.r#type {
+        TokenType::String(word) => word.clone(),
+        TokenType::Char(c) => c.to_string(),
+        TokenType::Number(n) => n.to_string(),
+        TokenType::StringLit(str) => format!("\"{}\"", escape(str)),
+        TokenType::CharLit(c) => match c {
+            '\n' => "'\\n'".to_string(),
+            '\t' => "'\\t'".to_string(),
+            '\r' => "'\\r'".to_string(),
+            '\'' => "'\\''".to_string(),
+            c => format!("'{}'", c),
+        },
+        TokenType::FormatStr(parts) => {
+            let mut str = String::from('"');
+            for part in parts {
+                match part {
+                    StrPart::Str(part) => str.push_str(&escape(part)),
+                    StrPart::Value { tokens, spec } => {
+                        let mut formatter = Formatter::new(Vec::new());
+                        formatter.items(tokens, false);
+                        str.push('{');
+                        str.push_str(&formatter.out);
+                        if let Some(spec) = spec {
+                            str.push(':');
+                            str.push_str(spec);
+                        }
+                        str.push('}');
+                    }
+                }
+            }
+            str.push('"');
+            str
+        }
+        TokenType::Group { .. } => unreachable!("Groups are formatted by the formatter"),
+    }
+}
+
+struct Formatter {
+    out: String,
+    indent: usize,
+    /// The comments which haven't been written yet
+    comments: VecDeque<Comment>,
+    /// The row of the last token or comment which was written
+    last_row: usize,
+    /// Whether nothing has been written on the current line yet
+    line_empty: bool,
+    /// Whether nothing has been written in the current block yet
+    block_start: bool,
+    /// Whether the current value continues on more indented lines, like a method chain does
+    continued: bool,
+}
+
+impl Formatter {
+    fn new(comments: Vec<Comment>) -> Self {
+        Self {
+            out: String::new(),
+            indent: 0,
+            comments: comments.into(),
+            last_row: 0,
+            line_empty: true,
+            block_start: true,
+            continued: false,
+        }
+    }
+
+    fn write(&mut self, text: &str, space: bool) {
+        if self.line_empty {
+            self.out.push_str(&INDENT.repeat(self.indent));
+        } else if space {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.line_empty = false;
+        self.block_start = false;
+    }
+
+    fn newline(&mut self) {
+        if !self.line_empty {
+            self.out.push('\n');
+            self.line_empty = true;
+        }
+    }
+
+    /// Keeps a single empty line where the source has empty lines before `row`
+    fn gap(&mut self, row: usize) {
+        if row > self.last_row + 1 && !self.block_start {
+            self.newline();
+            self.out.push('\n');
+        }
+    }
+
+    /// Whether there are comments before `row` which haven't been written yet
+    fn has_comments(&self, row: usize) -> bool {
+        self.comments
+            .front()
+            .is_some_and(|comment| comment.pos.row < row)
+    }
+
+    /// Writes the comments before `row`, after the code on their line or on their own line
+    fn comments_before(&mut self, row: usize) {
+        while self.has_comments(row) {
+            let comment = self.comments.pop_front().unwrap();
+            let text = comment.text.trim_end();
+            if !self.line_empty && comment.pos.row == self.last_row {
+                self.write(text, true);
+            } else {
+                self.newline();
+                self.gap(comment.pos.row);
+                self.write(text, false);
+            }
+            self.newline();
+            self.last_row = comment.pos.row;
+        }
+    }
+
+    /// Writes statements on lines of their own, up to the row their block ends on
+    fn block_body(&mut self, tokens: &[Token], end_row: usize) {
+        for (statement, semicolon) in split(tokens, ';') {
+            if let Some(first) = statement.first() {
+                self.comments_before(first.pos.row);
+                self.newline();
+                self.gap(first.pos.row);
+                self.items(statement, false);
+            }
+            if let Some(semicolon) = semicolon {
+                self.separator(semicolon);
+            }
+        }
+        self.comments_before(end_row);
+        self.newline();
+    }
+
+    /// Writes a `;` or `,` right after what it separates, even if it is on a line of its own
+    fn separator(&mut self, sep: &Token) {
+        if let TokenType::Char(c) = sep.r#type {
+            self.write(&c.to_string(), false);
+        }
+        self.last_row = sep.pos.row;
+    }
+
+    /// Writes tokens on the current line, with `space` before the first one
+    fn items(&mut self, tokens: &[Token], space: bool) {
+        let continued = mem::replace(&mut self.continued, false);
+        let items = items(tokens);
+        for (i, item) in items.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| &items[i]);
+            self.comments_before(item.pos().row);
+            // A line starting with `.` continues the value of the line before it
+            if item.is_op(".") && item.pos().row > self.last_row {
+                self.newline();
+                if !self.continued {
+                    self.continued = true;
+                    self.indent += 1;
+                }
+            }
+            let space = match prev {
+                Some(..) => spaced(prev, item, items.get(i + 1)),
+                None => space,
+            };
+            match item {
+                Item::Token(token) => self.token(token, prev, space),
+                Item::Op { op, .. } => self.write(op, space),
+            }
+            self.last_row = item.end_row();
+        }
+        if self.continued {
+            self.indent -= 1;
+        }
+        self.continued = continued;
+    }
+
+    fn token(&mut self, token: &Token, prev: Option<&Item>, space: bool) {
+        let (group, tokens, end) = match &token.r#type {
+            TokenType::Group {
+                r#type,
+                tokens,
+                end,
+            } => (r#type, tokens, end.row),
+            _ => return self.write(&literal(token), space),
+        };
+        let start = token.pos.row;
+        let single_line = start == end && !has_semicolon(tokens);
+        match group {
+            GroupType::Paren => self.list(("(", ")"), tokens, end, space, false),
+            GroupType::Brack => self.list(("[", "]"), tokens, end, space, false),
+            GroupType::Curly if tokens.is_empty() && !self.has_comments(end) => {
+                self.write("{}", space)
+            }
+            GroupType::Curly if items(tokens).iter().any(|item| item.is_op("=>")) => {
+                if single_line {
+                    self.list(("{", "}"), tokens, end, space, true)
+                } else {
+                    self.match_body(tokens, end, space)
+                }
+            }
+            GroupType::Curly if is_pattern(prev) => self.list(("{", "}"), tokens, end, space, true),
+            GroupType::Curly if single_line => {
+                self.write("{", space);
+                self.items(tokens, true);
+                self.write("}", true);
+            }
+            GroupType::Curly => {
+                self.write("{", space);
+                self.indent += 1;
+                self.block_start = true;
+                self.block_body(tokens, end);
+                self.indent -= 1;
+                self.write("}", false);
+            }
+        }
+    }
+
+    /// Writes items separated by `,` on one line, or on a line each if there are comments
+    /// between them. `padded` puts spaces inside of the brackets.
+    fn list(
+        &mut self,
+        (open, close): (&str, &str),
+        tokens: &[Token],
+        end: usize,
+        space: bool,
+        padded: bool,
+    ) {
+        self.write(open, space);
+        if self.has_comments(end) {
+            self.indent += 1;
+            for (item, comma) in split(tokens, ',') {
+                if let Some(first) = item.first() {
+                    self.comments_before(first.pos.row);
+                    self.newline();
+                    self.items(item, false);
+                }
+                if let Some(comma) = comma {
+                    self.separator(comma);
+                }
+            }
+            self.comments_before(end);
+            self.newline();
+            self.indent -= 1;
+            self.write(close, false);
+        } else {
+            for (i, (item, comma)) in split(tokens, ',').into_iter().enumerate() {
+                self.items(item, i > 0 || padded);
+                if let Some(comma) = comma {
+                    self.separator(comma);
+                }
+            }
+            self.write(close, padded);
+        }
+    }
+
+    /// Writes the arms of a match on a line each
+    fn match_body(&mut self, tokens: &[Token], end: usize, space: bool) {
+        self.write("{", space);
+        self.indent += 1;
+        for (arm, comma) in split(tokens, ',') {
+            if let Some(first) = arm.first() {
+                self.comments_before(first.pos.row);
+                self.newline();
+                self.items(arm, false);
+            }
+            if let Some(comma) = comma {
+                self.separator(comma);
+            }
+        }
+        self.comments_before(end);
+        self.newline();
+        self.indent -= 1;
+        self.write("}", false);
+    }
+}
+
+/// Whether `{` after `prev` starts a set pattern rather than a block
+fn is_pattern(prev: Option<&Item>) -> bool {
+    match prev {
+        None => true,
+        Some(Item::Op { op, .. }) => [",", ":", "..."].contains(&op.as_str()),
+        Some(Item::Token(Token {
+            r#type: TokenType::String(word),
+            ..
+        })) => word == "let" || word == "for",
+        Some(..) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn format(source: &str) -> String {
+        format_str(source, Path::new("test.sand")).unwrap()
+    }
+
+    #[test]
+    fn format_spacing() {
+        for (source, formatted) in [
+            ("let x=1+2*-3", "let x = 1 + 2 * -3\n"),
+            ("f (a,b) ;g(  )", "f(a, b);\ng()\n"),
+            ("x==y&&!z", "x == y && !z\n"),
+            ("5 .times (n) { n }", "5.times (n) { n }\n"),
+            ("let [a,...rest]=l", "let [a, ...rest] = l\n"),
+            ("let {x,y:0}=p", "let { x, y: 0 } = p\n"),
+            (
+                "Main.write (1,\"a\\n{x:>3}\")",
+                "Main.write(1, \"a\\n{x:>3}\")\n",
+            ),
+            ("f(x)?", "f(x)?\n"),
+            ("return -x", "return -x\n"),
+        ] {
+            assert_eq!(format(source), formatted, "{}", source);
+        }
+    }
+
+    #[test]
+    fn format_blocks() {
+        let source = "let f = (n) {\nlet a = n;\n\n\n  a };\nlet g = (n) { n };";
+        let formatted = "let f = (n) {\n    let a = n;\n\n    a\n};\nlet g = (n) { n };\n";
+        assert_eq!(format(source), formatted);
+
+        let source = "match n {\n0 => a, n => b }";
+        let formatted = "match n {\n    0 => a,\n    n => b\n}\n";
+        assert_eq!(format(source), formatted);
+        assert_eq!(
+            format("match n { 0 => a, _ => b }"),
+            "match n { 0 => a, _ => b }\n"
+        );
+
+        let source = "list\n.map(f)\n  .len";
+        assert_eq!(format(source), "list\n    .map(f)\n    .len\n");
+    }
+
+    #[test]
+    fn format_separators_on_their_own_line() {
+        for (source, formatted) in [
+            ("let x = 1\n;\nlet y = 2", "let x = 1;\nlet y = 2\n"),
+            (
+                "let f = () {\n    a\n    ;\n    b\n}",
+                "let f = () {\n    a;\n    b\n}\n",
+            ),
+            (";\nlet x = 1", ";\nlet x = 1\n"),
+            (
+                "let x = 1\n; # one\nlet y = 2",
+                "let x = 1; # one\nlet y = 2\n",
+            ),
+            (
+                "match n {\n0 => a\n,\nn => b }",
+                "match n {\n    0 => a,\n    n => b\n}\n",
+            ),
+            // Empty lines after the separator are still kept
+            ("let x = 1\n;\n\nlet y = 2", "let x = 1;\n\nlet y = 2\n"),
+        ] {
+            assert_eq!(format(source), formatted, "{}", source);
+        }
+    }
+
+    #[test]
+    fn format_comments() {
+        let source = "# Header\n\nlet x = 1; # one\n# About y\nlet y = (\n  a, # first\n  b\n);";
+        let formatted =
+            "# Header\n\nlet x = 1; # one\n# About y\nlet y = (\n    a, # first\n    b\n);\n";
+        assert_eq!(format(source), formatted);
+    }
+
+    #[test]
+    fn format_is_idempotent() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut files = vec![root.join("std.sand")];
+        for entry in fs::read_dir(root.join("examples")).unwrap() {
+            files.push(entry.unwrap().path());
+        }
+        for file in files {
+            let source = fs::read_to_string(&file).unwrap();
+            let formatted = format_str(&source, &file).unwrap();
+            assert_eq!(formatted, source, "{} isn't formatted", file.display());
+            assert_eq!(format_str(&formatted, &file).unwrap(), formatted);
+        }
+    }
+}
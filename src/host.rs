@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::interpreter::{lookup, InterpretingError, Scope};
+use crate::types::*;
+
+/// Rust types which arguments of host functions are converted to
+pub trait FromLiteral: Sized {
+    fn from_literal(lit: Literal) -> Result<Self, TypeError>;
+}
+
+impl FromLiteral for Literal {
+    fn from_literal(lit: Literal) -> Result<Self, TypeError> {
+        Ok(lit)
+    }
+}
+
+macro_rules! impl_from_literal {
+    ($($type:ty),*) => {
+        $(
+            impl FromLiteral for $type {
+                fn from_literal(lit: Literal) -> Result<Self, TypeError> {
+                    Self::try_from(lit)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_literal!((), String, char, f64, i64, bool, Callable);
+
+impl<T: FromLiteral> FromLiteral for Vec<T> {
+    fn from_literal(lit: Literal) -> Result<Self, TypeError> {
+        lit.as_list()?.into_iter().map(T::from_literal).collect()
+    }
+}
+
+impl<T: FromLiteral> FromLiteral for HashMap<String, T> {
+    fn from_literal(lit: Literal) -> Result<Self, TypeError> {
+        lit.as_set()?
+            .into_iter()
+            .map(|(k, v)| Ok((k, T::from_literal(v)?)))
+            .collect()
+    }
+}
+
+/// Rust types which the return values of host functions are converted from
+pub trait IntoResult {
+    fn into_result(self) -> Result<Literal, InterpretingError>;
+}
+
+impl<T: Into<Literal>> IntoResult for T {
+    fn into_result(self) -> Result<Literal, InterpretingError> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Literal>> IntoResult for Result<T, InterpretingError> {
+    fn into_result(self) -> Result<Literal, InterpretingError> {
+        self.map(Into::into)
+    }
+}
+
+/// Marks host functions which take the scope they are called in as their first argument
+pub struct Scoped;
+
+/// Rust functions and closures which can be called from sand
+///
+/// `Args` is the tuple of argument types, which only exists to tell the implementations for
+/// different numbers of arguments apart.
+pub trait HostFn<Args>: 'static {
+    /// How many values the function takes, not counting the scope
+    const ARITY: usize;
+
+    fn call(&self, scope: &mut Scope, args: Vec<Literal>) -> Result<Literal, InterpretingError>;
+}
+
+macro_rules! impl_host_fn {
+    ($($arg:ident),*) => {
+        impl<Fun, Ret, $($arg),*> HostFn<($($arg,)*)> for Fun
+        where
+            Fun: Fn($($arg),*) -> Ret + 'static,
+            Ret: IntoResult,
+            $($arg: FromLiteral,)*
+        {
+            const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(
+                &self,
+                _scope: &mut Scope,
+                args: Vec<Literal>,
+            ) -> Result<Literal, InterpretingError> {
+                let mut args = args.into_iter();
+                $(let $arg = $arg::from_literal(args.next().unwrap())?;)*
+                self($($arg),*).into_result()
+            }
+        }
+
+        impl<Fun, Ret, $($arg),*> HostFn<(Scoped, $($arg,)*)> for Fun
+        where
+            Fun: Fn(&mut Scope, $($arg),*) -> Ret + 'static,
+            Ret: IntoResult,
+            $($arg: FromLiteral,)*
+        {
+            const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(
+                &self,
+                scope: &mut Scope,
+                args: Vec<Literal>,
+            ) -> Result<Literal, InterpretingError> {
+                let mut args = args.into_iter();
+                $(let $arg = $arg::from_literal(args.next().unwrap())?;)*
+                self(scope, $($arg),*).into_result()
+            }
+        }
+    };
+}
+
+impl_host_fn!();
+impl_host_fn!(A);
+impl_host_fn!(A, B);
+impl_host_fn!(A, B, C);
+impl_host_fn!(A, B, C, D);
+impl_host_fn!(A, B, C, D, E);
+
+fn intrinsic<Args, F: HostFn<Args>>(args: &[&str], receiver: bool, fun: F) -> Literal {
+    assert_eq!(
+        F::ARITY,
+        args.len() + receiver as usize,
+        "The host function takes a different number of arguments than it has names for"
+    );
+    let mut names: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    if receiver {
+        names.insert(0, "self".to_string());
+    }
+    Literal::Fun(Callable::Intr(Intrinsic {
+        args: args.iter().map(|arg| Var::new(arg)).collect(),
+        fun_interpret: Rc::new(move |scope: &mut Scope| {
+            let vals = names
+                .iter()
+                .map(|name| lookup(scope, name))
+                .collect::<Result<_, _>>()?;
+            fun.call(scope, vals)
+        }),
+        name: None,
+    }))
+}
+
+/// Turns a Rust function into an intrinsic taking the parameters `args`
+///
+/// The arguments are converted to the parameter types of `fun`, failing with a type error if
+/// they can't be, and the return value is converted back into a literal. `fun` can also take
+/// the scope it is called in as its first argument.
+pub fn function<Args, F: HostFn<Args>>(args: &[&str], fun: F) -> Literal {
+    intrinsic(args, false, fun)
+}
+
+/// Like [`function`], but `fun` takes the receiver of the method as its first argument, after
+/// the scope
+pub fn method<Args, F: HostFn<Args>>(args: &[&str], fun: F) -> Literal {
+    intrinsic(args, true, fun)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::call;
+    use crate::FilePos;
+
+    fn call_host(fun: Literal, receiver: Option<Literal>, args: Vec<Literal>) -> Literal {
+        let pos = FilePos::internal();
+        call(fun, None, receiver, args, &mut Scope::new(), &pos).unwrap()
+    }
+
+    #[test]
+    fn host_function() {
+        let hypot = function(&["a", "b"], |a: f64, b: f64| (a * a + b * b).sqrt());
+        let args = vec![Literal::Num(3.0), Literal::Num(4.0)];
+        assert_eq!(call_host(hypot, None, args), Literal::Num(5.0));
+
+        let join = function(&["list"], |list: Vec<String>| list.join(", "));
+        let args = vec![Literal::from(vec!["a", "b"])];
+        assert_eq!(call_host(join, None, args), Literal::from("a, b"));
+    }
+
+    #[test]
+    fn host_method() {
+        let repeat = method(&["n"], |selff: String, n: i64| selff.repeat(n as usize));
+        let receiver = Some(Literal::from("ab"));
+        let args = vec![Literal::Num(2.0)];
+        assert_eq!(call_host(repeat, receiver, args), Literal::from("abab"));
+    }
+
+    #[test]
+    fn host_function_scoped() {
+        let get = function(&["name"], |scope: &mut Scope, name: String| {
+            lookup(scope, &name)
+        });
+        let mut scope = Scope::new();
+        scope.insert("x".to_string(), Literal::Num(1.0));
+        let args = vec![Literal::from("x")];
+        let pos = FilePos::internal();
+        assert_eq!(
+            call(get, None, None, args, &mut scope, &pos).unwrap(),
+            Literal::Num(1.0)
+        );
+    }
+
+    #[test]
+    fn host_function_type_error() {
+        let neg = function(&["n"], |n: f64| -n);
+        let pos = FilePos::internal();
+        let err = call(
+            neg,
+            None,
+            None,
+            vec![Literal::Bool(true)],
+            &mut Scope::new(),
+            &pos,
+        )
+        .unwrap_err();
+        assert_eq!(err.msg, "Literal is not a number");
+        let fail = function(&[], || -> Result<(), InterpretingError> {
+            Err(InterpretingError::new("failed", &FilePos::temp()))
+        });
+        let err = call(fail, None, None, Vec::new(), &mut Scope::new(), &pos).unwrap_err();
+        assert_eq!(err.msg, "failed");
+    }
+
+    #[test]
+    #[should_panic]
+    fn host_function_arity_mismatch() {
+        function(&["a"], |a: f64, b: f64| a + b);
+    }
+}
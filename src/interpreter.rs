@@ -1,19 +1,258 @@
+use std::cell::RefCell;
+use std::collections::hash_map;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::intrinsics::init_scope;
+use crate::coverage::Coverage;
+use crate::intrinsics::init_scope_with;
+use crate::intrinsics::Capabilities;
 use crate::parser::parse_file;
+use crate::profiler::{Callee, Profiler};
 use crate::tokenizer::tokenize_str;
 use crate::types::*;
 use crate::FilePos;
-use crate::SandError;
 
-pub type Scope = HashMap<String, Literal>;
+/// A call on the interpreter's call stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The name the callee was called by, if it was called by name
+    pub name: Option<String>,
+    /// Where the call was made from
+    pub pos: FilePos,
+    /// The call this one took the place of by being a tail call, or the call that one took the
+    /// place of and so on, which is the last call on the stack which wasn't a tail call
+    pub replaced: Option<Box<Frame>>,
+    /// How many tail calls led from `replaced` to this call
+    pub tail_calls: usize,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}: in `{}`", self.pos, name),
+            None => write!(f, "{}: in anonymous function", self.pos),
+        }
+    }
+}
+
+/// How many calls can be nested by default
+pub const RECURSION_LIMIT: usize = 1000;
+
+/// How many bytes of stack nested calls can grow onto the heap by default
+pub const STACK_SIZE: usize = 1 << 30;
+
+/// How much stack a call needs left to run on the stack of its caller. Calls which would have
+/// less continue on a new stack of `STACK_SEGMENT` bytes on the heap, so calls can be nested up
+/// to `Limits::call_depth` on any thread, whatever the size of its stack.
+const STACK_RED_ZONE: usize = 1 << 20;
+const STACK_SEGMENT: usize = 1 << 24;
+
+/// Limits on the resources a program can use, which fail the program when exceeded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// How many calls can be nested
+    pub call_depth: usize,
+    /// How many bytes of stack nested calls can grow onto the heap, on top of the stack of the
+    /// thread they run on. Every call takes up to tens of kilobytes of stack, so this is what
+    /// fails deep recursion when `call_depth` is too large to be reached.
+    pub stack_size: usize,
+    /// How many values and statements can be evaluated
+    pub steps: Option<u64>,
+    /// How many items a list, or bytes a string, created by a call or string interpolation
+    /// can have
+    pub size: Option<usize>,
+    /// How long evaluation can take
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            call_depth: RECURSION_LIMIT,
+            stack_size: STACK_SIZE,
+            steps: None,
+            size: None,
+            timeout: None,
+        }
+    }
+}
+
+/// Pauses a program before its statements, to let it be inspected
+pub trait Debugger {
+    /// Called before the statement at `pos` is interpreted in `scope`
+    ///
+    /// The debugger is not called for statements interpreted while it runs, like the ones of
+    /// values it evaluates in the scope.
+    fn statement(&mut self, pos: &FilePos, scope: &mut Scope) -> Result<(), InterpretingError>;
+}
+
+impl fmt::Debug for dyn Debugger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Debugger")
+    }
+}
+
+/// Interpreter state shared between all scopes of a program
+#[derive(Debug, Default)]
+pub struct State {
+    pub stack: Vec<Frame>,
+    pub limits: Limits,
+    /// What the program was allowed to use when its scope was created
+    pub capabilities: Capabilities,
+    pub debugger: Option<Box<dyn Debugger>>,
+    pub profiler: Option<Profiler>,
+    pub coverage: Option<Coverage>,
+    steps: u64,
+    /// How many bytes of stack calls have grown onto the heap
+    grown_stack: usize,
+    deadline: Option<Instant>,
+}
+
+impl State {
+    /// Counts steps and time towards the limits from zero
+    pub fn restart(&mut self) {
+        self.steps = 0;
+        self.deadline = None;
+    }
+}
+
+/// Counts a step of evaluation, and fails if the program has run for too long
+fn step(scope: &Scope) -> Result<(), InterpretingError> {
+    let mut state = scope.state.borrow_mut();
+    state.steps += 1;
+    if let Some(max) = state.limits.steps {
+        if state.steps > max {
+            return Err(InterpretingError::limit(&format!(
+                "Exceeded the limit of {} steps",
+                max
+            )));
+        }
+    }
+    if let Some(timeout) = state.limits.timeout {
+        let deadline = *state
+            .deadline
+            .get_or_insert_with(|| Instant::now() + timeout);
+        if Instant::now() > deadline {
+            return Err(InterpretingError::limit(&format!(
+                "Exceeded the time limit of {:?}",
+                timeout
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Records `statement` for coverage, and lets the debugger pause before it
+fn before_statement(statement: &Statement, scope: &mut Scope) -> Result<(), InterpretingError> {
+    let pos = match statement.get_pos() {
+        Some(pos) => pos,
+        None => return Ok(()),
+    };
+    let debugger = {
+        let mut state = scope.state.borrow_mut();
+        if let Some(coverage) = &mut state.coverage {
+            coverage.statement(pos);
+        }
+        // Taken out while it runs, so it isn't called again for what it evaluates
+        state.debugger.take()
+    };
+    match debugger {
+        Some(mut debugger) => {
+            let res = debugger.statement(pos, scope);
+            scope.state.borrow_mut().debugger = Some(debugger);
+            res
+        }
+        None => Ok(()),
+    }
+}
+
+/// Fails if `val` is a list or string larger than the size limit
+fn check_size(val: &Literal, scope: &Scope) -> Result<(), InterpretingError> {
+    match val {
+        Literal::List(list) => check_len(val.type_name(), list.len(), "items", scope),
+        Literal::Str(str) => check_len(val.type_name(), str.len(), "bytes", scope),
+        _ => Ok(()),
+    }
+}
+
+/// Fails if a list or string of `size` items or bytes would be larger than the size limit, so
+/// it can be checked before it is allocated
+fn check_len(
+    type_name: &str,
+    size: usize,
+    unit: &str,
+    scope: &Scope,
+) -> Result<(), InterpretingError> {
+    match scope.state.borrow().limits.size {
+        Some(max) if size > max => Err(InterpretingError::limit(&format!(
+            "A {} of {} {} exceeds the size limit of {}",
+            type_name, size, unit, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    vars: HashMap<String, Literal>,
+    pub state: Rc<RefCell<State>>,
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Literal> {
+        self.vars.get(name)
+    }
+
+    pub fn insert(&mut self, name: String, val: Literal) {
+        self.vars.insert(name, val);
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.vars.contains_key(name)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, String, Literal> {
+        self.vars.iter()
+    }
+
+    /// Returns a copy of the current call stack, outermost call first
+    pub fn stack(&self) -> Vec<Frame> {
+        self.state.borrow().stack.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// A runtime error from the interpreter or an intrinsic
+    Error,
+    /// A value raised from sand code, either by `Main.raise` or `?`
+    Raise(Box<Literal>),
+    /// An unrecoverable error from `Main.panic`, which `try` does not catch
+    Panic,
+    /// `break`, which unwinds to the innermost loop
+    Break,
+    /// `continue`, which unwinds to the innermost loop
+    Continue,
+    /// `return`, which unwinds to the innermost function with the value it returns
+    Return(Box<Literal>),
+    /// An exceeded limit, which `try` does not catch
+    Limit,
+}
 
 #[derive(Debug)]
 pub struct InterpretingError {
     pub msg: String,
     pub pos: FilePos,
+    pub kind: ErrorKind,
+    /// The call stack at the point the error was raised, outermost call first
+    pub trace: Vec<Frame>,
 }
 
 impl InterpretingError {
@@ -21,10 +260,115 @@ impl InterpretingError {
         Self {
             msg: msg.to_string(),
             pos: pos.clone(),
+            kind: ErrorKind::Error,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn raise(val: Literal, pos: &FilePos) -> Self {
+        let msg = match &val {
+            Literal::Str(str) => str.clone(),
+            val => format!("{:?}", val),
+        };
+        Self {
+            msg: format!("Uncaught error: {}", msg),
+            pos: pos.clone(),
+            kind: ErrorKind::Raise(Box::new(val)),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn panic(msg: &str, pos: &FilePos) -> Self {
+        Self {
+            msg: format!("Panic: {}", msg),
+            pos: pos.clone(),
+            kind: ErrorKind::Panic,
+            trace: Vec::new(),
+        }
+    }
+
+    /// A failed assertion, which can't be caught like a panic
+    pub fn assertion(msg: &str, pos: &FilePos) -> Self {
+        Self {
+            msg: format!("Assertion failed: {}", msg),
+            pos: pos.clone(),
+            kind: ErrorKind::Panic,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn limit(msg: &str) -> Self {
+        Self {
+            msg: msg.to_string(),
+            pos: FilePos::temp(),
+            kind: ErrorKind::Limit,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Control flow out of a loop or function, which is only an error if nothing catches it
+    pub fn control(kind: ErrorKind, pos: &FilePos) -> Self {
+        let msg = match kind {
+            ErrorKind::Break => "`break` outside of a loop",
+            ErrorKind::Continue => "`continue` outside of a loop",
+            _ => "`return` outside of a function",
+        };
+        Self {
+            msg: msg.to_string(),
+            pos: pos.clone(),
+            kind,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Moves errors without a known position to `pos`
+    ///
+    /// Intrinsics and literal conversions don't know where they are called from, so their errors
+    /// are placed at the nearest call site instead.
+    pub fn at(mut self, pos: &FilePos) -> Self {
+        if self.pos == FilePos::temp() || self.pos == FilePos::internal() {
+            self.pos = pos.clone();
         }
+        self
+    }
+
+    /// Turns a catchable error into the value `try` evaluates to
+    pub fn catch(self) -> Result<Literal, InterpretingError> {
+        match self.kind {
+            ErrorKind::Error => Ok(Literal::Err(Box::new(Literal::Str(self.msg)))),
+            ErrorKind::Raise(val) => Ok(Literal::Err(val)),
+            ErrorKind::Panic
+            | ErrorKind::Limit
+            | ErrorKind::Break
+            | ErrorKind::Continue
+            | ErrorKind::Return(..) => Err(self),
+        }
+    }
+
+    /// Turns `return` into the value of the function it returns from
+    pub fn returned(self) -> Result<Literal, InterpretingError> {
+        match self.kind {
+            ErrorKind::Return(val) => Ok(*val),
+            _ => Err(self),
+        }
+    }
+}
+
+impl From<TypeError> for InterpretingError {
+    fn from(type_error: TypeError) -> Self {
+        Self::new(&type_error.msg, &type_error.pos)
     }
 }
 
+pub fn lookup(scope: &Scope, name: &str) -> Result<Literal, InterpretingError> {
+    scope.get(name).cloned().ok_or_else(|| {
+        InterpretingError::new(
+            &format!("Variable `{}` not in scope", name),
+            &FilePos::temp(),
+        )
+    })
+}
+
 pub trait Interpret {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError>;
 }
@@ -32,8 +376,27 @@ pub trait Interpret {
 impl Interpret for Callable {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError> {
         match self {
-            Self::Fun(fun) => fun.body.interpret(scope),
+            Self::Fun(fun) => fun
+                .body
+                .interpret(scope)
+                .or_else(InterpretingError::returned),
             Self::Intr(intr) => (intr.fun_interpret)(scope),
+            Self::Method { fun, receiver } => {
+                scope.insert("self".to_string(), *receiver);
+                fun.interpret(scope)
+            }
+            Self::Constructor(constructor) => {
+                let mut fields = HashMap::new();
+                for param in &constructor.args {
+                    for var in param.pattern.bindings() {
+                        fields.insert(var.name.clone(), lookup(scope, &var.name)?);
+                    }
+                }
+                Ok(Literal::Instance {
+                    type_name: constructor.type_name,
+                    fields,
+                })
+            }
         }
     }
 }
@@ -41,68 +404,790 @@ impl Interpret for Callable {
 impl Interpret for Reference {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError> {
         match self {
-            Self::Var(var) => scope
-                .get(&var.name)
-                .ok_or(InterpretingError::new("Variable not in scope", &var.pos))
-                .map(|val| val.clone()),
-            Self::Member { set, field, .. } => {
-                // let mut set_scope = scope.clone();
-                let set_literal = set.interpret(scope)?;
-                let set_set = match &set_literal {
-                    Literal::Nope => scope.get("Nope").unwrap().clone().as_set().unwrap(),
-                    Literal::Str(str) => {
-                        scope.insert("self".to_string(), Literal::Str(str.clone()));
-                        scope.get("Str").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::Char(char) => {
-                        scope.insert("self".to_string(), Literal::Char(char.clone()));
-                        scope.get("Char").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::Num(num) => {
-                        scope.insert("self".to_string(), Literal::Num(num.clone()));
-                        scope.get("Num").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::Bool(bool) => {
-                        scope.insert("self".to_string(), Literal::Bool(bool.clone()));
-                        scope.get("Bool").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::List(list) => {
-                        scope.insert("self".to_string(), Literal::List(list.clone()));
-                        scope.get("List").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::Fun(fun) => {
-                        scope.insert("self".to_string(), Literal::Fun(fun.clone()));
-                        scope.get("Fun").unwrap().clone().as_set().unwrap()
-                    }
-                    Literal::Set(set) => set.clone(),
-                };
-                Ok(set_set
-                    .get(&field.name)
-                    .ok_or(InterpretingError::new("Set has no such member", &field.pos))
-                    .map(|val| val.clone())?)
+            Self::Var(var) => lookup(scope, &var.name).map_err(|err| err.at(&var.pos)),
+            Self::Member { set, field, pos } => {
+                let (val, receiver, is_method) = resolve_member(*set, &field, &pos, scope)?;
+                Ok(bind_method(val, receiver, is_method))
+            }
+        }
+    }
+}
+
+/// Looks up `field` in the set `set` evaluates to, and returns it with the receiver
+///
+/// Literals that aren't sets use the set named after their type, so `5.add` is `Num.add` with
+/// `self` bound to `5`. Instances of user-defined types look in their fields first. The
+/// returned flag tells whether the member is such a method.
+fn resolve_member(
+    set: Value,
+    field: &Var,
+    pos: &FilePos,
+    scope: &mut Scope,
+) -> Result<(Literal, Literal, bool), InterpretingError> {
+    let receiver = set.interpret(scope)?;
+    let (set_set, is_method) = match &receiver {
+        Literal::Set(set) => (set.clone(), false),
+        Literal::Instance { fields, .. } if fields.contains_key(&field.name) => {
+            let val = fields[&field.name].clone();
+            return Ok((val, receiver, false));
+        }
+        receiver => {
+            let set = lookup(scope, receiver.type_name())
+                .and_then(|set| Ok(set.as_set()?))
+                .map_err(|err| err.at(pos))?;
+            (set, true)
+        }
+    };
+    let val = set_set
+        .get(&field.name)
+        .cloned()
+        .or_else(|| extension_method(scope, &field.name).filter(|_| is_method))
+        .or_else(|| any_method(scope, &field.name).filter(|_| is_method))
+        .ok_or_else(|| {
+            InterpretingError::new(
+                &format!("{} has no member `{}`", receiver.type_name(), field.name),
+                &field.pos,
+            )
+        })?;
+    Ok((val, receiver, is_method))
+}
+
+/// Functions in scope whose first parameter is `self` are methods of every type which
+/// doesn't have a member with the same name
+///
+/// The returned method takes the remaining parameters, and gets `self` from its receiver.
+fn extension_method(scope: &Scope, name: &str) -> Option<Literal> {
+    let fun = match scope.get(name)? {
+        Literal::Fun(Callable::Fun(fun)) => fun,
+        _ => return None,
+    };
+    match fun.args.first()? {
+        Param {
+            pattern: Pattern::Bind(var),
+            rest: false,
+            ..
+        } if var.name == "self" => Some(Literal::Fun(Callable::Fun(Function {
+            args: fun.args[1..].to_vec(),
+            body: fun.body.clone(),
+            pos: fun.pos.clone(),
+        }))),
+        _ => None,
+    }
+}
+
+/// Members of the `Any` set are methods of every type which has neither a member nor an
+/// extension method with the same name
+fn any_method(scope: &Scope, name: &str) -> Option<Literal> {
+    match scope.get("Any")? {
+        Literal::Set(any) => any.get(name).cloned(),
+        _ => None,
+    }
+}
+
+/// Binds `self` of methods which aren't called right away, like `self.divisible` in
+/// `list.find(self.divisible)`
+fn bind_method(val: Literal, receiver: Literal, is_method: bool) -> Literal {
+    match val {
+        Literal::Fun(fun) if is_method => Literal::Fun(Callable::Method {
+            fun: Box::new(fun),
+            receiver: Box::new(receiver),
+        }),
+        val => val,
+    }
+}
+
+fn describe_callee(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("`{}`", name),
+        None => "Anonymous function".to_string(),
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Binds the arguments of a call to the parameters of `fun` in `fun_scope`
+///
+/// Parameters without a value are bound to their default value, which is evaluated in
+/// `fun_scope` so it can refer to the parameters before it.
+fn bind_args(
+    fun: &Callable,
+    name: Option<&str>,
+    vals: Vec<Literal>,
+    fun_scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<(), InterpretingError> {
+    let params = fun.get_args();
+    let required = params
+        .iter()
+        .filter(|param| param.default.is_none() && !param.rest)
+        .count();
+    let variadic = params.iter().any(|param| param.rest);
+    if vals.len() < required || (!variadic && vals.len() > params.len()) {
+        let expected = if variadic {
+            format!("at least {} argument{}", required, plural(required))
+        } else if required == params.len() {
+            format!("{} argument{}", required, plural(required))
+        } else {
+            format!("{} to {} arguments", required, params.len())
+        };
+        let defined = match fun.get_pos() {
+            Some(pos) => format!("defined at {}", pos),
+            None => "an intrinsic".to_string(),
+        };
+        return Err(InterpretingError::new(
+            &format!(
+                "{} takes {} but {} {} given ({})",
+                describe_callee(name),
+                expected,
+                vals.len(),
+                if vals.len() == 1 { "was" } else { "were" },
+                defined
+            ),
+            pos,
+        ));
+    }
+
+    let mut vals = vals.into_iter();
+    for param in params {
+        let val = if param.rest {
+            Literal::List(vals.by_ref().collect())
+        } else if let Some(val) = vals.next() {
+            val
+        } else {
+            // Checked above, only parameters with defaults can be missing
+            param.default.unwrap().interpret(fun_scope)?
+        };
+        let mut bindings = Vec::new();
+        match_pattern(&param.pattern, val, &mut bindings)?;
+        for (name, val) in bindings {
+            fun_scope.insert(name, val);
+        }
+    }
+    Ok(())
+}
+
+/// Calls `fun` with the already evaluated arguments `vals`
+///
+/// `name` is the name `fun` was called by, which is used in error messages and the call stack.
+pub fn call(
+    fun: Literal,
+    name: Option<String>,
+    receiver: Option<Literal>,
+    vals: Vec<Literal>,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<Literal, InterpretingError> {
+    let mut next = TailCall {
+        fun,
+        name,
+        receiver,
+        vals,
+        scope: scope.clone(),
+        pos: pos.clone(),
+        replaced: None,
+        tail_calls: 0,
+    };
+    // Calls in tail position are returned instead of made, so they don't grow the stack
+    loop {
+        match call_once(next)? {
+            Tail::Done(val) => return Ok(val),
+            Tail::Call(call) => next = *call,
+        }
+    }
+}
+
+/// The result of interpreting something in tail position
+enum Tail {
+    Done(Literal),
+    Call(Box<TailCall>),
+}
+
+/// A call which is left to the caller of the function it is the last value of
+struct TailCall {
+    fun: Literal,
+    name: Option<String>,
+    receiver: Option<Literal>,
+    vals: Vec<Literal>,
+    /// The scope the call was made in
+    scope: Scope,
+    pos: FilePos,
+    /// Like the fields of `Frame`, for the calls this one takes the place of
+    replaced: Option<Box<Frame>>,
+    tail_calls: usize,
+}
+
+fn call_once(call: TailCall) -> Result<Tail, InterpretingError> {
+    let TailCall {
+        fun,
+        name,
+        receiver,
+        vals,
+        scope: mut fun_scope,
+        pos,
+        replaced,
+        tail_calls,
+    } = call;
+    let (fun, receiver) = match fun {
+        Literal::Fun(Callable::Method { fun, receiver }) => (*fun, Some(*receiver)),
+        Literal::Fun(fun) => (fun, receiver),
+        // Calling a type calls its constructor
+        Literal::Set(set) if set.contains_key("new") => {
+            return call_once(TailCall {
+                fun: set["new"].clone(),
+                name,
+                receiver: None,
+                vals,
+                scope: fun_scope,
+                pos,
+                replaced,
+                tail_calls,
+            })
+        }
+        lit => {
+            return Err(InterpretingError::new(
+                &format!(
+                    "{} is not a function but a {}",
+                    describe_callee(name.as_deref()),
+                    lit.type_name()
+                ),
+                &pos,
+            ))
+        }
+    };
+    if let Some(receiver) = receiver {
+        fun_scope.insert("self".to_string(), receiver);
+    }
+    bind_args(&fun, name.as_deref(), vals, &mut fun_scope, &pos)?;
+
+    step(&fun_scope).map_err(|err| err.at(&pos))?;
+    let limit = fun_scope.state.borrow().limits.call_depth;
+    if fun_scope.state.borrow().stack.len() >= limit {
+        let mut err =
+            InterpretingError::limit(&format!("Maximum recursion depth of {} exceeded", limit))
+                .at(&pos);
+        err.trace = fun_scope.stack();
+        return Err(err);
+    }
+    let grow = stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE);
+    if grow {
+        let state = fun_scope.state.borrow();
+        let max = state.limits.stack_size;
+        if state.grown_stack + STACK_SEGMENT > max {
+            let mut err = InterpretingError::limit(&format!(
+                "Exceeded the stack size limit of {} bytes at a call depth of {}",
+                max,
+                state.stack.len()
+            ))
+            .at(&pos);
+            drop(state);
+            err.trace = fun_scope.stack();
+            return Err(err);
+        }
+    }
+    if let (Callable::Fun(fun), Some(coverage)) = (&fun, &mut fun_scope.state.borrow_mut().coverage)
+    {
+        coverage.function(&fun.pos);
+    }
+    if let Some(profiler) = &mut fun_scope.state.borrow_mut().profiler {
+        let callee = match (fun.get_pos(), &fun) {
+            (Some(pos), _) => Callee::Fun(pos),
+            (
+                None,
+                Callable::Intr(Intrinsic {
+                    name: Some(name), ..
+                }),
+            ) => Callee::Intrinsic(name.clone()),
+            (None, _) => {
+                Callee::Intrinsic(name.clone().unwrap_or_else(|| "<intrinsic>".to_string()))
+            }
+        };
+        profiler.enter(callee, name.as_deref());
+    }
+    fun_scope.state.borrow_mut().stack.push(Frame {
+        name,
+        pos: pos.clone(),
+        replaced,
+        tail_calls,
+    });
+    let run = |fun_scope: &mut Scope| match fun {
+        Callable::Fun(fun) => fun
+            .body
+            .interpret_tail(fun_scope)
+            .or_else(|err| err.returned().map(Tail::Done)),
+        fun => fun.interpret(fun_scope).and_then(|val| {
+            check_size(&val, fun_scope)?;
+            Ok(Tail::Done(val))
+        }),
+    };
+    let res = if grow {
+        fun_scope.state.borrow_mut().grown_stack += STACK_SEGMENT;
+        let res = stacker::grow(STACK_SEGMENT, || run(&mut fun_scope));
+        fun_scope.state.borrow_mut().grown_stack -= STACK_SEGMENT;
+        res
+    } else {
+        run(&mut fun_scope)
+    };
+    let res = res.map_err(|mut err| {
+        if err.trace.is_empty() {
+            err.trace = fun_scope.stack();
+        }
+        err.at(&pos)
+    });
+    let frame = fun_scope.state.borrow_mut().stack.pop().unwrap();
+    if let Some(profiler) = &mut fun_scope.state.borrow_mut().profiler {
+        profiler.exit();
+    }
+    // Tail calls take the place of this call, so tracebacks show where the first of them was
+    // made from instead
+    res.map(|tail| match tail {
+        Tail::Call(mut next) => {
+            let Frame {
+                name,
+                pos,
+                replaced,
+                tail_calls,
+            } = frame;
+            next.tail_calls = tail_calls + 1;
+            next.replaced = Some(replaced.unwrap_or_else(|| {
+                Box::new(Frame {
+                    name,
+                    pos,
+                    replaced: None,
+                    tail_calls: 0,
+                })
+            }));
+            Tail::Call(next)
+        }
+        done => done,
+    })
+}
+
+/// Evaluates the function and the arguments of a call
+#[allow(clippy::type_complexity)]
+fn prepare_call(
+    fun: Value,
+    params: Vec<Value>,
+    scope: &mut Scope,
+) -> Result<(Literal, Option<String>, Option<Literal>, Vec<Literal>), InterpretingError> {
+    let name = match &fun {
+        Value::Ref(Reference::Var(var)) => Some(var.name.clone()),
+        Value::Ref(Reference::Member { field, .. }) => Some(field.name.clone()),
+        _ => None,
+    };
+    // Call methods as they are, instead of calling them without arguments, and
+    // bind `self` to the receiver
+    let (fun, receiver) = match fun {
+        Value::Ref(Reference::Member { set, field, pos }) => {
+            let (fun, receiver, _) = resolve_member(*set, &field, &pos, scope)?;
+            (fun, Some(receiver))
+        }
+        Value::Ref(r#ref) => (r#ref.interpret(scope)?, None),
+        fun => (fun.interpret(scope)?, None),
+    };
+    let vals = params
+        .into_iter()
+        .map(|param| param.interpret(scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((fun, name, receiver, vals))
+}
+
+/// Finds the first arm matching `val`, and returns its body with the scope to evaluate it in
+fn select_arm(
+    val: Literal,
+    arms: Vec<MatchArm>,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<(Scope, Statements), InterpretingError> {
+    for arm in arms {
+        let mut bindings = Vec::new();
+        if match_pattern(&arm.pattern, val.clone(), &mut bindings).is_err() {
+            continue;
+        }
+        let mut arm_scope = scope.clone();
+        for (name, val) in bindings {
+            arm_scope.insert(name, val);
+        }
+        if let Some(guard) = arm.guard {
+            let guard = guard.interpret(&mut arm_scope)?;
+            let guard = guard
+                .as_bool()
+                .map_err(|err| InterpretingError::from(err).at(pos))?;
+            if !guard {
+                continue;
+            }
+        }
+        return Ok((arm_scope, arm.body));
+    }
+    Err(InterpretingError::new(
+        &format!("No arm of the match matches the {}", val.type_name()),
+        pos,
+    ))
+}
+
+impl Value {
+    /// Like `interpret`, but leaves a call in tail position to the caller
+    fn interpret_tail(self, scope: &mut Scope) -> Result<Tail, InterpretingError> {
+        match self {
+            Self::FunCall { fun, params, pos } => {
+                let (fun, name, receiver, vals) = prepare_call(*fun, params, scope)?;
+                Ok(Tail::Call(Box::new(TailCall {
+                    fun,
+                    name,
+                    receiver,
+                    vals,
+                    scope: scope.clone(),
+                    pos,
+                    replaced: None,
+                    tail_calls: 0,
+                })))
+            }
+            Self::Match { val, arms, pos } => {
+                let val = val.interpret(scope)?;
+                let (mut arm_scope, body) = select_arm(val, arms, scope, &pos)?;
+                body.interpret_tail(&mut arm_scope)
+            }
+            val => val.interpret(scope).map(Tail::Done),
+        }
+    }
+}
+
+impl Statements {
+    /// Like `interpret`, but leaves a call in tail position to the caller
+    fn interpret_tail(self, scope: &mut Scope) -> Result<Tail, InterpretingError> {
+        let mut statements = self.0;
+        let last = statements.pop().unwrap();
+        for statement in statements {
+            statement.interpret(scope)?;
+        }
+        if let Statement::Value(..) | Statement::Return { .. } = last {
+            before_statement(&last, scope)?;
+        }
+        match last {
+            Statement::Value(val) | Statement::Return { val, .. } => val.interpret_tail(scope),
+            last => last.interpret(scope).map(Tail::Done),
+        }
+    }
+}
+
+/// Converts `val` into a string with its `to_string` method
+///
+/// This is the Printable protocol: every literal whose set has a `to_string` method
+/// returning a string can be printed and interpolated.
+pub fn to_string(
+    val: Literal,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<String, InterpretingError> {
+    let set = match &val {
+        Literal::Set(set) => set.clone(),
+        val => lookup(scope, val.type_name())
+            .and_then(|set| Ok(set.as_set()?))
+            .map_err(|err| err.at(pos))?,
+    };
+    match set.get("to_string") {
+        Some(fun) => {
+            let name = Some("to_string".to_string());
+            match call(fun.clone(), name, Some(val), Vec::new(), scope, pos)? {
+                Literal::Str(str) => Ok(str),
+                lit => Err(InterpretingError::new(
+                    &format!(
+                        "`to_string` returned a {} instead of a Str",
+                        lit.type_name()
+                    ),
+                    pos,
+                )),
+            }
+        }
+        // Instances are printable without defining `to_string`
+        None if matches!(val, Literal::Instance { .. }) => display(val, scope, pos),
+        None => Err(InterpretingError::new(
+            &format!(
+                "{} is not printable, it has no `to_string` method",
+                val.type_name()
+            ),
+            pos,
+        )),
+    }
+}
+
+/// The default string representation of a literal, used by the built-in `to_string` methods
+pub fn display(
+    val: Literal,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<String, InterpretingError> {
+    Ok(match val {
+        Literal::Nope => "Nope".to_string(),
+        Literal::Str(str) => str,
+        Literal::Char(char) => char.to_string(),
+        Literal::Num(num) => num.to_string(),
+        Literal::Bool(true) => "True".to_string(),
+        Literal::Bool(false) => "False".to_string(),
+        Literal::List(list) => {
+            let items = list
+                .into_iter()
+                .map(|item| to_string(item, scope, pos))
+                .collect::<Result<Vec<_>, _>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Literal::Fun(fun) => match fun.get_pos() {
+            Some(pos) => format!("<function at {}>", pos),
+            None => "<intrinsic>".to_string(),
+        },
+        Literal::Err(err) => format!("Err({})", to_string(*err, scope, pos)?),
+        Literal::Instance { type_name, fields } => {
+            let mut fields: Vec<_> = fields.into_iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let fields = fields
+                .into_iter()
+                .map(|(name, val)| Ok(format!("{}: {}", name, to_string(val, scope, pos)?)))
+                .collect::<Result<Vec<_>, InterpretingError>>()?;
+            format!("{}({})", type_name, fields.join(", "))
+        }
+        val @ Literal::Set(..) => to_string(val, scope, pos)?,
+    })
+}
+
+/// How a value is written in sand, where strings and characters are quoted unlike when printed
+pub fn repr(val: &Literal, scope: &mut Scope, pos: &FilePos) -> Result<String, InterpretingError> {
+    Ok(match val {
+        Literal::Str(str) => format!("{:?}", str),
+        Literal::Char(char) => format!("{:?}", char),
+        Literal::List(list) => {
+            let items = list
+                .iter()
+                .map(|item| repr(item, scope, pos))
+                .collect::<Result<Vec<_>, _>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Literal::Err(err) => format!("Err({})", repr(err, scope, pos)?),
+        val => display(val.clone(), scope, pos)?,
+    })
+}
+
+/// Formats `val` according to the format specifier `spec`
+///
+/// The specifier is `[[fill]align][width][.precision]`, where `align` is one of `<`, `^`
+/// and `>`. Numbers are aligned to the right by default, and everything else to the left.
+/// Without an alignment, a width with a leading zero pads with zeros, after the sign of numbers.
+/// The precision is the number of decimals of numbers, and the maximum length of anything
+/// else.
+pub fn format_spec(
+    val: Literal,
+    spec: &str,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<String, InterpretingError> {
+    let bad_spec = || InterpretingError::new(&format!("Invalid format specifier `{}`", spec), pos);
+    let is_align = |c: char| ['<', '^', '>'].contains(&c);
+
+    let chars: Vec<char> = spec.chars().collect();
+    let (fill, align, rest) = match chars.as_slice() {
+        [fill, align, rest @ ..] if is_align(*align) => (*fill, Some(*align), rest),
+        [align, rest @ ..] if is_align(*align) => (' ', Some(*align), rest),
+        rest => (' ', None, rest),
+    };
+    // A width starting with '0' pads with zeros
+    let zeros = matches!((align, rest), (None, ['0', _, ..]));
+    let (fill, align) = if zeros {
+        ('0', Some('>'))
+    } else {
+        (fill, align)
+    };
+    let rest: String = rest.iter().collect();
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (rest.as_str(), None),
+    };
+    let width: usize = if width.is_empty() {
+        0
+    } else {
+        width.parse().map_err(|_| bad_spec())?
+    };
+    let precision: Option<usize> = match precision {
+        Some(precision) => Some(precision.parse().map_err(|_| bad_spec())?),
+        None => None,
+    };
+
+    let is_num = matches!(val, Literal::Num(..));
+    if let (true, Some(precision)) = (is_num, precision) {
+        check_len("Str", precision, "bytes", scope).map_err(|err| err.at(pos))?;
+    }
+    let str = match (val, precision) {
+        (Literal::Num(num), Some(precision)) => format!("{:.*}", precision, num),
+        (val, Some(precision)) => to_string(val, scope, pos)?
+            .chars()
+            .take(precision)
+            .collect(),
+        (val, None) => to_string(val, scope, pos)?,
+    };
+
+    let padding = width.saturating_sub(str.chars().count());
+    let (left, right) = match align.unwrap_or(if is_num { '>' } else { '<' }) {
+        '<' => (0, padding),
+        '^' => (padding / 2, padding - padding / 2),
+        _ => (padding, 0),
+    };
+    let size = str
+        .len()
+        .saturating_add(padding.saturating_mul(fill.len_utf8()));
+    check_len("Str", size, "bytes", scope).map_err(|err| err.at(pos))?;
+    let fill = |n| std::iter::repeat_n(fill, n).collect::<String>();
+    match str.strip_prefix('-') {
+        Some(digits) if zeros && is_num => Ok(format!("-{}{}", fill(left), digits)),
+        _ => Ok(format!("{}{}{}", fill(left), str, fill(right))),
+    }
+}
+
+fn count_items(n: usize) -> String {
+    format!("{} item{}", n, plural(n))
+}
+
+/// Matches `val` against `pattern`, adding the variables bound by the pattern to `bindings`
+///
+/// Fails with an error pointing at the part of the pattern which doesn't match.
+pub fn match_pattern(
+    pattern: &Pattern,
+    val: Literal,
+    bindings: &mut Vec<(String, Literal)>,
+) -> Result<(), InterpretingError> {
+    let expected = |type_name: &str, val: &Literal, pos: &FilePos| {
+        InterpretingError::new(
+            &format!("Expected a {} but found a {}", type_name, val.type_name()),
+            pos,
+        )
+    };
+    match pattern {
+        Pattern::Wildcard(..) => Ok(()),
+        Pattern::Bind(var) => {
+            bindings.push((var.name.clone(), val));
+            Ok(())
+        }
+        Pattern::Lit { lit, pos } => {
+            if lit.type_name() != val.type_name() {
+                Err(expected(lit.type_name(), &val, pos))
+            } else if lit != &val {
+                Err(InterpretingError::new(
+                    "Value is not equal to the pattern",
+                    pos,
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        Pattern::List { items, rest, pos } => {
+            let mut list = match val {
+                Literal::List(list) => list,
+                val => return Err(expected("List", &val, pos)),
+            };
+            if rest.is_none() && list.len() != items.len() {
+                return Err(InterpretingError::new(
+                    &format!(
+                        "Expected a list of {} but found {}",
+                        count_items(items.len()),
+                        list.len()
+                    ),
+                    pos,
+                ));
+            }
+            if list.len() < items.len() {
+                return Err(InterpretingError::new(
+                    &format!(
+                        "Expected a list of at least {} but found {}",
+                        count_items(items.len()),
+                        list.len()
+                    ),
+                    pos,
+                ));
+            }
+            let tail = list.split_off(items.len());
+            for (item, val) in items.iter().zip(list) {
+                match_pattern(item, val, bindings)?;
+            }
+            match rest {
+                Some(rest) => match_pattern(rest, Literal::List(tail), bindings),
+                None => Ok(()),
+            }
+        }
+        Pattern::Set { fields, pos } => {
+            let mut set = match val {
+                Literal::Set(set) | Literal::Instance { fields: set, .. } => set,
+                val => return Err(expected("Set", &val, pos)),
+            };
+            for (field, pattern) in fields {
+                let val = set.remove(&field.name).ok_or_else(|| {
+                    InterpretingError::new(
+                        &format!("Set has no member `{}`", field.name),
+                        &field.pos,
+                    )
+                })?;
+                match_pattern(pattern, val, bindings)?;
             }
+            Ok(())
         }
     }
 }
 
 impl Interpret for Value {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError> {
+        step(scope)?;
         match self {
             Self::Lit(lit) => Ok(lit),
-            Self::Ref(r#ref) => r#ref.interpret(scope),
-            Self::FunCall { fun, params, .. } => {
-                let fun = fun.interpret(scope)?.as_fun().unwrap();
-                let args = fun.get_args();
-                if args.len() != params.len() {
-                    return Err(InterpretingError::new("Mismatched arity", &FilePos::temp()));
+            Self::Ref(Reference::Member { set, field, pos }) => {
+                match resolve_member(*set, &field, &pos, scope)? {
+                    // Methods without parameters don't need to be called explicitly
+                    (Literal::Fun(fun), receiver, true) if fun.get_args().is_empty() => {
+                        let name = Some(field.name);
+                        call(
+                            Literal::Fun(fun),
+                            name,
+                            Some(receiver),
+                            Vec::new(),
+                            scope,
+                            &pos,
+                        )
+                    }
+                    (val, receiver, is_method) => Ok(bind_method(val, receiver, is_method)),
                 }
-                let mut fun_scope = scope.clone();
-                for n in 0..args.len() {
-                    let var = args.get(n).unwrap().name.clone();
-                    let val = params.get(n).unwrap().clone().interpret(scope)?;
-                    fun_scope.insert(var, val);
+            }
+            Self::Ref(r#ref) => r#ref.interpret(scope),
+            Self::FunCall { fun, params, pos } => {
+                let (fun, name, receiver, vals) = prepare_call(*fun, params, scope)?;
+                call(fun, name, receiver, vals, scope, &pos)
+            }
+            Self::Try { val, .. } => val.interpret(scope).or_else(|err| err.catch()),
+            Self::Propagate { val, pos } => match val.interpret(scope)? {
+                Literal::Err(err) => Err(InterpretingError::raise(*err, &pos)),
+                val => Ok(val),
+            },
+            Self::Match { val, arms, pos } => {
+                let val = val.interpret(scope)?;
+                let (mut arm_scope, body) = select_arm(val, arms, scope, &pos)?;
+                body.interpret(&mut arm_scope)
+            }
+            Self::Format { parts, pos } => {
+                let mut str = String::new();
+                for part in parts {
+                    match part {
+                        FormatPart::Str(part) => str.push_str(&part),
+                        FormatPart::Value { val, spec } => {
+                            let val = val.interpret(scope)?;
+                            str.push_str(&match spec {
+                                Some(spec) => format_spec(val, &spec, scope, &pos)?,
+                                None => to_string(val, scope, &pos)?,
+                            });
+                        }
+                    }
                 }
-                fun.interpret(&mut fun_scope)
+                let str = Literal::Str(str);
+                check_size(&str, scope).map_err(|err| err.at(&pos))?;
+                Ok(str)
             }
         }
     }
@@ -110,32 +1195,142 @@ impl Interpret for Value {
 
 impl Interpret for Statement {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError> {
+        step(scope)?;
+        before_statement(&self, scope)?;
         match self {
-            Self::Assignment { var, val, .. } => {
-                match var {
-                    Reference::Var(var) => {
-                        scope.insert(var.name.clone(), val.interpret(&mut scope.clone())?);
-                        assert!(scope.contains_key(&var.name));
+            Self::Assignment { var, val, .. } => match var {
+                Reference::Var(var) => {
+                    scope.insert(var.name.clone(), val.interpret(&mut scope.clone())?);
+                    assert!(scope.contains_key(&var.name));
+                    Ok(Literal::Nope)
+                }
+                Reference::Member { set, field, pos } => {
+                    if let Value::Ref(Reference::Var(var)) = *set {
+                        let val = val.interpret(scope)?;
+                        let parent =
+                            match lookup(scope, &var.name).map_err(|err| err.at(&var.pos))? {
+                                Literal::Set(mut set) => {
+                                    set.insert(field.name, val);
+                                    Literal::Set(set)
+                                }
+                                Literal::Instance {
+                                    type_name,
+                                    mut fields,
+                                } => {
+                                    fields.insert(field.name, val);
+                                    Literal::Instance { type_name, fields }
+                                }
+                                parent => {
+                                    return Err(InterpretingError::new(
+                                        &format!(
+                                            "Cannot assign to a member of a {}",
+                                            parent.type_name()
+                                        ),
+                                        &var.pos,
+                                    ))
+                                }
+                            };
+                        scope.insert(var.name, parent);
                         Ok(Literal::Nope)
+                    } else {
+                        Err(InterpretingError::new(
+                            "Complex referencing is not supported yet",
+                            &pos,
+                        ))
                     }
-                    Reference::Member { set, field, pos } => {
-                        if let Value::Ref(Reference::Var(var)) = *set {
-                            let mut parent =
-                                scope.get(&var.name).unwrap().clone().as_set().unwrap(); // TODO: Handle unwraps
-                            parent.insert(field.name, val.interpret(scope)?);
-                            scope.insert(var.name, Literal::Set(parent));
-                            Ok(Literal::Nope)
-                        } else {
-                            return Err(InterpretingError::new(
-                                "Complex referencing is not supported yet",
-                                &pos,
-                            ));
-                        }
+                }
+            },
+            Self::Type {
+                name,
+                fields,
+                members,
+                pos,
+            } => {
+                let constructor = Constructor {
+                    type_name: name.name.clone(),
+                    args: fields,
+                    pos,
+                };
+                let mut set = HashMap::new();
+                set.insert(
+                    "new".to_string(),
+                    Literal::Fun(Callable::Constructor(constructor)),
+                );
+                scope.insert(name.name.clone(), Literal::Set(set.clone()));
+                // Members can use the type and the members before them
+                for (var, val) in members {
+                    set.insert(var.name, val.interpret(&mut scope.clone())?);
+                    scope.insert(name.name.clone(), Literal::Set(set.clone()));
+                }
+                Ok(Literal::Nope)
+            }
+            Self::Destructure { pattern, val, .. } => {
+                let val = val.interpret(&mut scope.clone())?;
+                let mut bindings = Vec::new();
+                match_pattern(&pattern, val, &mut bindings)?;
+                for (name, val) in bindings {
+                    scope.insert(name, val);
+                }
+                Ok(Literal::Nope)
+            }
+            Self::While { cond, body, pos } => {
+                while cond
+                    .clone()
+                    .interpret(scope)?
+                    .as_bool()
+                    .map_err(|err| InterpretingError::from(err).at(&pos))?
+                {
+                    if !loop_body(&body, scope, &pos)? {
+                        break;
                     }
                 }
+                Ok(Literal::Nope)
+            }
+            Self::For {
+                pattern,
+                iterable,
+                body,
+                pos,
+            } => {
+                let items = match iterable.interpret(scope)? {
+                    Literal::List(list) => list,
+                    Literal::Str(str) => str.chars().map(Literal::Char).collect(),
+                    lit => {
+                        return Err(InterpretingError::new(
+                            &format!("Cannot iterate over a {}", lit.type_name()),
+                            &pos,
+                        ))
+                    }
+                };
+                for item in items {
+                    let mut bindings = Vec::new();
+                    match_pattern(&pattern, item, &mut bindings)?;
+                    for (name, val) in bindings {
+                        scope.insert(name, val);
+                    }
+                    if !loop_body(&body, scope, &pos)? {
+                        break;
+                    }
+                }
+                Ok(Literal::Nope)
+            }
+            Self::Break(pos) => Err(InterpretingError::control(ErrorKind::Break, &pos)),
+            Self::Continue(pos) => Err(InterpretingError::control(ErrorKind::Continue, &pos)),
+            Self::Return { val, pos } => {
+                let val = val.interpret(scope)?;
+                Err(InterpretingError::control(
+                    ErrorKind::Return(Box::new(val)),
+                    &pos,
+                ))
             }
             Self::Value(val) => val.interpret(scope),
             Self::Include(file) => {
+                if !scope.state.borrow().capabilities.fs {
+                    return Err(InterpretingError::new(
+                        &format!("Cannot include `{}` without file access", file.display()),
+                        &FilePos::temp(),
+                    ));
+                }
                 let str = fs::read_to_string(&file).map_err(|err| {
                     InterpretingError::new(
                         &format!("Cannot include `{}` because:\n{}", file.display(), err),
@@ -144,21 +1339,13 @@ impl Interpret for Statement {
                 })?;
                 let tokens = tokenize_str(&str, FilePos::new(&file, 1, 1)).map_err(|err| {
                     InterpretingError::new(
-                        &format!(
-                            "Cannot tokenize `{}` because:\n{}",
-                            file.display(),
-                            SandError::from(err)
-                        ),
+                        &format!("Cannot tokenize `{}` because:\n{}", file.display(), err),
                         &FilePos::temp(),
                     )
                 })?;
                 let tree = parse_file(tokens).map_err(|err| {
                     InterpretingError::new(
-                        &format!(
-                            "Cannot parse `{}` because:\n{}",
-                            file.display(),
-                            SandError::from(err)
-                        ),
+                        &format!("Cannot parse `{}` because:\n{}", file.display(), err),
                         &FilePos::temp(),
                     )
                 })?;
@@ -168,31 +1355,70 @@ impl Interpret for Statement {
     }
 }
 
+/// Runs one iteration of a loop in the scope around it, and returns whether to keep looping
+///
+/// Errors without a known position are placed at the loop.
+fn loop_body(
+    body: &Statements,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<bool, InterpretingError> {
+    match body.clone().interpret(scope) {
+        Err(err) if err.kind == ErrorKind::Break => Ok(false),
+        Err(err) if err.kind == ErrorKind::Continue => Ok(true),
+        res => res.map(|_| true).map_err(|err| err.at(pos)),
+    }
+}
+
 impl Interpret for Statements {
     fn interpret(self, scope: &mut Scope) -> Result<Literal, InterpretingError> {
-        if self.0.len() > 1 {
-            for i in 0..self.0.len() - 1 {
-                self.0.get(i).unwrap().clone().interpret(scope)?;
-            }
+        let mut statements = self.0;
+        let last = statements.pop().unwrap();
+        for statement in statements {
+            statement.interpret(scope)?;
         }
-        self.0.last().unwrap().clone().interpret(scope)
+        last.interpret(scope)
     }
 }
 
-pub fn interpret_file(tree: Statements, args: Vec<String>) -> Result<i32, InterpretingError> {
-    let mut scope = init_scope();
-    tree.interpret(&mut scope)?;
+pub fn interpret_file(
+    tree: Statements,
+    _args: Vec<String>,
+    limits: Limits,
+    capabilities: Capabilities,
+) -> Result<i32, InterpretingError> {
+    let mut scope = init_scope_with(capabilities);
+    scope.state.borrow_mut().limits = limits;
+    interpret_main(tree, &mut scope)
+}
+
+/// Interprets the file in `scope`, and calls its `main` function
+pub fn interpret_main(tree: Statements, scope: &mut Scope) -> Result<i32, InterpretingError> {
+    tree.interpret(scope)?;
+    if !scope.contains_key("main") {
+        return Err(InterpretingError::new(
+            "No `main` function defined",
+            &FilePos::internal(),
+        ));
+    }
+    // `main` isn't called from anywhere in the file, so its call is where it is defined
+    let pos = match scope.get("main") {
+        Some(Literal::Fun(Callable::Fun(fun))) => fun.pos.clone(),
+        _ => FilePos::internal(),
+    };
     let call = Value::FunCall {
-        fun: Box::new(Value::Lit(scope.get("main").unwrap().clone())),
+        fun: Box::new(Value::Ref(Reference::Var(Var::new("main")))),
         params: vec![Value::Lit(Literal::Str("ur mom gae lol".to_string()))], // TODO
-        pos: FilePos::internal(),
+        pos,
     };
-    let exit_code = call.interpret(&mut scope)?.as_int().unwrap();
+    let exit_code = call.interpret(scope)?.as_int()?;
     Ok(exit_code as i32)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use crate::intrinsics::init_scope;
 
     use super::*;
@@ -264,4 +1490,658 @@ mod tests {
         let tree = parse_file(tokens).unwrap();
         assert!(tree.interpret(&mut init_scope()).unwrap() == Literal::Str("foo".to_string()))
     }
+
+    #[test]
+    fn interpret_value_try() {
+        let str = r#"try 1.add("foo")"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        match tree.interpret(&mut init_scope()).unwrap() {
+            Literal::Err(..) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn interpret_value_try_raise() {
+        let str = r#"(try Main.raise("oops")).value()"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(tree.interpret(&mut init_scope()).unwrap() == Literal::Str("oops".to_string()))
+    }
+
+    #[test]
+    fn interpret_value_try_ok() {
+        let str = r#"try 1.add(1)"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(tree.interpret(&mut init_scope()).unwrap() == Literal::Num(2.0))
+    }
+
+    #[test]
+    fn interpret_value_try_or() {
+        for (str, expected) in [
+            (r#"(try 1.add(1)).or(0)"#, Literal::Num(2.0)),
+            (r#"(try 1.add("x")).or(0)"#, Literal::Num(0.0)),
+            (r#"(try "ok").value"#, Literal::Str("ok".to_string())),
+            (r#"let Num.or = (n) { 5 }; 1.or(0)"#, Literal::Num(5.0)),
+            (r#"(try False).or(True)"#, Literal::Bool(true)),
+        ] {
+            assert_eq!(interpret_operators(str), expected, "{}", str);
+        }
+    }
+
+    #[test]
+    fn interpret_value_try_panic() {
+        let str = r#"try Main.panic("oops")"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.kind == ErrorKind::Panic)
+    }
+
+    #[test]
+    fn interpret_value_propagate() {
+        let str =
+            r#"let fail = () { Main.raise("oops") }; let f = () { (try fail())?; 1 }; try f()"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(
+            tree.interpret(&mut init_scope()).unwrap()
+                == Literal::Err(Box::new(Literal::Str("oops".to_string())))
+        )
+    }
+
+    #[test]
+    fn interpret_error_trace() {
+        // None of the calls are in tail position, so they all stay on the stack
+        let str = r#"
+            let bad = (n) { n.add("x"); Nope };
+            let f = () { 2.times((n) { bad(n); Nope }); Nope };
+            f()
+        "#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        let names: Vec<_> = err.trace.iter().map(|frame| frame.name.clone()).collect();
+        assert!(
+            names
+                == vec![
+                    Some("f".to_string()),
+                    Some("times".to_string()),
+                    Some("f".to_string()),
+                    Some("bad".to_string()),
+                    Some("add".to_string()),
+                ]
+        )
+    }
+
+    #[test]
+    fn interpret_error_trace_tail_calls() {
+        // `h` is replaced by the tail calls to `g` and `add`, which keep where `h` was called
+        let str = "let g = (n) { n.add(\"x\") };\nlet h = (n) { g(n) };\nlet x = h(1); x";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert_eq!(err.trace.len(), 1);
+        let frame = &err.trace[0];
+        assert_eq!(frame.name.as_deref(), Some("add"));
+        assert_eq!(frame.tail_calls, 2);
+        let replaced = frame.replaced.as_ref().unwrap();
+        assert_eq!(replaced.name.as_deref(), Some("h"));
+        assert_eq!((replaced.pos.row, replaced.pos.col), (2, 9));
+
+        let message = crate::SandError::from(err).to_string();
+        assert!(message.contains("in `h`\n    [Replaced by 2 tail calls]\n"));
+    }
+
+    #[test]
+    fn interpret_tail_call() {
+        // Deeper than the recursion limit, which tail calls don't count towards
+        let str = r#"
+            let count = (n, acc) { match n { 0 => acc, n => count(n - 1, acc + 1) } };
+            count(3000, 0)
+        "#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let mut scope = init_scope();
+        assert_eq!(tree.interpret(&mut scope).unwrap(), Literal::Num(3000.0));
+        assert!(scope.stack().is_empty())
+    }
+
+    #[test]
+    fn interpret_recursion_limit() {
+        let str = r#"let sum = (n) { match n { 0 => 0, n => n + sum(n - 1) } }; sum(30)"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let mut scope = init_scope();
+        scope.state.borrow_mut().limits.call_depth = 20;
+        let err = tree.clone().interpret(&mut scope).unwrap_err();
+        assert_eq!(err.msg, "Maximum recursion depth of 20 exceeded");
+        assert_eq!(err.kind, ErrorKind::Limit);
+        assert_eq!(err.trace.len(), 20);
+        assert!(err
+            .trace
+            .iter()
+            .all(|frame| frame.name.as_deref() == Some("sum")));
+        assert!(scope.stack().is_empty());
+
+        scope.state.borrow_mut().limits.call_depth = 40;
+        assert_eq!(tree.interpret(&mut scope).unwrap(), Literal::Num(465.0));
+
+        // Like the other limits, `try` can't catch it
+        let str = r#"let f = (n) { 1 + f(n + 1) }; (try f(0)).or(7)"#;
+        let tree = parse_file(tokenize_str(str, FilePos::internal()).unwrap()).unwrap();
+        let err = tree.interpret(&mut scope).unwrap_err();
+        assert_eq!(err.msg, "Maximum recursion depth of 40 exceeded");
+        assert_eq!(err.kind, ErrorKind::Limit);
+    }
+
+    #[test]
+    fn interpret_while() {
+        let str = r#"
+            let i = 0;
+            let sum = 0;
+            while i < 10 {
+                let i = i + 1;
+                match i % 2 { 0 => { continue }, _ => Nope };
+                match i > 7 { True => { break }, False => Nope };
+                let sum = sum + i
+            };
+            sum
+        "#;
+        // 1 + 3 + 5 + 7
+        assert_eq!(interpret_operators(str), Literal::Num(16.0))
+    }
+
+    #[test]
+    fn interpret_for() {
+        let str = r#"
+            let sum = 0;
+            for [a, b] in [[1, 2], [3, 4]] { let sum = sum + a * b };
+            let n = 0;
+            for c in "abc" { match c { 'b' => { break }, _ => Nope }; let n = n + 1 };
+            sum * 10 + n
+        "#;
+        assert_eq!(interpret_operators(str), Literal::Num(141.0))
+    }
+
+    #[test]
+    fn interpret_loop_errors() {
+        for (str, msg) in [
+            ("while 1 { Nope }", "Literal is not a boolean"),
+            ("for x in 5 { Nope }", "Cannot iterate over a Num"),
+            ("while True { 1.add(\"x\") }", "Literal is not a number"),
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            let tree = parse_file(tokens).unwrap();
+            let err = tree.interpret(&mut init_scope()).unwrap_err();
+            assert_eq!(err.msg, msg);
+        }
+    }
+
+    #[test]
+    fn interpret_try_passes_break() {
+        let str = r#"let i = 0; while True { let i = i + 1; try match i { 3 => { break }, _ => Nope } }; i"#;
+        assert_eq!(interpret_operators(str), Literal::Num(3.0))
+    }
+
+    #[test]
+    fn interpret_return() {
+        let sign = r#"
+            let sign = (n) {
+                match n < 0 { True => { return -1 }, False => Nope };
+                match n == 0 { True => { return }, False => Nope };
+                1
+            };
+        "#;
+        assert_eq!(
+            interpret_operators(&format!("{} sign(-5) * 10 + sign(5)", sign)),
+            Literal::Num(-9.0)
+        );
+        assert_eq!(
+            interpret_operators(&format!("{} sign(0)", sign)),
+            Literal::Nope
+        );
+    }
+
+    #[test]
+    fn interpret_return_from_loop() {
+        let str = r#"
+            let find = (list, x) { for [i, item] in list { match item == x { True => { return i }, False => Nope } }; -1 };
+            find([[0, 'a'], [1, 'b']], 'b') * 10 + find([[0, 'a']], 'c')
+        "#;
+        assert_eq!(interpret_operators(str), Literal::Num(9.0))
+    }
+
+    #[test]
+    fn interpret_return_not_through_intrinsic() {
+        // `return` only stops the function passed to `times`, not `times` or `f`
+        let str = r#"
+            let n = 0;
+            let f = () { 3.times((i) { return; Main.panic("unreachable") }); 5 };
+            f()
+        "#;
+        assert_eq!(interpret_operators(str), Literal::Num(5.0))
+    }
+
+    #[test]
+    fn interpret_return_tail_call() {
+        let str = r#"
+            let count = (n) { match n { 0 => Nope, _ => { return count(n - 1) } } };
+            count(3000)
+        "#;
+        assert_eq!(interpret_operators(str), Literal::Nope)
+    }
+
+    fn interpret_limited(str: &str, limits: Limits) -> InterpretingError {
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let mut scope = init_scope();
+        scope.state.borrow_mut().limits = limits;
+        tree.interpret(&mut scope).unwrap_err()
+    }
+
+    #[test]
+    fn interpret_step_limit() {
+        let limits = Limits {
+            steps: Some(1000),
+            ..Limits::default()
+        };
+        // `try` doesn't catch exceeded limits
+        let err = interpret_limited("while True { try 1 + 1 }", limits.clone());
+        assert_eq!(err.msg, "Exceeded the limit of 1000 steps");
+        assert!(err.kind == ErrorKind::Limit);
+        let err = interpret_limited("let f = () { f() }; f()", limits);
+        assert_eq!(err.msg, "Exceeded the limit of 1000 steps");
+    }
+
+    #[test]
+    fn interpret_time_limit() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let err = interpret_limited("while True { Nope }", limits);
+        assert_eq!(err.msg, "Exceeded the time limit of 50ms");
+    }
+
+    #[test]
+    fn interpret_size_limit() {
+        let limits = Limits {
+            size: Some(8),
+            ..Limits::default()
+        };
+        let err = interpret_limited(
+            r#"let s = "ab"; while True { let s = s + s }"#,
+            limits.clone(),
+        );
+        assert_eq!(err.msg, "A Str of 16 bytes exceeds the size limit of 8");
+        let err = interpret_limited(r#""{123456}{789}""#, limits.clone());
+        assert_eq!(err.msg, "A Str of 9 bytes exceeds the size limit of 8");
+        assert_eq!(err.pos, FilePos::internal());
+        // Padding and decimals are checked before they are allocated
+        let err = interpret_limited(r#""{1:20000000000}""#, limits.clone());
+        assert_eq!(
+            err.msg,
+            "A Str of 20000000000 bytes exceeds the size limit of 8"
+        );
+        assert!(err.kind == ErrorKind::Limit);
+        let err = interpret_limited(r#""{1:.20000000000}""#, limits);
+        assert_eq!(
+            err.msg,
+            "A Str of 20000000000 bytes exceeds the size limit of 8"
+        );
+    }
+
+    #[test]
+    fn interpret_stack_size_limit() {
+        let limits = Limits {
+            call_depth: usize::MAX,
+            stack_size: 1 << 26,
+            ..Limits::default()
+        };
+        let err = interpret_limited("let f = (n) { 1 + f(n) }; f(1)", limits);
+        assert!(err
+            .msg
+            .starts_with("Exceeded the stack size limit of 67108864 bytes at a call depth of "));
+        assert!(err.kind == ErrorKind::Limit);
+        assert!(!err.trace.is_empty());
+    }
+
+    #[test]
+    fn interpret_include_without_fs() {
+        let tokens = tokenize_str(
+            r#"include "../std.sand""#,
+            FilePos::new(Path::new("src/test.sand"), 1, 1),
+        )
+        .unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let capabilities = Capabilities {
+            fs: false,
+            ..Capabilities::default()
+        };
+        let err = tree
+            .interpret(&mut init_scope_with(capabilities))
+            .unwrap_err();
+        assert!(err.msg.starts_with("Cannot include"));
+    }
+
+    #[test]
+    fn interpret_stack_unwound() {
+        let str = r#"let bad = () { 1.add("x") }; try bad()"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let mut scope = init_scope();
+        tree.interpret(&mut scope).unwrap();
+        assert!(scope.stack().is_empty())
+    }
+
+    #[test]
+    fn interpret_funcall_default() {
+        let str = r#"let f = (a, b = a.add(1)) { a.mul(b) }; f(2)"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(tree.interpret(&mut init_scope()).unwrap() == Literal::Num(6.0))
+    }
+
+    #[test]
+    fn interpret_funcall_rest() {
+        let str = r#"let f = (a, ...rest) { rest }; f(1, 2, 3)"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(
+            tree.interpret(&mut init_scope()).unwrap()
+                == Literal::List(vec![Literal::Num(2.0), Literal::Num(3.0)])
+        )
+    }
+
+    #[test]
+    fn interpret_funcall_arity() {
+        let str = r#"let f = (a, b = 1) { a }; f()"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err
+            .msg
+            .starts_with("`f` takes 1 to 2 arguments but 0 were given"))
+    }
+
+    #[test]
+    fn interpret_funcall_not_a_function() {
+        let str = r#"let f = 5; f()"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "`f` is not a function but a Num")
+    }
+
+    #[test]
+    fn interpret_method_without_parens() {
+        let str = r#"2 pow 3 to_str"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(tree.interpret(&mut init_scope()).unwrap() == Literal::Str("8".to_string()))
+    }
+
+    #[test]
+    fn interpret_set_member_not_called() {
+        let str = r#"Main.dump"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        assert!(matches!(
+            tree.interpret(&mut init_scope()).unwrap(),
+            Literal::Fun(..)
+        ))
+    }
+
+    fn interpret_operators(str: &str) -> Literal {
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        tree.interpret(&mut init_scope()).unwrap()
+    }
+
+    #[test]
+    fn interpret_operator_precedence() {
+        assert!(interpret_operators("1 + 2 * 3 - 4") == Literal::Num(3.0));
+        assert!(interpret_operators("(1 + 2) * 3") == Literal::Num(9.0));
+        assert!(interpret_operators("2 ** 3 ** 2") == Literal::Num(512.0));
+        assert!(interpret_operators("-2 ** 2") == Literal::Num(-4.0));
+        assert!(interpret_operators("10 - 4 - 3") == Literal::Num(3.0));
+        assert!(interpret_operators("7 % 4 / 2") == Literal::Num(1.5));
+    }
+
+    #[test]
+    fn interpret_operator_comparison() {
+        assert!(interpret_operators("1 + 1 == 2 && 3 > 2") == Literal::Bool(true));
+        assert!(interpret_operators("1 != 1 || 2 <= 1") == Literal::Bool(false));
+        assert!(interpret_operators("!(2 >= 3)") == Literal::Bool(true));
+        assert!(interpret_operators(r#""foo" + "bar" == "foobar""#) == Literal::Bool(true));
+        assert!(interpret_operators("'a' != 'b'") == Literal::Bool(true));
+    }
+
+    #[test]
+    fn interpret_operator_overload() {
+        let str = r#"let Bool.add = (b) { self || b }; True + False"#;
+        assert!(interpret_operators(str) == Literal::Bool(true));
+    }
+
+    #[test]
+    fn interpret_operator_self_not_clobbered() {
+        let str = r#"let Num.twice = () { 5.to_str; self * 2 }; 3.twice"#;
+        assert!(interpret_operators(str) == Literal::Num(6.0));
+    }
+
+    #[test]
+    fn interpret_format_str() {
+        let str = r#"let n = 3; "{n} + {n} = {n + n}, {[1, 'a', True]} {Nope}""#;
+        let expected = "3 + 3 = 6, [1, a, True] Nope";
+        assert!(interpret_operators(str) == Literal::Str(expected.to_string()));
+    }
+
+    #[test]
+    fn interpret_format_spec() {
+        let str = r#""[{3.14159:.2}|{1:>3}|{"ab":<4}|{"ab":*^6}|{"abc":.2}|{5:03}]""#;
+        let expected = "[3.14|  1|ab  |**ab**|ab|005]";
+        assert!(interpret_operators(str) == Literal::Str(expected.to_string()));
+        // Zeros go after the sign, unlike an explicit fill
+        let str = r#""[{-5:03}|{-1.5:06.2}|{-5:0>3}|{"-5":03}]""#;
+        let expected = "[-05|-01.50|0-5|0-5]";
+        assert_eq!(interpret_operators(str), Literal::Str(expected.to_string()));
+        let str = r#"Main.format(2, "-<3")"#;
+        assert!(interpret_operators(str) == Literal::Str("2--".to_string()));
+    }
+
+    #[test]
+    fn interpret_format_bad_spec() {
+        let tokens = tokenize_str(r#""{1:x}""#, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Invalid format specifier `x`")
+    }
+
+    #[test]
+    fn interpret_printable_set() {
+        let str = r#"let Main.to_string = () { "the main set" }; "{Main}!""#;
+        assert!(interpret_operators(str) == Literal::Str("the main set!".to_string()));
+        let str = r#"let Num.to_string = () { "num" }; "{[1, 2]}""#;
+        assert!(interpret_operators(str) == Literal::Str("[num, num]".to_string()));
+    }
+
+    #[test]
+    fn interpret_not_printable() {
+        let tokens = tokenize_str(r#""{Main}""#, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Set is not printable, it has no `to_string` method")
+    }
+
+    #[test]
+    fn interpret_match_literal() {
+        let str = r#"let name = (n) { match n { 0 => "zero", 1 => "one", _ => "many" } }; "{name(0)} {name(1)} {name(5)}""#;
+        assert!(interpret_operators(str) == Literal::Str("zero one many".to_string()));
+    }
+
+    #[test]
+    fn interpret_match_list() {
+        let str = r#"
+            let sum = (list) { match list { [] => 0, [head, ...tail] => head + sum(tail) } };
+            sum([1, 2, 3, 4])
+        "#;
+        assert!(interpret_operators(str) == Literal::Num(10.0));
+        let str = r#"match [1, 2] { [a] => a, [a, b, c] => c, [_, b] => b * 10 }"#;
+        assert!(interpret_operators(str) == Literal::Num(20.0));
+    }
+
+    #[test]
+    fn interpret_match_set() {
+        let str = r#"let Main.x = 5; match Main { { y } => y, { x: 4 } => 4, { x } => x + 1 }"#;
+        assert!(interpret_operators(str) == Literal::Num(6.0));
+    }
+
+    #[test]
+    fn interpret_match_guard() {
+        let str = r#"
+            let sign = (n) { match n { 0 => 0, n if n < 0 => -1, _ => 1 } };
+            "{sign(-5)} {sign(0)} {sign(3)}"
+        "#;
+        assert!(interpret_operators(str) == Literal::Str("-1 0 1".to_string()));
+    }
+
+    #[test]
+    fn interpret_match_bool() {
+        let str = r#"match 1 > 2 { True => "yes", False => "no" }"#;
+        assert!(interpret_operators(str) == Literal::Str("no".to_string()));
+    }
+
+    #[test]
+    fn interpret_match_no_arm() {
+        let tokens = tokenize_str(r#"match "a" { 1 => 1 }"#, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "No arm of the match matches the Str")
+    }
+
+    #[test]
+    fn interpret_destructure() {
+        let str = r#"let Main.x = 1; let [a, b, ...rest] = [2, 3, 4, 5]; let { x } = Main; "{x} {a} {b} {rest}""#;
+        assert!(interpret_operators(str) == Literal::Str("1 2 3 [4, 5]".to_string()));
+    }
+
+    #[test]
+    fn interpret_destructure_mismatch() {
+        let str = "let [a, [b, c]] = [1, [2]]";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Expected a list of 2 items but found 1");
+        assert!(err.pos.col == 8);
+
+        let str = "let { x, y } = Main";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Set has no member `x`");
+        assert!(err.pos.col == 6);
+    }
+
+    #[test]
+    fn interpret_funcall_destructure() {
+        let str = r#"let f = ([a, b], ...[c, d]) { a + b * c - d }; f([1, 2], 3, 4)"#;
+        assert!(interpret_operators(str) == Literal::Num(3.0));
+
+        let str = r#"let f = ([a, b]) { a }; f(5)"#;
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Expected a List but found a Num");
+        assert!(err.pos.col == 9);
+    }
+
+    #[test]
+    fn interpret_type() {
+        let str = r#"
+            type Point = (x, y = 0) {
+                let len = () { (self.x ** 2 + self.y ** 2) ** 0.5 };
+                let add = (other) { Point(self.x + other.x, self.y + other.y) };
+                let origin = Point.new(0);
+            };
+            let p = Point(3, 4);
+            let q = p + Point(1);
+            "{p.len} {q} {q.x} {Point.origin}"
+        "#;
+        let expected = "5 Point(x: 4, y: 4) 4 Point(x: 0, y: 0)";
+        assert!(interpret_operators(str) == Literal::Str(expected.to_string()));
+    }
+
+    #[test]
+    fn interpret_type_fields() {
+        let str = r#"
+            type Counter = (n) { let to_string = () { "Counter at {self.n}" } };
+            let c = Counter(1);
+            let c.n = c.n + 1;
+            let { n } = c;
+            "{c} {n} {match c { { n: 2 } => "two", _ => "other" }}"
+        "#;
+        let expected = "Counter at 2 2 two";
+        assert!(interpret_operators(str) == Literal::Str(expected.to_string()));
+    }
+
+    #[test]
+    fn interpret_type_constructor_arity() {
+        let str = "type Point = (x, y) {}; Point(1)";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "`Point` takes 2 arguments but 1 was given (defined at internal:0:0)");
+    }
+
+    #[test]
+    fn interpret_extend_builtin() {
+        let str = r#"
+            let Num.double = () { self * 2 };
+            let List.first = () { match self { [head, ..._] => head } };
+            "{4.double} {[3, 2, 1].first}"
+        "#;
+        assert!(interpret_operators(str) == Literal::Str("8 3".to_string()));
+    }
+
+    #[test]
+    fn interpret_bound_method() {
+        let str = r#"let apply = (f) { f(2) }; let add = 5.add; "{add(1)} {apply(10.sub)}""#;
+        assert!(interpret_operators(str) == Literal::Str("6 8".to_string()));
+    }
+
+    #[test]
+    fn interpret_extension_method() {
+        let str = r#"
+            let divisible = (self, n) { self % n == 0 };
+            let is_even = (self) { self.divisible(2) };
+            let apply = (f, n) { f(n) };
+            "{6.divisible(3)} {7.is_even} {divisible(9, 3)} {apply(10.divisible, 4)}"
+        "#;
+        let expected = "True False True False";
+        assert!(interpret_operators(str) == Literal::Str(expected.to_string()));
+    }
+
+    #[test]
+    fn interpret_extension_method_priority() {
+        let str = r#"let to_str = (self) { "extension" }; let Num.half = () { self / 2 }; "{5.to_str} {6.half}""#;
+        assert!(interpret_operators(str) == Literal::Str("5 3".to_string()));
+    }
+
+    #[test]
+    fn interpret_extension_method_needs_self() {
+        let str = "let double = (n) { n * 2 }; 5.double";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Num has no member `double`");
+
+        let str = "let is_main = (self) { True }; Main.is_main";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        let tree = parse_file(tokens).unwrap();
+        let err = tree.interpret(&mut init_scope()).unwrap_err();
+        assert!(err.msg == "Set has no member `is_main`");
+    }
 }
@@ -1,226 +1,514 @@
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::env;
+use std::fs;
 
-use crate::interpreter::Interpret;
+use crate::host::{function, method};
+use crate::interpreter::call;
+use crate::interpreter::display;
+use crate::interpreter::format_spec;
+use crate::interpreter::to_string;
 use crate::interpreter::InterpretingError;
 use crate::interpreter::Scope;
+use crate::testing::diff;
 use crate::types::*;
 use crate::FilePos;
 
+/// What a program can do outside of the interpreter, so untrusted programs can be sandboxed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// `Main.write` and `Main.dump`
+    pub write: bool,
+    /// `Fs`, which reads files, and `include`
+    pub fs: bool,
+    /// `Process`, which reads the environment
+    pub process: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            write: true,
+            fs: true,
+            process: true,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Nothing but computing values
+    pub fn none() -> Self {
+        Self {
+            write: false,
+            fs: false,
+            process: false,
+        }
+    }
+}
+
+/// The signatures and descriptions of the intrinsics, in the style of the signature comments
+/// of sand code, for `sand doc`
+pub const DOCS: &[(&str, &str, &str)] = &[
+    (
+        "Main.write",
+        "(int, Printable){nope}",
+        "Writes `string` to stdout if `stream` is 1, or to stderr if it is 2.",
+    ),
+    (
+        "Main.format",
+        "(Printable, str){str}",
+        "Formats `val` with a format specifier, like `\"{val:spec}\"` does.",
+    ),
+    (
+        "Main.dump",
+        "(){nope}",
+        "Prints every variable in scope and stops the program.",
+    ),
+    (
+        "Main.raise",
+        "(*){nope}",
+        "Fails with `err` as the error value, which `try` turns into an error value.",
+    ),
+    (
+        "Main.panic",
+        "(str){nope}",
+        "Fails with the message `msg`, which `try` can't catch.",
+    ),
+    (
+        "Main.is_err",
+        "(*){bool}",
+        "Whether `val` is an error value.",
+    ),
+    (
+        "Main.assert",
+        "(bool){nope}",
+        "Fails the program, or the test it runs in, if `cond` is false. Unlike `Main.raise`, \
+         this can't be caught with `try`.",
+    ),
+    (
+        "Main.assert_eq",
+        "(*, *){nope}",
+        "Fails like `Main.assert` if `actual` is not equal to `expected`, showing how they \
+         differ.",
+    ),
+    (
+        "Fs.read",
+        "(str){str}",
+        "The contents of the file at `path`.",
+    ),
+    (
+        "Fs.exists",
+        "(str){bool}",
+        "Whether there is a file or directory at `path`.",
+    ),
+    (
+        "Process.env",
+        "(str){str}",
+        "The value of the environment variable `name`, or Nope if it isn't set.",
+    ),
+    ("Nope.to_string", "(){str}", "`\"Nope\"`."),
+    ("Str.add", "(str){str}", "The string followed by `n`."),
+    ("Str.eq", "(str){bool}", "Whether the strings are equal."),
+    ("Str.ne", "(str){bool}", "Whether the strings differ."),
+    ("Str.to_string", "(){str}", "The string itself."),
+    (
+        "Char.eq",
+        "(char){bool}",
+        "Whether the characters are equal.",
+    ),
+    ("Char.ne", "(char){bool}", "Whether the characters differ."),
+    ("Char.to_string", "(){str}", "The character as a string."),
+    (
+        "Num.add",
+        "(num){num}",
+        "The sum of the numbers, `self + n`.",
+    ),
+    (
+        "Num.sub",
+        "(num){num}",
+        "The difference of the numbers, `self - n`.",
+    ),
+    (
+        "Num.mul",
+        "(num){num}",
+        "The product of the numbers, `self * n`.",
+    ),
+    (
+        "Num.div",
+        "(num){num}",
+        "The quotient of the numbers, `self / n`.",
+    ),
+    (
+        "Num.mod",
+        "(num){num}",
+        "The remainder of dividing the number by `n`, `self % n`.",
+    ),
+    (
+        "Num.pow",
+        "(num){num}",
+        "The number to the power of `n`, `self ** n`.",
+    ),
+    (
+        "Num.eq",
+        "(num){bool}",
+        "Whether the numbers are equal, `self == n`.",
+    ),
+    (
+        "Num.ne",
+        "(num){bool}",
+        "Whether the numbers differ, `self != n`.",
+    ),
+    (
+        "Num.lt",
+        "(num){bool}",
+        "Whether the number is less than `n`, `self < n`.",
+    ),
+    (
+        "Num.gt",
+        "(num){bool}",
+        "Whether the number is greater than `n`, `self > n`.",
+    ),
+    (
+        "Num.le",
+        "(num){bool}",
+        "Whether the number is at most `n`, `self <= n`.",
+    ),
+    (
+        "Num.ge",
+        "(num){bool}",
+        "Whether the number is at least `n`, `self >= n`.",
+    ),
+    ("Num.neg", "(){num}", "The negated number, `-self`."),
+    (
+        "Num.times",
+        "((int){*}){nope}",
+        "Calls `f` with every integer from 0 up to the number.",
+    ),
+    ("Num.to_str", "(){str}", "The number as a string."),
+    ("Num.to_string", "(){str}", "The number as a string."),
+    (
+        "Bool.then",
+        "((){*}){nope}",
+        "Calls `f` if the boolean is True.",
+    ),
+    (
+        "Bool.and",
+        "(bool){bool}",
+        "Whether both booleans are True, `self && n`.",
+    ),
+    (
+        "Bool.or",
+        "(bool){bool}",
+        "Whether either boolean is True, `self || n`.",
+    ),
+    ("Bool.eq", "(bool){bool}", "Whether the booleans are equal."),
+    ("Bool.ne", "(bool){bool}", "Whether the booleans differ."),
+    ("Bool.not", "(){bool}", "The negated boolean, `!self`."),
+    ("Bool.to_string", "(){str}", "`\"True\"` or `\"False\"`."),
+    (
+        "List.to_string",
+        "(){str}",
+        "The items of the list, like `[1, 2]`.",
+    ),
+    (
+        "Fun.to_string",
+        "(){str}",
+        "Where the function is defined, like `<function at main.sand:3:9>`.",
+    ),
+    ("Err.value", "(){*}", "The value the error was raised with."),
+    (
+        "Err.or",
+        "(*){*}",
+        "`default`, since the value is an error.",
+    ),
+    ("Err.raise", "(){nope}", "Raises the error again."),
+    (
+        "Err.to_string",
+        "(){str}",
+        "The value the error was raised with, like `Err(5)`.",
+    ),
+    (
+        "Any.value",
+        "(){*}",
+        "The value itself, since it isn't an error.",
+    ),
+    (
+        "Any.or",
+        "(*){*}",
+        "The value itself, since it isn't an error. `Bool.or` is the logical or instead.",
+    ),
+];
+
 /* ======== MAIN ======== */
 pub fn init_main() -> Literal {
-    let mut main: Scope = HashMap::new();
+    let mut main = HashMap::new();
 
     main.insert(
         "write".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("stream"), Var::new("string")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let stream = scope.get("stream").unwrap().clone().as_int().unwrap();
-                let string = scope.get("string").unwrap().clone().as_str().unwrap();
+        function(
+            &["stream", "string"],
+            |scope: &mut Scope, stream: i64, string: Literal| {
+                let string = to_string(string, scope, &FilePos::temp())?;
                 match stream {
                     1 => println!("{}", string),
                     2 => eprintln!("{}", string),
-                    _ => unreachable!(),
+                    _ => {
+                        return Err(InterpretingError::new(
+                            &format!("Unknown stream `{}`", stream),
+                            &FilePos::temp(),
+                        ))
+                    }
                 }
-                Ok(Literal::Nope)
-            }),
-        })),
+                Ok(())
+            },
+        ),
+    );
+
+    main.insert(
+        "format".to_string(),
+        function(
+            &["val", "spec"],
+            |scope: &mut Scope, val: Literal, spec: String| {
+                format_spec(val, &spec, scope, &FilePos::temp())
+            },
+        ),
     );
 
     main.insert(
         "dump".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                println!("Dumping scope:");
-                for (var, val) in scope.iter() {
-                    println!("== {}: {:?}", var, val)
+        function(&[], |scope: &mut Scope| -> Result<(), InterpretingError> {
+            println!("Dumping scope:");
+            for (var, val) in scope.iter() {
+                println!("== {}: {:?}", var, val)
+            }
+            Err(InterpretingError::new(
+                "Exiting after dumping scope",
+                &FilePos::internal(),
+            ))
+        }),
+    );
+
+    main.insert(
+        "raise".to_string(),
+        function(&["err"], |err: Literal| -> Result<(), InterpretingError> {
+            Err(InterpretingError::raise(err, &FilePos::temp()))
+        }),
+    );
+
+    main.insert(
+        "panic".to_string(),
+        function(&["msg"], |msg: String| -> Result<(), InterpretingError> {
+            Err(InterpretingError::panic(&msg, &FilePos::temp()))
+        }),
+    );
+
+    main.insert(
+        "is_err".to_string(),
+        function(&["val"], |val: Literal| matches!(val, Literal::Err(..))),
+    );
+
+    main.insert(
+        "assert".to_string(),
+        function(&["cond"], |cond: bool| -> Result<(), InterpretingError> {
+            match cond {
+                true => Ok(()),
+                false => Err(InterpretingError::assertion(
+                    "the condition is False",
+                    &FilePos::temp(),
+                )),
+            }
+        }),
+    );
+
+    main.insert(
+        "assert_eq".to_string(),
+        function(
+            &["actual", "expected"],
+            |scope: &mut Scope,
+             actual: Literal,
+             expected: Literal|
+             -> Result<(), InterpretingError> {
+                let diff = diff(&expected, &actual, scope, &FilePos::temp())?;
+                match diff.is_empty() {
+                    true => Ok(()),
+                    false => Err(InterpretingError::assertion(
+                        &format!(
+                            "the values are not equal (- expected, + actual)\n{}",
+                            diff.join("\n")
+                        ),
+                        &FilePos::temp(),
+                    )),
                 }
-                Err(InterpretingError::new(
-                    "Exiting after dumping scope",
-                    &FilePos::internal(),
-                ))
-            }),
-        })),
+            },
+        ),
     );
 
     Literal::Set(main)
 }
 
+/* ======== FILE SYSTEM ======== */
+pub fn init_fs() -> Literal {
+    let mut fs = HashMap::new();
+
+    fs.insert(
+        "read".to_string(),
+        function(&["path"], |path: String| {
+            fs::read_to_string(&path).map_err(|err| {
+                InterpretingError::new(
+                    &format!("Cannot read `{}` because: {}", path, err),
+                    &FilePos::temp(),
+                )
+            })
+        }),
+    );
+
+    fs.insert(
+        "exists".to_string(),
+        function(&["path"], |path: String| {
+            std::path::Path::new(&path).exists()
+        }),
+    );
+
+    Literal::Set(fs)
+}
+
+/* ======== PROCESS ======== */
+pub fn init_process() -> Literal {
+    let mut process = HashMap::new();
+
+    process.insert(
+        "env".to_string(),
+        function(&["name"], |name: String| match env::var(name) {
+            Ok(val) => Literal::Str(val),
+            Err(..) => Literal::Nope,
+        }),
+    );
+
+    Literal::Set(process)
+}
+
+/// The built-in `to_string` method of every type
+fn init_to_string() -> Literal {
+    method(&[], |scope: &mut Scope, selff: Literal| {
+        display(selff, scope, &FilePos::temp())
+    })
+}
+
 /* ======== NOPE ======== */
 pub fn init_nope() -> Literal {
     let mut nope = HashMap::new();
+    nope.insert("to_string".to_string(), init_to_string());
     Literal::Set(nope)
 }
 
 /* ======== STRING ======== */
 pub fn init_str() -> Literal {
     let mut str = HashMap::new();
+    str.insert(
+        "add".to_string(),
+        method(&["n"], |selff: String, n: String| selff + &n),
+    );
+    str.insert(
+        "eq".to_string(),
+        method(&["n"], |selff: String, n: String| selff == n),
+    );
+    str.insert(
+        "ne".to_string(),
+        method(&["n"], |selff: String, n: String| selff != n),
+    );
+    str.insert("to_string".to_string(), init_to_string());
     Literal::Set(str)
 }
 
 /* ======== CHAR ======== */
 pub fn init_char() -> Literal {
     let mut char = HashMap::new();
+    char.insert(
+        "eq".to_string(),
+        method(&["n"], |selff: char, n: char| selff == n),
+    );
+    char.insert(
+        "ne".to_string(),
+        method(&["n"], |selff: char, n: char| selff != n),
+    );
+    char.insert("to_string".to_string(), init_to_string());
     Literal::Set(char)
 }
 
 /* ======== NUMBER ======== */
 pub fn init_num() -> Literal {
     let mut num = HashMap::new();
-    // num.add_member(Var::new("self"), Literal::Number(number));
-
     num.insert(
         "add".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff + n))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff + n),
     );
-
     num.insert(
         "sub".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff - n))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff - n),
     );
-
     num.insert(
         "mul".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff * n))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff * n),
     );
-
     num.insert(
         "div".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff / n))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff / n),
     );
-
     num.insert(
         "mod".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff % n))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff % n),
     );
-
     num.insert(
         "pow".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Num(selff.powf(n)))
-            }),
-        })),
+        method(&["n"], |selff: f64, n: f64| selff.powf(n)),
     );
     num.insert(
         "eq".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("n")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                let n = scope.get("n").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Bool(selff == n))
-            }),
-        })),
-    );
-    //
-    // num.insert(
-    //     "ne".to_string(),
-    //     Literal::Fun(Callable::Intr(Intrinsic {
-    //         args: vec!["n"],
-    //         fun_interpret: Rc::new(|scope: &mut Scope| {
-    //             let selff = scope.get(&Var::new("self")).unwrap().as_number().unwrap();
-    //             let n = scope.get(&Var::new("n")).unwrap().as_number().unwrap();
-    //             Literal::Number(selff != n)
-    //         }),
-    //     })),
-    // );
-    //
-    // num.add_member(
-    //     Var::new("lt"),
-    //     Literal::Callable(Callable::Intrinsic(Intrinsic::new(
-    //         vec![Var::new("n")],
-    //         Rc::new(|scope: &mut Scope| {
-    //             let selff = scope.get(&Var::new("self")).unwrap().as_number().unwrap();
-    //             let n = scope.get(&Var::new("n")).unwrap().as_number().unwrap();
-    //             Literal::Boolean(selff < n)
-    //         }),
-    //     ))),
-    // );
-    //
-    // num.add_member(
-    //     Var::new("gt"),
-    //     Literal::Callable(Callable::Intrinsic(Intrinsic::new(
-    //         vec![Var::new("n")],
-    //         Rc::new(|scope: &mut Scope| {
-    //             let selff = scope.get(&Var::new("self")).unwrap().as_number().unwrap();
-    //             let n = scope.get(&Var::new("n")).unwrap().as_number().unwrap();
-    //             Literal::Boolean(selff > n)
-    //         }),
-    //     ))),
-    // );
-    //
+        method(&["n"], |selff: f64, n: f64| selff == n),
+    );
+    num.insert(
+        "ne".to_string(),
+        method(&["n"], |selff: f64, n: f64| selff != n),
+    );
+    num.insert(
+        "lt".to_string(),
+        method(&["n"], |selff: f64, n: f64| selff < n),
+    );
+    num.insert(
+        "gt".to_string(),
+        method(&["n"], |selff: f64, n: f64| selff > n),
+    );
+    num.insert(
+        "le".to_string(),
+        method(&["n"], |selff: f64, n: f64| selff <= n),
+    );
+    num.insert(
+        "ge".to_string(),
+        method(&["n"], |selff: f64, n: f64| selff >= n),
+    );
+    num.insert("neg".to_string(), method(&[], |selff: f64| -selff));
+
     num.insert(
         "times".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("f")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_int().unwrap();
+        method(
+            &["f"],
+            |scope: &mut Scope, selff: i64, f: Literal| -> Result<(), InterpretingError> {
                 for n in 0..selff {
-                    let call = Value::FunCall {
-                        fun: Box::new(Value::Lit(scope.get("f").unwrap().clone())),
-                        params: vec![Value::Lit(Literal::Num(n as f64))],
-                        pos: FilePos::internal(),
-                    };
-                    call.interpret(&mut scope.clone()).unwrap(); // TODO: handle unwrap
+                    let args = vec![Literal::Num(n as f64)];
+                    let name = Some("f".to_string());
+                    call(f.clone(), name, None, args, scope, &FilePos::internal())?;
                 }
-                Ok(Literal::Nope)
-            }),
-        })),
+                Ok(())
+            },
+        ),
     );
 
     // TODO: Implement this in the standard library instead
     num.insert(
         "to_str".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: Vec::new(),
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_num().unwrap();
-                Ok(Literal::Str(selff.to_string()))
-            }),
-        })),
+        method(&[], |selff: f64| selff.to_string()),
     );
 
+    num.insert("to_string".to_string(), init_to_string());
     Literal::Set(num)
 }
 
@@ -230,46 +518,145 @@ pub fn init_bool() -> Literal {
 
     bool.insert(
         "then".to_string(),
-        Literal::Fun(Callable::Intr(Intrinsic {
-            args: vec![Var::new("f")],
-            fun_interpret: Rc::new(|scope: &mut Scope| {
-                let selff = scope.get("self").unwrap().clone().as_bool().unwrap();
+        method(
+            &["f"],
+            |scope: &mut Scope, selff: bool, f: Literal| -> Result<(), InterpretingError> {
                 if selff {
-                    let call = Value::FunCall {
-                        fun: Box::new(Value::Lit(scope.get("f").unwrap().clone())),
-                        params: Vec::new(),
-                        pos: FilePos::internal(),
-                    };
-                    call.interpret(&mut scope.clone()).unwrap(); // TODO: handle unwrap
+                    let name = Some("f".to_string());
+                    call(f, name, None, Vec::new(), scope, &FilePos::internal())?;
                 }
-                Ok(Literal::Nope)
-            }),
-        })),
+                Ok(())
+            },
+        ),
+    );
+
+    bool.insert(
+        "and".to_string(),
+        method(&["n"], |selff: bool, n: bool| selff && n),
+    );
+    bool.insert(
+        "or".to_string(),
+        method(&["n"], |selff: bool, n: bool| selff || n),
     );
+    bool.insert(
+        "eq".to_string(),
+        method(&["n"], |selff: bool, n: bool| selff == n),
+    );
+    bool.insert(
+        "ne".to_string(),
+        method(&["n"], |selff: bool, n: bool| selff != n),
+    );
+    bool.insert("not".to_string(), method(&[], |selff: bool| !selff));
+
+    bool.insert("to_string".to_string(), init_to_string());
     Literal::Set(bool)
 }
 
 /* ======== LIST ======== */
 pub fn init_list() -> Literal {
     let mut list = HashMap::new();
+    list.insert("to_string".to_string(), init_to_string());
     Literal::Set(list)
 }
 
 /* ======== CALLABLE ======== */
 pub fn init_fun() -> Literal {
     let mut fun = HashMap::new();
+    fun.insert("to_string".to_string(), init_to_string());
     Literal::Set(fun)
 }
 
+/* ======== ERROR ======== */
+pub fn init_err() -> Literal {
+    let mut err = HashMap::new();
+
+    err.insert(
+        "value".to_string(),
+        method(
+            &[],
+            |selff: Literal| -> Result<Literal, InterpretingError> { Ok(selff.as_err()?) },
+        ),
+    );
+
+    err.insert(
+        "or".to_string(),
+        method(&["default"], |_: Literal, default: Literal| default),
+    );
+
+    err.insert(
+        "raise".to_string(),
+        method(&[], |selff: Literal| -> Result<(), InterpretingError> {
+            Err(InterpretingError::raise(selff.as_err()?, &FilePos::temp()))
+        }),
+    );
+
+    err.insert("to_string".to_string(), init_to_string());
+    Literal::Set(err)
+}
+
+/* ======== ANY ======== */
+/// Methods of every type without a member of the same name, which make the values `try`
+/// returns on success work like error values
+pub fn init_any() -> Literal {
+    let mut any = HashMap::new();
+
+    any.insert("value".to_string(), method(&[], |selff: Literal| selff));
+
+    any.insert(
+        "or".to_string(),
+        method(&["default"], |selff: Literal, _: Literal| selff),
+    );
+
+    Literal::Set(any)
+}
+
 pub fn init_scope() -> Scope {
-    let mut scope = HashMap::new();
-    scope.insert("Main".to_string(), init_main());
-    scope.insert("Nope".to_string(), init_nope());
-    scope.insert("Str".to_string(), init_str());
-    scope.insert("Char".to_string(), init_char());
-    scope.insert("Num".to_string(), init_num());
-    scope.insert("Bool".to_string(), init_bool());
-    scope.insert("List".to_string(), init_list());
-    scope.insert("Fun".to_string(), init_fun());
+    init_scope_with(Capabilities::default())
+}
+
+/// Names `val` if it is an intrinsic, like `Num.add`
+pub(crate) fn name_intrinsic(val: &mut Literal, name: &str) {
+    if let Literal::Fun(Callable::Intr(intrinsic)) = val {
+        intrinsic.name = Some(name.to_string());
+    }
+}
+
+/// Creates the global scope, without the intrinsics `capabilities` don't allow
+pub fn init_scope_with(capabilities: Capabilities) -> Scope {
+    let mut scope = Scope::new();
+    let mut main = init_main();
+    if let Literal::Set(main) = &mut main {
+        if !capabilities.write {
+            main.remove("write");
+            main.remove("dump");
+        }
+    }
+    let mut sets = vec![("Main", main)];
+    if capabilities.fs {
+        sets.push(("Fs", init_fs()));
+    }
+    if capabilities.process {
+        sets.push(("Process", init_process()));
+    }
+    sets.extend([
+        ("Nope", init_nope()),
+        ("Str", init_str()),
+        ("Char", init_char()),
+        ("Num", init_num()),
+        ("Bool", init_bool()),
+        ("List", init_list()),
+        ("Fun", init_fun()),
+        ("Err", init_err()),
+        ("Any", init_any()),
+    ]);
+    for (name, mut set) in sets {
+        if let Literal::Set(members) = &mut set {
+            for (member, val) in members.iter_mut() {
+                name_intrinsic(val, &format!("{}.{}", name, member));
+            }
+        }
+        scope.insert(name.to_string(), set);
+    }
+    scope.state.borrow_mut().capabilities = capabilities;
     scope
 }
@@ -0,0 +1,182 @@
+//! sand as a library, for embedding the interpreter in other programs through [`Engine`]
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// pub mod compiler;
+pub mod coverage;
+pub mod debugger;
+pub mod doc;
+mod engine;
+pub mod formatter;
+pub mod host;
+pub mod interpreter;
+pub mod intrinsics;
+pub mod lsp;
+pub mod parser;
+pub mod profiler;
+pub mod testing;
+pub mod tokenizer;
+pub mod types;
+
+pub use engine::Engine;
+
+use interpreter::Frame;
+use interpreter::InterpretingError;
+use parser::ParseError;
+use tokenizer::Token;
+use tokenizer::TokenError;
+use types::TypeError;
+
+// TODO: Implement the compiler (llvm?)
+// TODO: Implement typechecking
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct FilePos {
+    file: PathBuf,
+    row: usize,
+    col: usize,
+}
+
+impl fmt::Display for FilePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.row, self.col)
+    }
+}
+
+impl fmt::Debug for FilePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.file.file_name().unwrap().to_str().unwrap(),
+            self.row,
+            self.col
+        )
+    }
+}
+
+impl FilePos {
+    pub fn new(file: &Path, row: usize, col: usize) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            row,
+            col,
+        }
+    }
+
+    pub fn internal() -> Self {
+        Self {
+            file: PathBuf::from("internal"),
+            row: 0,
+            col: 0,
+        }
+    }
+
+    pub fn temp() -> Self {
+        Self {
+            file: PathBuf::from("undefined"),
+            row: 0,
+            col: 0,
+        }
+    }
+}
+
+impl From<&[Token]> for FilePos {
+    fn from(tokens: &[Token]) -> Self {
+        tokens.first().unwrap().pos.clone()
+    }
+}
+
+pub struct SandError {
+    pos: FilePos,
+    msg: String,
+    trace: Vec<Frame>,
+}
+
+impl SandError {
+    pub(crate) fn new(msg: &str, pos: &FilePos) -> Self {
+        Self {
+            pos: pos.clone(),
+            msg: msg.to_string(),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for SandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.trace.is_empty() {
+            writeln!(f, "Traceback (most recent call last):")?;
+            // Recursion repeats the same frame, so only the first of a run is printed
+            let mut i = 0;
+            while i < self.trace.len() {
+                let frame = &self.trace[i];
+                let repeats = self.trace[i + 1..]
+                    .iter()
+                    .take_while(|other| *other == frame)
+                    .count();
+                // Tail calls leave a marker where the frames they took the place of were
+                if let Some(replaced) = &frame.replaced {
+                    writeln!(f, "    {}", replaced)?;
+                    match frame.tail_calls {
+                        1 => writeln!(f, "    [Replaced by a tail call]")?,
+                        n => writeln!(f, "    [Replaced by {} tail calls]", n)?,
+                    }
+                }
+                writeln!(f, "    {}", frame)?;
+                if repeats > 0 {
+                    writeln!(f, "    [Previous frame repeated {} more times]", repeats)?;
+                }
+                i += repeats + 1;
+            }
+        }
+        write!(f, "{}: {}", self.pos, self.msg)
+    }
+}
+
+impl fmt::Debug for SandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pos, self.msg)
+    }
+}
+
+impl From<TokenError> for SandError {
+    fn from(token_error: TokenError) -> Self {
+        Self {
+            pos: token_error.pos,
+            msg: format!("TOKEN_ERROR: {}", token_error.msg),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl From<ParseError> for SandError {
+    fn from(parse_error: ParseError) -> Self {
+        Self {
+            pos: parse_error.pos,
+            msg: format!("PARSE_ERROR: {}", parse_error.msg),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl From<InterpretingError> for SandError {
+    fn from(parse_error: InterpretingError) -> Self {
+        Self {
+            pos: parse_error.pos,
+            msg: format!("INTERPRETING_ERROR: {}", parse_error.msg),
+            trace: parse_error.trace,
+        }
+    }
+}
+
+impl From<TypeError> for SandError {
+    fn from(parse_error: TypeError) -> Self {
+        Self {
+            pos: parse_error.pos,
+            msg: format!("TYPE_ERROR: {}", parse_error.msg),
+            trace: Vec::new(),
+        }
+    }
+}
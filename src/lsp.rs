@@ -0,0 +1,1165 @@
+//! A language server for sand, speaking the Language Server Protocol over stdio
+//!
+//! Positions are counted in characters rather than the UTF-16 code units of the protocol, which
+//! only differs for characters outside of the basic multilingual plane.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value as Json};
+
+use crate::intrinsics::init_scope;
+use crate::parser::{parse_file, KEYWORDS};
+use crate::tokenizer::tokenize_str;
+use crate::types::*;
+use crate::FilePos;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// LSP symbol kinds
+const SYMBOL_CLASS: u8 = 5;
+const SYMBOL_METHOD: u8 = 6;
+const SYMBOL_PROPERTY: u8 = 7;
+const SYMBOL_FIELD: u8 = 8;
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+
+// LSP completion item kinds
+const COMPLETION_METHOD: u8 = 2;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_CLASS: u8 = 7;
+const COMPLETION_MODULE: u8 = 9;
+const COMPLETION_PROPERTY: u8 = 10;
+const COMPLETION_KEYWORD: u8 = 14;
+
+/// Serves one client reading from `input` and writing to `output` until it exits, returning the
+/// exit code the server should exit with
+pub fn serve<R: BufRead, W: Write>(mut input: R, output: W) -> io::Result<i32> {
+    let mut server = Server::new(output);
+    while let Some(body) = read_message(&mut input)? {
+        let code = match serde_json::from_slice(&body) {
+            Ok(msg) => server.handle(msg)?,
+            Err(err) => {
+                server.respond(Json::Null, Err((PARSE_ERROR, err.to_string())))?;
+                None
+            }
+        };
+        if let Some(code) = code {
+            return Ok(code);
+        }
+    }
+    Ok(server.exit_code())
+}
+
+/// Reads the body of the next message, or None at the end of the input
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(len) = line.strip_prefix("Content-Length:") {
+            length = len.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message<W: Write>(output: &mut W, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// An open file, with the tree of the last version of it which could be parsed
+struct Document {
+    path: PathBuf,
+    text: String,
+    tree: Statements,
+}
+
+/// The members of the sets in the global scope, with the parameters of the functions among them
+type Builtins = BTreeMap<String, BTreeMap<String, Option<Vec<String>>>>;
+
+struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, Document>,
+    builtins: Builtins,
+    shut_down: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Self {
+        let builtins = init_scope()
+            .iter()
+            .filter_map(|(name, val)| match val {
+                Literal::Set(members) => {
+                    let members = members
+                        .iter()
+                        .map(|(member, val)| (member.clone(), intrinsic_params(val)))
+                        .collect();
+                    Some((name.clone(), members))
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            output,
+            documents: HashMap::new(),
+            builtins,
+            shut_down: false,
+        }
+    }
+
+    /// The protocol asks servers to fail when they exit without being shut down
+    fn exit_code(&self) -> i32 {
+        if self.shut_down {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Handles a message, returning the exit code if the client wants the server to exit
+    fn handle(&mut self, msg: Json) -> io::Result<Option<i32>> {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        match msg.get("id") {
+            // Responses to requests of the server, which it never makes
+            Some(..) if method.is_empty() => {}
+            Some(id) => {
+                let result = if self.shut_down {
+                    Err((INVALID_REQUEST, "The server is shut down".to_string()))
+                } else {
+                    self.request(method, params)
+                };
+                self.respond(id.clone(), result)?;
+            }
+            None => match method {
+                "textDocument/didOpen" => {
+                    let doc = &params["textDocument"];
+                    let uri = doc["uri"].as_str().unwrap_or_default();
+                    let text = doc["text"].as_str().unwrap_or_default();
+                    self.update(uri, text)?;
+                }
+                "textDocument/didChange" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    // The server asks for the full text on every change
+                    let changes = params["contentChanges"].as_array();
+                    if let Some(text) = changes.and_then(|changes| changes.last()) {
+                        self.update(uri, text["text"].as_str().unwrap_or_default())?;
+                    }
+                }
+                "textDocument/didClose" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                    self.documents.remove(uri);
+                    self.notify(
+                        "textDocument/publishDiagnostics",
+                        json!({ "uri": uri, "diagnostics": [] }),
+                    )?;
+                }
+                "exit" => return Ok(Some(self.exit_code())),
+                _ => {}
+            },
+        }
+        Ok(None)
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        Ok(match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "sand", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shut_down = true;
+                Json::Null
+            }
+            "textDocument/definition" => self.at(params, Self::definition),
+            "textDocument/hover" => self.at(params, Self::hover),
+            "textDocument/completion" => self.at(params, Self::completion),
+            "textDocument/documentSymbol" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                match self.documents.get(uri) {
+                    Some(doc) => Json::Array(symbols(&doc.tree)),
+                    None => Json::Null,
+                }
+            }
+            _ => return Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", method))),
+        })
+    }
+
+    /// Answers a request about a position in an open document
+    fn at(&self, params: &Json, answer: fn(&Self, &Document, usize, usize) -> Json) -> Json {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+        match self.documents.get(uri) {
+            Some(doc) => answer(self, doc, line, character),
+            None => Json::Null,
+        }
+    }
+
+    fn respond(&mut self, id: Json, result: Result<Json, (i64, String)>) -> io::Result<()> {
+        let msg = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        write_message(&mut self.output, &msg)
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        let msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.output, &msg)
+    }
+
+    /// Stores the new text of a document and publishes its errors
+    fn update(&mut self, uri: &str, text: &str) -> io::Result<()> {
+        let path = uri_to_path(uri);
+        let (tree, diagnostics) = match analyze(&path, text) {
+            Ok(tree) => (Some(tree), Vec::new()),
+            Err((pos, msg)) => (None, vec![diagnostic(text, &pos, &msg)]),
+        };
+        let old = self.documents.remove(uri).map(|doc| doc.tree);
+        let tree = tree.or(old).unwrap_or_else(|| Statements(Vec::new()));
+        let doc = Document {
+            path,
+            text: text.to_string(),
+            tree,
+        };
+        self.documents.insert(uri.to_string(), doc);
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    /// The text of a file, preferring the version open in the editor
+    fn source(&self, path: &Path) -> Option<String> {
+        self.documents
+            .values()
+            .find(|doc| doc.path == path)
+            .map(|doc| doc.text.clone())
+            .or_else(|| fs::read_to_string(path).ok())
+    }
+
+    /// The bindings of the files included by the document, and the files they include
+    fn included(&self, doc: &Document) -> Vec<Binding> {
+        let mut bindings = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = Collector::collect(&doc.tree).includes;
+        while let Some(path) = queue.pop() {
+            if !visited.insert(path.clone()) {
+                continue;
+            }
+            let tree = self
+                .source(&path)
+                .and_then(|text| analyze(&path, &text).ok());
+            if let Some(tree) = tree {
+                let collector = Collector::collect(&tree);
+                bindings.extend(collector.bindings);
+                queue.extend(collector.includes);
+            }
+        }
+        bindings
+    }
+
+    /// Finds the binding of `name` used at `at`
+    ///
+    /// Scoping is dynamic, so which binding is used can't be known before running the program.
+    /// This picks the closest binding before the use, then ones from included files, and then
+    /// the first one after the use.
+    fn resolve(&self, doc: &Document, name: &str, at: (usize, usize)) -> Option<Binding> {
+        let local: Vec<Binding> = Collector::collect(&doc.tree)
+            .bindings
+            .into_iter()
+            .filter(|binding| binding.name == name)
+            .collect();
+        local
+            .iter()
+            .rev()
+            .find(|binding| (binding.pos.row, binding.pos.col) <= at)
+            .cloned()
+            .or_else(|| {
+                self.included(doc)
+                    .into_iter()
+                    .rev()
+                    .find(|binding| binding.name == name)
+            })
+            .or_else(|| local.first().cloned())
+    }
+
+    /// The set whose members a value written as `receiver` has
+    fn set_of(&self, receiver: &str) -> String {
+        if receiver.starts_with('"') {
+            "Str".to_string()
+        } else if receiver.parse::<f64>().is_ok() {
+            "Num".to_string()
+        } else {
+            receiver.to_string()
+        }
+    }
+
+    fn definition(&self, doc: &Document, line: usize, character: usize) -> Json {
+        if let Some(path) = include_at(doc, line, character) {
+            let start = json!({ "line": 0, "character": 0 });
+            return json!({
+                "uri": path_to_uri(&path),
+                "range": { "start": start, "end": start },
+            });
+        }
+        let (word, _, _) = match word_at(&doc.text, line, character) {
+            Some(word) => word,
+            None => return Json::Null,
+        };
+        match self.resolve(doc, &word, (line + 1, character + 1)) {
+            Some(binding) => json!({
+                "uri": path_to_uri(&binding.pos.file),
+                "range": range(&binding.pos, binding.name.chars().count()),
+            }),
+            None => Json::Null,
+        }
+    }
+
+    fn hover(&self, doc: &Document, line: usize, character: usize) -> Json {
+        let (word, start, receiver) = match word_at(&doc.text, line, character) {
+            Some(word) => word,
+            None => return Json::Null,
+        };
+        let builtin = receiver
+            .map(|receiver| self.set_of(&receiver))
+            .and_then(|set| {
+                let params = self.builtins.get(&set)?.get(&word)?;
+                Some(format!("{}.{}{}", set, word, render_params(params)))
+            });
+        let contents = if let Some(signature) = builtin {
+            markdown(&signature, "Built-in")
+        } else if let Some(binding) = self.resolve(doc, &word, (line + 1, start + 1)) {
+            let comment = self
+                .source(&binding.pos.file)
+                .map(|text| doc_comment(&text, binding.pos.row))
+                .unwrap_or_default();
+            markdown(&binding.signature(), &comment)
+        } else if let Some(members) = self.builtins.get(&word) {
+            let members: Vec<String> = members.keys().map(|name| format!("`{}`", name)).collect();
+            markdown(
+                &word,
+                &format!("Built-in set with the members {}", members.join(", ")),
+            )
+        } else {
+            return Json::Null;
+        };
+        let pos = FilePos::new(&doc.path, line + 1, start + 1);
+        json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": range(&pos, word.chars().count()),
+        })
+    }
+
+    fn completion(&self, doc: &Document, line: usize, character: usize) -> Json {
+        let text: Vec<char> = doc
+            .text
+            .lines()
+            .nth(line)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        let end = character.min(text.len());
+        let start = word_start(&text, end);
+        let mut bindings = Collector::collect(&doc.tree).bindings;
+        bindings.extend(self.included(doc));
+
+        let mut items = BTreeMap::new();
+        if let Some(receiver) = receiver_before(&text, start) {
+            let set = self.set_of(&receiver);
+            if let Some(members) = self.builtins.get(&set) {
+                for (name, params) in members {
+                    let detail = format!("{}.{}{}", set, name, render_params(params));
+                    items.insert(name.clone(), (COMPLETION_METHOD, detail));
+                }
+            }
+            for binding in &bindings {
+                if binding.kind == Kind::Type && binding.name == set {
+                    let detail = format!("{}.new{}", set, render_params(&binding.params));
+                    items.insert("new".to_string(), (COMPLETION_METHOD, detail));
+                } else if binding.kind != Kind::Field
+                    && (binding.set.as_ref() == Some(&set) || binding.is_method())
+                {
+                    let kind = match binding.params {
+                        Some(..) => COMPLETION_METHOD,
+                        None => COMPLETION_PROPERTY,
+                    };
+                    items.insert(binding.name.clone(), (kind, binding.signature()));
+                }
+            }
+        } else {
+            for keyword in KEYWORDS {
+                items.insert(keyword.to_string(), (COMPLETION_KEYWORD, String::new()));
+            }
+            for set in self.builtins.keys() {
+                items.insert(set.clone(), (COMPLETION_MODULE, "Built-in".to_string()));
+            }
+            for binding in bindings.iter().filter(|binding| binding.set.is_none()) {
+                let kind = match binding.kind {
+                    Kind::Function => COMPLETION_FUNCTION,
+                    Kind::Type => COMPLETION_CLASS,
+                    _ => COMPLETION_VARIABLE,
+                };
+                items.insert(binding.name.clone(), (kind, binding.signature()));
+            }
+        }
+        items
+            .into_iter()
+            .map(
+                |(label, (kind, detail))| json!({ "label": label, "kind": kind, "detail": detail }),
+            )
+            .collect()
+    }
+}
+
+/// Tokenizes and parses a file, returning the first error with its position
+fn analyze(path: &Path, text: &str) -> Result<Statements, (FilePos, String)> {
+    let tokens = tokenize_str(text, FilePos::new(path, 1, 1)).map_err(|err| (err.pos, err.msg))?;
+    parse_file(tokens).map_err(|err| (err.pos, err.msg))
+}
+
+fn diagnostic(text: &str, pos: &FilePos, msg: &str) -> Json {
+    let len = match word_at(text, pos.row.saturating_sub(1), pos.col.saturating_sub(1)) {
+        Some((word, start, _)) if start + 1 == pos.col => word.chars().count(),
+        _ => 1,
+    };
+    json!({ "range": range(pos, len), "severity": 1, "source": "sand", "message": msg })
+}
+
+fn position(row: usize, col: usize) -> Json {
+    json!({ "line": row.saturating_sub(1), "character": col.saturating_sub(1) })
+}
+
+/// The range of `len` characters starting at `pos`
+fn range(pos: &FilePos, len: usize) -> Json {
+    json!({ "start": position(pos.row, pos.col), "end": position(pos.row, pos.col + len) })
+}
+
+fn markdown(code: &str, text: &str) -> String {
+    let mut value = format!("```sand\n{}\n```", code);
+    if !text.is_empty() {
+        value.push_str("\n\n");
+        value.push_str(text);
+    }
+    value
+}
+
+/// The comment lines right above `row`, like the signature comment `# name: (num){num}`
+fn doc_comment(text: &str, row: usize) -> String {
+    let lines: Vec<&str> = text.lines().take(row.saturating_sub(1)).collect();
+    let comment: Vec<&str> = lines
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim())
+        .collect();
+    comment.into_iter().rev().collect::<Vec<_>>().join("\n")
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn word_start(line: &[char], mut start: usize) -> usize {
+    while start > 0 && is_word(line[start - 1]) {
+        start -= 1;
+    }
+    start
+}
+
+/// The word at a position, with the column it starts at and what it is a member of, if it
+/// follows a `.`
+fn word_at(text: &str, line: usize, character: usize) -> Option<(String, usize, Option<String>)> {
+    let line: Vec<char> = text.lines().nth(line)?.chars().collect();
+    let character = character.min(line.len());
+    let start = word_start(&line, character);
+    let mut end = character;
+    while end < line.len() && is_word(line[end]) {
+        end += 1;
+    }
+    if start == end {
+        return None;
+    }
+    let word = line[start..end].iter().collect();
+    Some((word, start, receiver_before(&line, start)))
+}
+
+/// The receiver of a member starting at `start`, a word or `"` for string literals
+fn receiver_before(line: &[char], start: usize) -> Option<String> {
+    if start == 0 || line[start - 1] != '.' {
+        return None;
+    }
+    let end = start - 1;
+    if end > 0 && line[end - 1] == '"' {
+        return Some("\"".to_string());
+    }
+    let begin = word_start(line, end);
+    if begin == end {
+        return None;
+    }
+    Some(line[begin..end].iter().collect())
+}
+
+/// The file an `include` names, if the position is on its path
+fn include_at(doc: &Document, line: usize, character: usize) -> Option<PathBuf> {
+    let text = doc.text.lines().nth(line)?;
+    if !text.trim_start().starts_with("include") {
+        return None;
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let open = chars.iter().position(|c| *c == '"')?;
+    let close = open + 1 + chars[open + 1..].iter().position(|c| *c == '"')?;
+    if character < open || character > close {
+        return None;
+    }
+    let path: String = chars[open + 1..close].iter().collect();
+    doc.path.parent()?.join(path).canonicalize().ok()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path,
+        None => return PathBuf::from(uri),
+    };
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) if *byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(*byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn intrinsic_params(val: &Literal) -> Option<Vec<String>> {
+    match val {
+        Literal::Fun(Callable::Intr(intrinsic)) => {
+            Some(intrinsic.args.iter().map(|arg| arg.name.clone()).collect())
+        }
+        Literal::Fun(Callable::Fun(fun)) => Some(fun.args.iter().map(Param::to_string).collect()),
+        _ => None,
+    }
+}
+
+fn render_params(params: &Option<Vec<String>>) -> String {
+    match params {
+        Some(params) => format!("({})", params.join(", ")),
+        None => String::new(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Variable,
+    Function,
+    Type,
+    /// A member of a set, like `double` in `let Num.double = ...`
+    Member,
+    /// A field of the instances of a type
+    Field,
+    Param,
+}
+
+/// A name bound somewhere in a file
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    kind: Kind,
+    pos: FilePos,
+    /// The set the name is a member of
+    set: Option<String>,
+    /// The parameters of functions and the fields of types
+    params: Option<Vec<String>>,
+}
+
+impl Binding {
+    fn signature(&self) -> String {
+        let name = match &self.set {
+            Some(set) => format!("{}.{}", set, self.name),
+            None => self.name.clone(),
+        };
+        match self.kind {
+            Kind::Type => format!("type {} = {}", name, render_params(&self.params)),
+            Kind::Field | Kind::Param => name,
+            _ if self.params.is_some() => format!("let {} = {}", name, render_params(&self.params)),
+            _ => format!("let {}", name),
+        }
+    }
+
+    /// Functions whose first parameter is `self` are methods of every type
+    fn is_method(&self) -> bool {
+        self.kind == Kind::Function
+            && self.params.as_ref().and_then(|params| params.first()) == Some(&"self".to_string())
+    }
+}
+
+/// Collects every binding and include of a tree, in the order they appear in
+#[derive(Default)]
+struct Collector {
+    bindings: Vec<Binding>,
+    includes: Vec<PathBuf>,
+}
+
+impl Collector {
+    fn collect(tree: &Statements) -> Self {
+        let mut collector = Self::default();
+        collector.statements(tree);
+        collector
+    }
+
+    fn push(&mut self, var: &Var, kind: Kind, set: Option<String>, params: Option<Vec<String>>) {
+        self.bindings.push(Binding {
+            name: var.name.clone(),
+            kind,
+            pos: var.pos.clone(),
+            set,
+            params,
+        });
+    }
+
+    fn statements(&mut self, statements: &Statements) {
+        statements
+            .0
+            .iter()
+            .for_each(|statement| self.statement(statement));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assignment { var, val, .. } => {
+                match var {
+                    Reference::Var(var) => self.bind(var, None, val),
+                    Reference::Member { set, field, .. } => {
+                        self.value(set);
+                        self.bind(field, Some(set_name(set)), val);
+                    }
+                }
+                self.value(val);
+            }
+            Statement::Type {
+                name,
+                fields,
+                members,
+                ..
+            } => {
+                let params = fields.iter().map(Param::to_string).collect();
+                self.push(name, Kind::Type, None, Some(params));
+                for field in fields {
+                    for var in field.pattern.bindings() {
+                        self.push(var, Kind::Field, Some(name.name.clone()), None);
+                    }
+                    if let Some(default) = &field.default {
+                        self.value(default);
+                    }
+                }
+                for (member, val) in members {
+                    self.bind(member, Some(name.name.clone()), val);
+                    self.value(val);
+                }
+            }
+            Statement::Destructure { pattern, val, .. } => {
+                self.pattern(pattern, Kind::Variable);
+                self.value(val);
+            }
+            Statement::While { cond, body, .. } => {
+                self.value(cond);
+                self.statements(body);
+            }
+            Statement::For {
+                pattern,
+                iterable,
+                body,
+                ..
+            } => {
+                self.pattern(pattern, Kind::Variable);
+                self.value(iterable);
+                self.statements(body);
+            }
+            Statement::Return { val, .. } | Statement::Value(val) => self.value(val),
+            Statement::Include(path) => self.includes.push(path.clone()),
+            Statement::Break(..) | Statement::Continue(..) => {}
+        }
+    }
+
+    fn bind(&mut self, var: &Var, set: Option<String>, val: &Value) {
+        let params = match val {
+            Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+                Some(fun.args.iter().map(Param::to_string).collect())
+            }
+            _ => None,
+        };
+        let kind = match (&set, &params) {
+            (Some(..), _) => Kind::Member,
+            (None, Some(..)) => Kind::Function,
+            (None, None) => Kind::Variable,
+        };
+        self.push(var, kind, set, params);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, kind: Kind) {
+        for var in pattern.bindings() {
+            self.push(var, kind, None, None);
+        }
+    }
+
+    fn value(&mut self, val: &Value) {
+        match val {
+            Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+                for param in &fun.args {
+                    self.pattern(&param.pattern, Kind::Param);
+                    if let Some(default) = &param.default {
+                        self.value(default);
+                    }
+                }
+                self.statements(&fun.body);
+            }
+            Value::Lit(..) | Value::Ref(Reference::Var(..)) => {}
+            Value::Ref(Reference::Member { set, .. }) => self.value(set),
+            Value::FunCall { fun, params, .. } => {
+                self.value(fun);
+                params.iter().for_each(|param| self.value(param));
+            }
+            Value::Try { val, .. } | Value::Propagate { val, .. } => self.value(val),
+            Value::Format { parts, .. } => {
+                for part in parts {
+                    if let FormatPart::Value { val, .. } = part {
+                        self.value(val);
+                    }
+                }
+            }
+            Value::Match { val, arms, .. } => {
+                self.value(val);
+                for arm in arms {
+                    self.pattern(&arm.pattern, Kind::Variable);
+                    if let Some(guard) = &arm.guard {
+                        self.value(guard);
+                    }
+                    self.statements(&arm.body);
+                }
+            }
+        }
+    }
+}
+
+/// The name of the set in `let Set.member = ...`, if it is a variable
+fn set_name(set: &Value) -> String {
+    match set {
+        Value::Ref(Reference::Var(var)) => var.name.clone(),
+        _ => "_".to_string(),
+    }
+}
+
+/// The outline of a file: its bindings, with the members of types and the bindings in the
+/// bodies of functions nested in them
+fn symbols(statements: &Statements) -> Vec<Json> {
+    let mut symbols = Vec::new();
+    for statement in &statements.0 {
+        match statement {
+            Statement::Assignment { var, val, .. } => {
+                let (name, set) = match var {
+                    Reference::Var(var) => (var, None),
+                    Reference::Member { set, field, .. } => (field, Some(set_name(set))),
+                };
+                symbols.push(value_symbol(name, set, val));
+            }
+            Statement::Type {
+                name,
+                fields,
+                members,
+                ..
+            } => {
+                let mut children: Vec<Json> = fields
+                    .iter()
+                    .flat_map(|field| field.pattern.bindings())
+                    .map(|var| symbol(var, SYMBOL_FIELD, "", Vec::new()))
+                    .collect();
+                children.extend(
+                    members
+                        .iter()
+                        .map(|(member, val)| value_symbol(member, None, val)),
+                );
+                let params: Vec<String> = fields.iter().map(Param::to_string).collect();
+                let detail = format!("({})", params.join(", "));
+                symbols.push(symbol(name, SYMBOL_CLASS, &detail, children));
+            }
+            Statement::Destructure { pattern, .. } => {
+                for var in pattern.bindings() {
+                    symbols.push(symbol(var, SYMBOL_VARIABLE, "", Vec::new()));
+                }
+            }
+            // Loop bodies run in the scope around them
+            Statement::While { body, .. } => symbols.extend(self::symbols(body)),
+            Statement::For { pattern, body, .. } => {
+                for var in pattern.bindings() {
+                    symbols.push(symbol(var, SYMBOL_VARIABLE, "", Vec::new()));
+                }
+                symbols.extend(self::symbols(body));
+            }
+            _ => {}
+        }
+    }
+    symbols
+}
+
+fn value_symbol(var: &Var, set: Option<String>, val: &Value) -> Json {
+    let name = Var {
+        name: match set {
+            Some(set) => format!("{}.{}", set, var.name),
+            None => var.name.clone(),
+        },
+        pos: var.pos.clone(),
+    };
+    match val {
+        Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+            let params: Vec<String> = fun.args.iter().map(Param::to_string).collect();
+            let detail = format!("({})", params.join(", "));
+            let kind = if name.name.contains('.') {
+                SYMBOL_METHOD
+            } else {
+                SYMBOL_FUNCTION
+            };
+            symbol(&name, kind, &detail, symbols(&fun.body))
+        }
+        _ if name.name.contains('.') => symbol(&name, SYMBOL_PROPERTY, "", Vec::new()),
+        _ => symbol(&name, SYMBOL_VARIABLE, "", Vec::new()),
+    }
+}
+
+fn symbol(var: &Var, kind: u8, detail: &str, children: Vec<Json>) -> Json {
+    // Only the start of statements is known, so the symbol covers just its name
+    let len = var
+        .name
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count();
+    let range = range(&var.pos, len);
+    json!({
+        "name": var.name,
+        "kind": kind,
+        "detail": detail,
+        "range": range,
+        "selectionRange": range,
+        "children": children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames the messages of a session, as a client would send them
+    fn session(messages: &[Json]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for msg in messages {
+            write_message(&mut input, msg).unwrap();
+        }
+        input
+    }
+
+    /// Runs a session, returning the exit code and the messages the server sent
+    fn run(messages: &[Json]) -> (i32, Vec<Json>) {
+        let input = session(messages);
+        let mut output = Vec::new();
+        let code = serve(input.as_slice(), &mut output).unwrap();
+        let mut output = output.as_slice();
+        let mut sent = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            sent.push(serde_json::from_slice(&body).unwrap());
+        }
+        (code, sent)
+    }
+
+    fn request(id: u64, method: &str, params: Json) -> Json {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn open(uri: &str, text: &str) -> Json {
+        notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "sand", "version": 1, "text": text } }),
+        )
+    }
+
+    fn at(id: u64, method: &str, uri: &str, line: u64, character: u64) -> Json {
+        request(
+            id,
+            method,
+            json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    /// The result of the response to the request `id`
+    fn result(sent: &[Json], id: u64) -> &Json {
+        let response = sent.iter().find(|msg| msg["id"] == id).unwrap();
+        &response["result"]
+    }
+
+    const URI: &str = "file:///tmp/test.sand";
+
+    #[test]
+    fn lifecycle() {
+        let (code, sent) = run(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            request(2, "shutdown", Json::Null),
+            notification("exit", Json::Null),
+        ]);
+        assert_eq!(code, 0);
+        let capabilities = &result(&sent, 1)["capabilities"];
+        assert_eq!(capabilities["hoverProvider"], true);
+        assert_eq!(
+            capabilities["completionProvider"]["triggerCharacters"][0],
+            "."
+        );
+        assert_eq!(result(&sent, 2), &Json::Null);
+
+        let (code, sent) = run(&[request(1, "workspace/unknown", json!({}))]);
+        assert_eq!(code, 1);
+        assert_eq!(sent[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let (_, sent) = run(&[
+            open(URI, "let x = 1;\nlet = 2;\n"),
+            notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": URI, "version": 2 },
+                    "contentChanges": [{ "text": "let x = 1;\n" }],
+                }),
+            ),
+        ]);
+        let diagnostics = &sent[0]["params"]["diagnostics"];
+        assert_eq!(sent[0]["params"]["uri"], URI);
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+        assert_eq!(diagnostics[0]["range"]["end"]["character"], 3);
+        assert_eq!(sent[1]["params"]["diagnostics"], json!([]));
+
+        let (_, sent) = run(&[open(URI, "let s = \"unterminated")]);
+        let message = sent[0]["params"]["diagnostics"][0]["message"].as_str();
+        assert!(!message.unwrap().is_empty());
+
+        // Half-written code is reported where it stops making sense
+        for (text, line, character) in [("let x =", 0, 6), (";;", 0, 0), ("let x = 1;;", 0, 10)] {
+            let (_, sent) = run(&[open(URI, text)]);
+            let start = &sent[0]["params"]["diagnostics"][0]["range"]["start"];
+            assert_eq!(
+                (start["line"].clone(), start["character"].clone()),
+                (json!(line), json!(character))
+            );
+        }
+    }
+
+    #[test]
+    fn diagnostics_keep_serving() {
+        // Values the parser can't make sense of are diagnostics, and the server goes on
+        let (code, sent) = run(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            open(URI, "let x = == 1;\nlet y = a && || b;\n"),
+            at(2, "textDocument/hover", URI, 0, 4),
+            request(3, "shutdown", Json::Null),
+            notification("exit", Json::Null),
+        ]);
+        assert_eq!(code, 0);
+        let published = sent
+            .iter()
+            .find(|msg| msg["method"] == "textDocument/publishDiagnostics")
+            .unwrap();
+        let diagnostic = &published["params"]["diagnostics"][0];
+        assert!(diagnostic["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("`==` cannot start a value"));
+        assert_eq!(diagnostic["range"]["start"]["character"], 8);
+        assert_eq!(result(&sent, 3), &Json::Null);
+    }
+
+    #[test]
+    fn definition() {
+        let text = "let double = (n) { n * 2 };\nlet x = double(4);\n";
+        let (_, sent) = run(&[
+            open(URI, text),
+            at(1, "textDocument/definition", URI, 1, 10),
+        ]);
+        let location = result(&sent, 1);
+        assert_eq!(location["uri"], URI);
+        assert_eq!(
+            location["range"]["start"],
+            json!({ "line": 0, "character": 4 })
+        );
+        assert_eq!(
+            location["range"]["end"],
+            json!({ "line": 0, "character": 10 })
+        );
+    }
+
+    #[test]
+    fn definition_in_included_file() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let file = root.join("examples").join("lsp.sand");
+        let uri = path_to_uri(&file);
+        let text = "include \"../std.sand\";\nprint(\"hi\");\n";
+        let (_, sent) = run(&[
+            open(&uri, text),
+            at(1, "textDocument/definition", &uri, 1, 2),
+            at(2, "textDocument/definition", &uri, 0, 12),
+        ]);
+        let std = path_to_uri(&root.join("std.sand").canonicalize().unwrap());
+        assert_eq!(result(&sent, 1)["uri"], std);
+        assert_eq!(result(&sent, 1)["range"]["start"]["line"], 4);
+        assert_eq!(result(&sent, 2)["uri"], std);
+        assert_eq!(result(&sent, 2)["range"]["start"]["line"], 0);
+    }
+
+    #[test]
+    fn hover() {
+        let text = "# twice: (num){num}\nlet twice = (n) { n * 2 };\ntwice(1).add(2);\n";
+        let (_, sent) = run(&[
+            open(URI, text),
+            at(1, "textDocument/hover", URI, 2, 1),
+            at(2, "textDocument/hover", URI, 2, 10),
+            at(3, "textDocument/hover", URI, 2, 8),
+        ]);
+        assert_eq!(
+            result(&sent, 1)["contents"]["value"],
+            "```sand\nlet twice = (n)\n```\n\ntwice: (num){num}"
+        );
+        assert_eq!(result(&sent, 2), &Json::Null);
+
+        let (_, sent) = run(&[
+            open(URI, "Num.add;\n5.times;\n"),
+            at(1, "textDocument/hover", URI, 0, 5),
+            at(2, "textDocument/hover", URI, 1, 3),
+            at(3, "textDocument/hover", URI, 0, 1),
+        ]);
+        let value = |id| result(&sent, id)["contents"]["value"].as_str().unwrap();
+        assert_eq!(value(1), "```sand\nNum.add(n)\n```\n\nBuilt-in");
+        assert_eq!(value(2), "```sand\nNum.times(f)\n```\n\nBuilt-in");
+        assert!(value(3).contains("`add`"));
+    }
+
+    #[test]
+    fn completion() {
+        let text = "type Point = (x, y) { let len = () { 0 }; };\nNum.\nPoint.\nle";
+        let (_, sent) = run(&[
+            open(URI, text),
+            at(1, "textDocument/completion", URI, 1, 4),
+            at(2, "textDocument/completion", URI, 2, 6),
+            at(3, "textDocument/completion", URI, 3, 2),
+        ]);
+        let labels = |id| -> Vec<String> {
+            let items = result(&sent, id).as_array().unwrap();
+            items
+                .iter()
+                .map(|item| item["label"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert!(labels(1).contains(&"add".to_string()));
+        assert!(labels(1).contains(&"times".to_string()));
+        assert_eq!(labels(2), vec!["len", "new"]);
+        let globals = labels(3);
+        for label in &["let", "Main", "Bool", "List", "Point"] {
+            assert!(globals.contains(&label.to_string()), "{}", label);
+        }
+    }
+
+    #[test]
+    fn document_symbols() {
+        let text =
+            "let f = (a, b) { let c = a; c };\ntype P = (x) { let g = () { 1 }; };\nlet P.h = 2;\n";
+        let (_, sent) = run(&[
+            open(URI, text),
+            request(
+                1,
+                "textDocument/documentSymbol",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+        let symbols = result(&sent, 1);
+        assert_eq!(symbols[0]["name"], "f");
+        assert_eq!(symbols[0]["kind"], SYMBOL_FUNCTION);
+        assert_eq!(symbols[0]["detail"], "(a, b)");
+        assert_eq!(symbols[0]["children"][0]["name"], "c");
+        assert_eq!(symbols[1]["name"], "P");
+        assert_eq!(symbols[1]["kind"], SYMBOL_CLASS);
+        assert_eq!(symbols[1]["children"][0]["name"], "x");
+        assert_eq!(symbols[1]["children"][1]["name"], "g");
+        assert_eq!(symbols[2]["name"], "P.h");
+        assert_eq!(symbols[2]["kind"], SYMBOL_PROPERTY);
+    }
+
+    #[test]
+    fn uris() {
+        let path = Path::new("/tmp/a b/c.sand");
+        assert_eq!(path_to_uri(path), "file:///tmp/a%20b/c.sand");
+        assert_eq!(uri_to_path(&path_to_uri(path)), path);
+    }
+}
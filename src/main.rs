@@ -1,165 +1,264 @@
-use std::fmt;
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
-// mod compiler;
-mod interpreter;
-mod intrinsics;
-mod parser;
-mod tokenizer;
-mod types;
-
-use interpreter::interpret_file;
-use interpreter::InterpretingError;
-use parser::parse_file;
-use parser::ParseError;
-use tokenizer::tokenize_str;
-use tokenizer::Token;
-use tokenizer::TokenError;
-use types::TypeError;
-
-// TODO: Implement the compiler (llvm?)
-// TODO: Implement typechecking
-// TODO: Implement a language server
-
-#[derive(Clone, PartialEq)]
-pub struct FilePos {
-    file: PathBuf,
-    row: usize,
-    col: usize,
-}
+use sand::coverage::{self, Coverage};
+use sand::debugger::{Breakpoint, Repl};
+use sand::doc::{self, Format};
+use sand::formatter::format_str;
+use sand::interpreter::interpret_file;
+use sand::interpreter::interpret_main;
+use sand::interpreter::Limits;
+use sand::intrinsics::init_scope_with;
+use sand::intrinsics::Capabilities;
+use sand::lsp;
+use sand::parser::parse_file;
+use sand::profiler::Profiler;
+use sand::testing;
+use sand::tokenizer::{tokenize_lossless, tokenize_str};
+use sand::FilePos;
+use sand::SandError;
 
-impl fmt::Display for FilePos {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}:{}", self.file.display(), self.row, self.col)
-    }
+#[derive(StructOpt)]
+enum Cmd {
+    Tokenize(TokenizeOpt),
+    Parse,
+    Run(RunOpt),
+    /// Runs the `test_*` functions of the file
+    ///
+    /// Every top-level function of the file named `test_*` is called in a fresh scope the file
+    /// was interpreted in, and the tests fail if any of them fails. `Main.assert` and
+    /// `Main.assert_eq` fail tests with how the values differ. The tests of std.sand are in
+    /// std_test.sand.
+    Test(TestOpt),
+    /// Runs the file, pausing at breakpoints to inspect it
+    ///
+    /// The program pauses before its first statement, or at the breakpoints given with
+    /// `--break`. While paused, it can step a line at a time into, over or out of calls, set
+    /// breakpoints, show the variables in scope and the call stack, and evaluate values in the
+    /// paused scope. `help` lists its commands.
+    Debug(DebugOpt),
+    /// Serves the Language Server Protocol over stdio, for editors
+    ///
+    /// It reports token and parse errors, and offers go-to-definition for `let` bindings and
+    /// included files, hover, completion of members and globals, and an outline of the bindings
+    /// of a file.
+    Lsp,
+    /// Rewrites the file in the canonical style
+    ///
+    /// The canonical style has four spaces of indentation, a line per statement or match arm of
+    /// blocks spanning several lines, and single spaces around binary operators. Comments and
+    /// single empty lines are kept.
+    Fmt(FmtOpt),
+    /// Documents the file, the files it includes and the built-in sets
+    ///
+    /// A comment right above a binding documents it, and its first line is its signature by
+    /// convention, like `# max: (num, num){num}`. The documentation has these comments and the
+    /// signatures of the top-level bindings, set members and types of the files.
+    Doc(DocOpt),
+    #[structopt(external_subcommand)]
+    Unknown(Vec<String>),
 }
 
-impl fmt::Debug for FilePos {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}:{}:{}",
-            self.file.file_name().unwrap().to_str().unwrap(),
-            self.row,
-            self.col
-        )
+impl Cmd {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tokenize(..) => "tokenize",
+            Self::Parse => "parse",
+            Self::Run(..) => "run",
+            Self::Test(..) => "test",
+            Self::Debug(..) => "debug",
+            Self::Lsp => "lsp",
+            Self::Fmt(..) => "fmt",
+            Self::Doc(..) => "doc",
+            Self::Unknown(..) => "unknown",
+        }
     }
 }
 
-impl FilePos {
-    pub fn new(file: &PathBuf, row: usize, col: usize) -> Self {
-        Self {
-            file: file.clone(),
-            row,
-            col,
-        }
-    }
+#[derive(StructOpt)]
+struct RunOpt {
+    /// Prints how many times every function was called and how long the calls took to stderr,
+    /// and writes the call stacks for flamegraph tools to `--folded`. Functions are told apart
+    /// by where they are defined, and intrinsics by their set, like `Num.add`
+    #[structopt(long)]
+    profile: bool,
 
-    pub fn internal() -> Self {
-        Self {
-            file: PathBuf::from("internal"),
-            row: 0,
-            col: 0,
-        }
-    }
+    /// Where `--profile` writes the call stacks, in the folded format
+    #[structopt(long, parse(from_os_str), default_value = "profile.folded")]
+    folded: PathBuf,
 
-    pub fn temp() -> Self {
-        Self {
-            file: PathBuf::from("undefined"),
-            row: 0,
-            col: 0,
-        }
-    }
+    #[structopt(flatten)]
+    limits: LimitsOpt,
 }
 
-impl From<&Vec<Token>> for FilePos {
-    fn from(tokens: &Vec<Token>) -> Self {
-        tokens.get(0).unwrap().pos.clone()
-    }
-}
+#[derive(StructOpt)]
+struct TestOpt {
+    /// Records which lines and functions of the file and the files it includes the tests ran,
+    /// printing a summary and writing it to `--lcov`
+    #[structopt(long)]
+    coverage: bool,
 
-pub struct SandError {
-    pos: FilePos,
-    msg: String,
-}
+    /// Where `--coverage` writes what ran, in the lcov format
+    #[structopt(long, parse(from_os_str), default_value = "lcov.info")]
+    lcov: PathBuf,
 
-impl fmt::Display for SandError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.pos, self.msg)
-    }
+    #[structopt(flatten)]
+    limits: LimitsOpt,
 }
 
-impl fmt::Debug for SandError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.pos, self.msg)
-    }
+#[derive(StructOpt)]
+struct LimitsOpt {
+    /// How many calls can be nested before failing [default: 1000]. Calls in tail position
+    /// don't count, and nested calls fail anyway once they take up 1 GiB of stack
+    #[structopt(long)]
+    recursion_limit: Option<usize>,
+
+    /// How many values and statements can be evaluated before failing
+    #[structopt(long)]
+    max_steps: Option<u64>,
+
+    /// How many items a list, or bytes a string, can have
+    #[structopt(long)]
+    max_size: Option<usize>,
+
+    /// How many seconds the program can run for before failing
+    #[structopt(long)]
+    timeout: Option<f64>,
+
+    /// Disables reading files, `include` and the environment. The program can still write to
+    /// stdout and stderr
+    #[structopt(long)]
+    sandbox: bool,
 }
 
-impl From<TokenError> for SandError {
-    fn from(token_error: TokenError) -> Self {
-        Self {
-            pos: token_error.pos,
-            msg: format!("TOKEN_ERROR: {}", token_error.msg),
+impl LimitsOpt {
+    fn limits(&self) -> Limits {
+        let default = Limits::default();
+        Limits {
+            call_depth: self.recursion_limit.unwrap_or(default.call_depth),
+            stack_size: default.stack_size,
+            steps: self.max_steps,
+            size: self.max_size,
+            timeout: self.timeout.map(Duration::from_secs_f64),
         }
     }
-}
 
-impl From<ParseError> for SandError {
-    fn from(parse_error: ParseError) -> Self {
-        Self {
-            pos: parse_error.pos,
-            msg: format!("PARSE_ERROR: {}", parse_error.msg),
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            fs: !self.sandbox,
+            process: !self.sandbox,
+            ..Capabilities::default()
         }
     }
 }
 
-impl From<InterpretingError> for SandError {
-    fn from(parse_error: InterpretingError) -> Self {
-        Self {
-            pos: parse_error.pos,
-            msg: format!("INTERPRETING_ERROR: {}", parse_error.msg),
-        }
-    }
+#[derive(StructOpt)]
+struct DebugOpt {
+    /// Where to pause, like `main.sand:12`. Without breakpoints, the program pauses before its
+    /// first statement
+    #[structopt(long = "break", short)]
+    breakpoints: Vec<Breakpoint>,
+
+    #[structopt(flatten)]
+    limits: LimitsOpt,
 }
 
-impl From<TypeError> for SandError {
-    fn from(parse_error: TypeError) -> Self {
-        Self {
-            pos: parse_error.pos,
-            msg: format!("TYPE_ERROR: {}", parse_error.msg),
-        }
-    }
+#[derive(StructOpt)]
+struct TokenizeOpt {
+    /// Also prints the whitespace and comments, with the byte offsets of everything
+    #[structopt(long)]
+    lossless: bool,
 }
 
 #[derive(StructOpt)]
-enum Cmd {
-    Tokenize,
-    Parse,
-    Run,
+struct FmtOpt {
+    /// Fails if the file isn't formatted instead of rewriting it
+    #[structopt(long)]
+    check: bool,
+}
+
+#[derive(StructOpt)]
+struct DocOpt {
+    /// `md` or `html`
+    #[structopt(long, default_value = "md")]
+    format: Format,
+
+    /// Where to write the documentation instead of stdout
+    #[structopt(long, short, parse(from_os_str))]
+    output: Option<PathBuf>,
 }
 
 #[derive(StructOpt)]
+// clap rejects a first argument named like a subcommand, like `tests/a.sand` for `test`, as a
+// misspelled subcommand unless it allows unknown subcommands. It is the file, and unknown
+// subcommands are reported by `run` instead.
+#[structopt(setting = AppSettings::AllowExternalSubcommands)]
 struct Opt {
     #[structopt(subcommand)]
     subcommand: Cmd,
 
+    /// The file to run the subcommand on, which all but `lsp` need
     #[structopt(parse(from_os_str))]
-    file: PathBuf,
+    file: Option<PathBuf>,
 }
 
 fn main() {
     let opt = Opt::from_args();
+    if let Cmd::Lsp = opt.subcommand {
+        let stdin = io::stdin();
+        match lsp::serve(stdin.lock(), io::stdout()) {
+            Ok(exit_code) => exit(exit_code),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1)
+            }
+        }
+    }
+
+    if let Cmd::Unknown(args) = &opt.subcommand {
+        eprintln!("Unknown subcommand `{}`, see `sand --help`", args[0]);
+        exit(2)
+    }
 
-    let file_contents = fs::read_to_string(&opt.file).unwrap();
-    let pos = FilePos::new(&opt.file, 1, 1);
+    let file = match opt.file {
+        Some(file) => file,
+        None => {
+            eprintln!("The `{}` subcommand needs a file", opt.subcommand.name());
+            exit(2)
+        }
+    };
+    let file_contents = fs::read_to_string(&file).unwrap();
+    let pos = FilePos::new(&file, 1, 1);
 
     let args = Vec::new(); // TODO
 
     match opt.subcommand {
-        Cmd::Tokenize => {
+        Cmd::Tokenize(opt) if opt.lossless => {
+            println!("==== File:\n{}", file_contents);
+            match tokenize_lossless(&file_contents, pos) {
+                Ok(tokens) => {
+                    println!("==== Tokens:");
+                    for token in &tokens.tokens {
+                        for trivia in &token.leading {
+                            println!("{:?} {:?}", trivia.span, trivia.text);
+                        }
+                        println!("{:?} {:?} {:?}", token.span, token.kind, token.text);
+                    }
+                    for trivia in &tokens.trailing {
+                        println!("{:?} {:?}", trivia.span, trivia.text);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{}", SandError::from(err))
+                }
+            }
+        }
+        Cmd::Tokenize(..) => {
             println!("==== File:\n{}", file_contents);
             match tokenize_str(&file_contents, pos) {
                 Ok(tokens) => {
@@ -178,7 +277,7 @@ fn main() {
                         println!("==== Tree:\n{:#?}", tree);
                     }
                     Err(err) => {
-                        eprintln!("{}", SandError::from(err))
+                        eprintln!("{}", err)
                     }
                 },
                 Err(err) => {
@@ -186,19 +285,180 @@ fn main() {
                 }
             }
         }
-        Cmd::Run => match tokenize_str(&file_contents, pos) {
+        Cmd::Run(opt) => match tokenize_str(&file_contents, pos) {
             Ok(tokens) => match parse_file(tokens) {
-                Ok(tree) => match interpret_file(tree, args) {
-                    Ok(exit_code) => exit(exit_code),
-                    Err(err) => eprintln!("{}", SandError::from(err)),
-                },
+                Ok(tree) if opt.profile => {
+                    let mut scope = init_scope_with(opt.limits.capabilities());
+                    scope.state.borrow_mut().limits = opt.limits.limits();
+                    scope.state.borrow_mut().profiler = Some(Profiler::new());
+                    let res = interpret_main(tree, &mut scope);
+                    let profiler = scope.state.borrow_mut().profiler.take().unwrap();
+                    eprint!("{}", profiler);
+                    if let Err(err) = fs::write(&opt.folded, profiler.folded()) {
+                        eprintln!("Cannot write `{}` because: {}", opt.folded.display(), err);
+                    }
+                    match res {
+                        Ok(exit_code) => exit(exit_code),
+                        Err(err) => {
+                            eprintln!("{}", SandError::from(err));
+                            exit(1)
+                        }
+                    }
+                }
+                Ok(tree) => {
+                    match interpret_file(tree, args, opt.limits.limits(), opt.limits.capabilities())
+                    {
+                        Ok(exit_code) => exit(exit_code),
+                        Err(err) => {
+                            eprintln!("{}", SandError::from(err));
+                            exit(1)
+                        }
+                    }
+                }
                 Err(err) => {
-                    eprintln!("{}", SandError::from(err))
+                    eprintln!("{}", err);
+                    exit(1)
                 }
             },
             Err(err) => {
-                eprintln!("{}", SandError::from(err))
+                eprintln!("{}", SandError::from(err));
+                exit(1)
+            }
+        },
+        Cmd::Debug(opt) => match tokenize_str(&file_contents, pos) {
+            Ok(tokens) => match parse_file(tokens) {
+                Ok(tree) => {
+                    let mut scope = init_scope_with(opt.limits.capabilities());
+                    let stdin = io::stdin();
+                    let repl = Repl::new(stdin.lock(), io::stdout(), opt.breakpoints);
+                    scope.state.borrow_mut().limits = opt.limits.limits();
+                    scope.state.borrow_mut().debugger = Some(Box::new(repl));
+                    match interpret_main(tree, &mut scope) {
+                        Ok(exit_code) => {
+                            println!("The program exited with {}", exit_code);
+                            exit(exit_code)
+                        }
+                        Err(err) => {
+                            eprintln!("{}", SandError::from(err));
+                            exit(1)
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    exit(1)
+                }
+            },
+            Err(err) => {
+                eprintln!("{}", SandError::from(err));
+                exit(1)
+            }
+        },
+        Cmd::Test(opt) => {
+            let tree = match tokenize_str(&file_contents, pos) {
+                Ok(tokens) => match parse_file(tokens) {
+                    Ok(tree) => tree,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        exit(1)
+                    }
+                },
+                Err(err) => {
+                    eprintln!("{}", SandError::from(err));
+                    exit(1)
+                }
+            };
+            let tests = testing::discover(&tree);
+            println!("running {} tests", tests.len());
+            let limits = opt.limits.limits();
+            let mut coverage = if opt.coverage {
+                Some(Coverage::new())
+            } else {
+                None
+            };
+            let mut failures = Vec::new();
+            for test in &tests {
+                match testing::run_test(
+                    &tree,
+                    test,
+                    &limits,
+                    opt.limits.capabilities(),
+                    &mut coverage,
+                ) {
+                    Ok(()) => println!("test {} ... ok", test.name),
+                    Err(err) => {
+                        println!("test {} ... FAILED", test.name);
+                        failures.push((test, SandError::from(err)));
+                    }
+                }
+            }
+            for (test, err) in &failures {
+                println!("\n---- {} ({}) ----\n{}", test.name, test.pos, err);
+            }
+            if let Some(coverage) = coverage {
+                let files = coverage.report(&tree);
+                println!("\ncoverage:");
+                for file in &files {
+                    println!(
+                        "{}: {}/{} lines, {}/{} functions",
+                        file.file.display(),
+                        file.lines_hit(),
+                        file.lines.len(),
+                        file.functions_hit(),
+                        file.functions.len()
+                    );
+                }
+                if let Err(err) = fs::write(&opt.lcov, coverage::lcov(&files)) {
+                    eprintln!("Cannot write `{}` because: {}", opt.lcov.display(), err);
+                }
+            }
+            let passed = tests.len() - failures.len();
+            match failures.len() {
+                0 => println!("\ntest result: ok. {} passed; 0 failed", passed),
+                failed => {
+                    println!(
+                        "\ntest result: FAILED. {} passed; {} failed",
+                        passed, failed
+                    );
+                    exit(1)
+                }
+            }
+        }
+        Cmd::Fmt(opt) => match format_str(&file_contents, &file) {
+            Ok(formatted) if formatted == file_contents => {}
+            Ok(..) if opt.check => {
+                eprintln!("{} is not formatted", file.display());
+                exit(1)
+            }
+            Ok(formatted) => {
+                if let Err(err) = fs::write(&file, formatted) {
+                    eprintln!("Cannot write `{}` because: {}", file.display(), err);
+                    exit(1)
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", SandError::from(err));
+                exit(1)
+            }
+        },
+        Cmd::Doc(opt) => match doc::document(&file) {
+            Ok(docs) => {
+                let rendered = docs.render(opt.format);
+                match opt.output {
+                    Some(output) => {
+                        if let Err(err) = fs::write(&output, rendered) {
+                            eprintln!("Cannot write `{}` because: {}", output.display(), err);
+                            exit(1)
+                        }
+                    }
+                    None => print!("{}", rendered),
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1)
             }
         },
+        Cmd::Lsp | Cmd::Unknown(..) => unreachable!(),
     }
 }
@@ -13,13 +13,13 @@ impl ParseError {
     fn new(msg: &str, tokens: Vec<Token>) -> Self {
         Self {
             msg: msg.to_string(),
-            pos: tokens.get(0).unwrap().pos.clone(),
+            pos: tokens.first().unwrap().pos.clone(),
         }
     }
 }
 
 trait Parse {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>>
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>>
     where
         Self: Sized;
 }
@@ -27,36 +27,77 @@ trait Parse {
 // TODO: Remove all instances of `?.ok()?`
 
 impl Parse for Function {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
         if tokens.len() != 2 {
             return None;
         }
         if let TokenType::Group {
             r#type: GroupType::Paren,
             tokens: args_tokens,
-        } = &tokens.get(0).unwrap().r#type
+            ..
+        } = &tokens.first().unwrap().r#type
         {
-            let mut args_tokens = args_tokens.iter();
-            let mut args = Vec::new();
-            while let Some(token) = args_tokens.next() {
-                if let Some(token2) = args_tokens.next() {
-                    if let TokenType::Char(',') = token2.r#type {
-                        args.push(Var::parse(&vec![token.clone()])?.ok()?);
-                    } else {
-                        return None; // TODO: This should be an error
+            let mut args: Vec<Param> = Vec::new();
+            if !args_tokens.is_empty() {
+                for arg_tokens in
+                    args_tokens.split(|token| matches!(token.r#type, TokenType::Char(',')))
+                {
+                    if arg_tokens.is_empty() {
+                        return Some(Err(ParseError::new(
+                            "Expected a parameter",
+                            tokens.to_vec(),
+                        )));
                     }
-                } else {
-                    args.push(Var::parse(&vec![token.clone()])?.ok()?);
+                    let arg = match Param::parse(arg_tokens) {
+                        Some(Ok(arg)) => arg,
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => {
+                            return Some(Err(ParseError::new(
+                                "Cannot parse into a parameter",
+                                arg_tokens.to_vec(),
+                            )))
+                        }
+                    };
+                    if let Some(last) = args.last() {
+                        let msg = if last.rest {
+                            Some("No parameters can follow a rest parameter")
+                        } else if last.default.is_some() && arg.default.is_none() && !arg.rest {
+                            Some("Parameters without a default value cannot follow parameters with one")
+                        } else if args.iter().any(|other| {
+                            other.pattern.bindings().iter().any(|other| {
+                                arg.pattern
+                                    .bindings()
+                                    .iter()
+                                    .any(|var| var.name == other.name)
+                            })
+                        }) {
+                            Some("Duplicate parameter name")
+                        } else {
+                            None
+                        };
+                        if let Some(msg) = msg {
+                            return Some(Err(ParseError::new(msg, arg_tokens.to_vec())));
+                        }
+                    }
+                    args.push(arg);
                 }
             }
 
             if let TokenType::Group {
                 r#type: GroupType::Curly,
                 tokens: body_tokens,
+                ..
             } = &tokens.get(1).unwrap().r#type
             {
-                let body = Statements::parse(&body_tokens)?.ok()?;
+                let body = if body_tokens.is_empty() {
+                    Statements(vec![Statement::Value(Value::Lit(Literal::Nope))])
+                } else {
+                    match Statements::parse(body_tokens)? {
+                        Ok(body) => body,
+                        Err(err) => return Some(Err(err)),
+                    }
+                };
                 Some(Ok(Self {
                     args,
                     body,
@@ -71,21 +112,72 @@ impl Parse for Function {
     }
 }
 
+impl Parse for Param {
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
+        if tokens.len() > 3
+            && tokens[..3]
+                .iter()
+                .all(|token| matches!(token.r#type, TokenType::Char('.')))
+        {
+            return Some(parse_pattern(&tokens[3..]).map(|pattern| Self {
+                pattern,
+                default: None,
+                rest: true,
+            }));
+        }
+        if tokens.len() > 1 {
+            if let TokenType::Char('=') = tokens[1].r#type {
+                let pattern = match parse_pattern(&tokens[..1]) {
+                    Ok(pattern) => pattern,
+                    Err(err) => return Some(Err(err)),
+                };
+                if tokens.len() == 2 {
+                    return Some(Err(ParseError::new(
+                        "Expected a default value",
+                        tokens.to_vec(),
+                    )));
+                }
+                let default = match Value::parse(&tokens[2..]) {
+                    Some(Ok(val)) => val,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {
+                        return Some(Err(ParseError::new(
+                            "Cannot parse into a default value",
+                            tokens[2..].to_vec(),
+                        )))
+                    }
+                };
+                return Some(Ok(Self {
+                    pattern,
+                    default: Some(default),
+                    rest: false,
+                }));
+            }
+        }
+        Some(parse_pattern(tokens).map(|pattern| Self {
+            pattern,
+            default: None,
+            rest: false,
+        }))
+    }
+}
+
 impl Parse for Callable {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        Function::parse(tokens).map(|res| res.map(|fun| Callable::Fun(fun)))
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        Function::parse(tokens).map(|res| res.map(Callable::Fun))
     }
 }
 
 impl Parse for Literal {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
         {
             if tokens.len() == 1 {
-                match &tokens.get(0).unwrap().r#type {
+                match &tokens.first().unwrap().r#type {
                     TokenType::StringLit(s) => Some(Ok(Self::Str(s.clone()))),
-                    TokenType::CharLit(c) => Some(Ok(Self::Char(c.clone()))),
-                    TokenType::Number(n) => Some(Ok(Self::Num(n.clone()))),
+                    TokenType::CharLit(c) => Some(Ok(Self::Char(*c))),
+                    TokenType::Number(n) => Some(Ok(Self::Num(*n))),
                     TokenType::String(s) => match s.as_str() {
                         "Nope" => Some(Ok(Self::Nope)),
                         "True" => Some(Ok(Self::Bool(true))),
@@ -95,18 +187,20 @@ impl Parse for Literal {
                     TokenType::Group {
                         r#type: GroupType::Brack,
                         tokens: l,
+                        ..
                     } => {
                         let mut lit_tokens = l.iter();
                         let mut literals = Vec::new();
                         while let Some(token) = lit_tokens.next() {
                             if let Some(token2) = lit_tokens.next() {
                                 if let TokenType::Char(',') = token2.r#type {
-                                    literals.push(Literal::parse(&vec![token.clone()])?.ok()?);
+                                    literals
+                                        .push(Literal::parse(std::slice::from_ref(token))?.ok()?);
                                 } else {
                                     return None; // TODO: This should be an error
                                 }
                             } else {
-                                literals.push(Literal::parse(&vec![token.clone()])?.ok()?);
+                                literals.push(Literal::parse(std::slice::from_ref(token))?.ok()?);
                             }
                         }
                         Some(Ok(Self::List(literals)))
@@ -117,79 +211,754 @@ impl Parse for Literal {
                 None
             }
         }
-        .or_else(|| Callable::parse(tokens).map(|res| res.map(|fun| Literal::Fun(fun))))
+        .or_else(|| Callable::parse(tokens).map(|res| res.map(Literal::Fun)))
     }
 }
 
 impl Parse for Value {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
-        Literal::parse(tokens)
-            .map(|res| res.map(|lit| Value::Lit(lit)))
-            .or_else(|| Reference::parse(tokens).map(|res| res.map(|r#ref| Value::Ref(r#ref))))
-            .or_else(|| {
-                let fun_tokens = &tokens.get(0..tokens.len() - 1).unwrap().to_vec();
-                if fun_tokens.len() == 0 {
-                    return None;
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
+        if let TokenType::String(s) = &tokens[0].r#type {
+            if s == "try" {
+                if tokens.len() == 1 {
+                    return Some(Err(ParseError::new(
+                        "Expected a value after `try`",
+                        tokens.to_vec(),
+                    )));
+                }
+                return Some(Value::parse(&tokens[1..])?.map(|val| Self::Try {
+                    val: Box::new(val),
+                    pos: tokens.into(),
+                }));
+            }
+        }
+        if let TokenType::String(s) = &tokens[0].r#type {
+            if s == "match" {
+                return Some(parse_match(tokens));
+            }
+        }
+        parse_operators(tokens, 0)
+    }
+}
+
+/// `'match' Value '{' MatchArms '}'`
+fn parse_match(tokens: &[Token]) -> Result<Value, ParseError> {
+    let arms_tokens = match tokens.last().map(|token| &token.r#type) {
+        Some(TokenType::Group {
+            r#type: GroupType::Curly,
+            tokens,
+            ..
+        }) if tokens.len() > 1 => tokens,
+        _ => {
+            return Err(ParseError::new(
+                "Expected a value and a block of arms after `match`",
+                tokens.to_vec(),
+            ))
+        }
+    };
+    let val_tokens = &tokens[1..tokens.len() - 1];
+    let val = match Value::parse(val_tokens) {
+        Some(res) => res?,
+        None => {
+            return Err(ParseError::new(
+                "Cannot parse into a value",
+                val_tokens.to_vec(),
+            ))
+        }
+    };
+
+    let mut arm_tokens: Vec<&[Token]> = arms_tokens
+        .split(|token| matches!(token.r#type, TokenType::Char(',')))
+        .collect();
+    // Allow a trailing comma
+    if arm_tokens.len() > 1 && arm_tokens.last().unwrap().is_empty() {
+        arm_tokens.pop();
+    }
+    let mut arms = Vec::new();
+    for arm_tokens in arm_tokens {
+        if arm_tokens.is_empty() {
+            return Err(ParseError::new("Expected a match arm", tokens.to_vec()));
+        }
+        arms.push(parse_match_arm(arm_tokens)?);
+    }
+    check_exhaustive(&arms, &tokens[0])?;
+
+    Ok(Value::Match {
+        val: Box::new(val),
+        arms,
+        pos: tokens.into(),
+    })
+}
+
+/// `Pattern ['if' Value] '=>' (Value | Block)`
+fn parse_match_arm(tokens: &[Token]) -> Result<MatchArm, ParseError> {
+    let arrow = (0..tokens.len().saturating_sub(1)).find(|&i| {
+        matches!(tokens[i].r#type, TokenType::Char('='))
+            && matches!(tokens[i + 1].r#type, TokenType::Char('>'))
+            && tokens[i + 1].pos.row == tokens[i].pos.row
+            && tokens[i + 1].pos.col == tokens[i].pos.col + 1
+    });
+    let arrow = match arrow {
+        Some(arrow) => arrow,
+        None => {
+            return Err(ParseError::new(
+                "Expected `=>` in match arm",
+                tokens.to_vec(),
+            ))
+        }
+    };
+    let (pattern_tokens, body_tokens) = (&tokens[..arrow], &tokens[arrow + 2..]);
+    if pattern_tokens.is_empty() {
+        return Err(ParseError::new(
+            "Expected a pattern before `=>`",
+            tokens.to_vec(),
+        ));
+    }
+    if body_tokens.is_empty() {
+        return Err(ParseError::new(
+            "Expected a value after `=>`",
+            tokens[arrow..].to_vec(),
+        ));
+    }
+
+    let guard_start = pattern_tokens
+        .iter()
+        .position(|token| matches!(&token.r#type, TokenType::String(s) if s == "if"));
+    let (pattern_tokens, guard) = match guard_start {
+        Some(start) => {
+            let guard_tokens = &pattern_tokens[start + 1..];
+            if guard_tokens.is_empty() {
+                return Err(ParseError::new(
+                    "Expected a guard after `if`",
+                    pattern_tokens[start..].to_vec(),
+                ));
+            }
+            let guard = match Value::parse(guard_tokens) {
+                Some(res) => res?,
+                None => {
+                    return Err(ParseError::new(
+                        "Cannot parse into a guard",
+                        guard_tokens.to_vec(),
+                    ))
                 }
-                let fun = Box::new(match Value::parse(fun_tokens) {
+            };
+            (&pattern_tokens[..start], Some(guard))
+        }
+        None => (pattern_tokens, None),
+    };
+    if pattern_tokens.is_empty() {
+        return Err(ParseError::new(
+            "Expected a pattern before `if`",
+            tokens.to_vec(),
+        ));
+    }
+    let pattern = parse_pattern(pattern_tokens)?;
+
+    let body = match body_tokens {
+        [Token {
+            r#type:
+                TokenType::Group {
+                    r#type: GroupType::Curly,
+                    tokens,
+                    ..
+                },
+            ..
+        }] => {
+            if tokens.is_empty() {
+                Statements(vec![Statement::Value(Value::Lit(Literal::Nope))])
+            } else {
+                Statements::parse(tokens).unwrap()?
+            }
+        }
+        _ => match Value::parse(body_tokens) {
+            Some(res) => Statements(vec![Statement::Value(res?)]),
+            None => {
+                return Err(ParseError::new(
+                    "Cannot parse into a value",
+                    body_tokens.to_vec(),
+                ))
+            }
+        },
+    };
+
+    Ok(MatchArm {
+        pattern,
+        guard,
+        body,
+    })
+}
+
+/// Matches on booleans must cover both `True` and `False`
+fn check_exhaustive(arms: &[MatchArm], token: &Token) -> Result<(), ParseError> {
+    let mut covered = Vec::new();
+    for arm in arms {
+        match &arm.pattern {
+            Pattern::Lit {
+                lit: Literal::Bool(bool),
+                ..
+            } => {
+                if arm.guard.is_none() {
+                    covered.push(*bool);
+                }
+            }
+            Pattern::Wildcard(..) | Pattern::Bind(..) if arm.guard.is_none() => return Ok(()),
+            Pattern::Wildcard(..) | Pattern::Bind(..) => (),
+            // Only matches on booleans are checked
+            _ => return Ok(()),
+        }
+    }
+    for (bool, name) in [(true, "True"), (false, "False")] {
+        if !covered.contains(&bool) {
+            return Err(ParseError::new(
+                &format!("Match is not exhaustive, `{}` is not covered", name),
+                vec![token.clone()],
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a pattern, and checks that no variable is bound twice
+fn parse_pattern(tokens: &[Token]) -> Result<Pattern, ParseError> {
+    let pattern = match Pattern::parse(tokens) {
+        Some(res) => res?,
+        None => {
+            return Err(ParseError::new(
+                "Cannot parse into a pattern",
+                tokens.to_vec(),
+            ))
+        }
+    };
+    let bindings = pattern.bindings();
+    for (i, var) in bindings.iter().enumerate() {
+        if bindings[..i].iter().any(|other| other.name == var.name) {
+            return Err(ParseError {
+                msg: format!("`{}` is bound more than once in the pattern", var.name),
+                pos: var.pos.clone(),
+            });
+        }
+    }
+    Ok(pattern)
+}
+
+impl Parse for Pattern {
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
+        let pos: FilePos = tokens.into();
+        if let [Token {
+            r#type: TokenType::Char('-'),
+            ..
+        }, Token {
+            r#type: TokenType::Number(n),
+            ..
+        }] = tokens
+        {
+            return Some(Ok(Self::Lit {
+                lit: Literal::Num(-n),
+                pos,
+            }));
+        }
+        if tokens.len() != 1 {
+            return None;
+        }
+        match &tokens[0].r#type {
+            TokenType::String(s) if s == "_" => Some(Ok(Self::Wildcard(pos))),
+            TokenType::String(s) if KEYWORDS.contains(&s.as_str()) => {
+                Literal::parse(tokens).map(|res| res.map(|lit| Self::Lit { lit, pos }))
+            }
+            TokenType::String(..) => Var::parse(tokens).map(|res| res.map(Self::Bind)),
+            TokenType::StringLit(..) | TokenType::CharLit(..) | TokenType::Number(..) => {
+                Literal::parse(tokens).map(|res| res.map(|lit| Self::Lit { lit, pos }))
+            }
+            TokenType::Group {
+                r#type: GroupType::Brack,
+                tokens: item_tokens,
+                ..
+            } => Some(parse_list_pattern(item_tokens, pos)),
+            TokenType::Group {
+                r#type: GroupType::Curly,
+                tokens: field_tokens,
+                ..
+            } => Some(parse_set_pattern(field_tokens, pos)),
+            _ => None,
+        }
+    }
+}
+
+/// `'[' Patterns [',' '...' Pattern] ']'`
+fn parse_list_pattern(tokens: &[Token], pos: FilePos) -> Result<Pattern, ParseError> {
+    let mut items = Vec::new();
+    let mut rest = None;
+    if !tokens.is_empty() {
+        let item_tokens: Vec<&[Token]> = tokens
+            .split(|token| matches!(token.r#type, TokenType::Char(',')))
+            .collect();
+        let len = item_tokens.len();
+        for (i, item_tokens) in item_tokens.into_iter().enumerate() {
+            if item_tokens.is_empty() {
+                return Err(ParseError::new("Expected a pattern", tokens.to_vec()));
+            }
+            let is_rest = item_tokens.len() > 3
+                && item_tokens[..3]
+                    .iter()
+                    .all(|token| matches!(token.r#type, TokenType::Char('.')));
+            if is_rest {
+                if i != len - 1 {
+                    return Err(ParseError::new(
+                        "No patterns can follow a rest pattern",
+                        item_tokens.to_vec(),
+                    ));
+                }
+                rest = Some(Box::new(parse_pattern(&item_tokens[3..])?));
+            } else {
+                items.push(parse_pattern(item_tokens)?);
+            }
+        }
+    }
+    Ok(Pattern::List { items, rest, pos })
+}
+
+/// `'{' Field [':' Pattern] {',' Field [':' Pattern]} '}'`
+fn parse_set_pattern(tokens: &[Token], pos: FilePos) -> Result<Pattern, ParseError> {
+    let mut fields: Vec<(Var, Pattern)> = Vec::new();
+    if tokens.is_empty() {
+        return Ok(Pattern::Set { fields, pos });
+    }
+    for field_tokens in tokens.split(|token| matches!(token.r#type, TokenType::Char(','))) {
+        if field_tokens.is_empty() || !is_name(&field_tokens[0]) {
+            return Err(ParseError::new(
+                "Expected the name of a field",
+                if field_tokens.is_empty() {
+                    tokens.to_vec()
+                } else {
+                    field_tokens.to_vec()
+                },
+            ));
+        }
+        let field = Var::parse(&field_tokens[..1]).unwrap()?;
+        if fields.iter().any(|(other, _)| other.name == field.name) {
+            return Err(ParseError::new(
+                "Duplicate field in pattern",
+                field_tokens.to_vec(),
+            ));
+        }
+        let pattern = match &field_tokens[1..] {
+            [] => Pattern::Bind(field.clone()),
+            [Token {
+                r#type: TokenType::Char(':'),
+                ..
+            }, pattern_tokens @ ..]
+                if !pattern_tokens.is_empty() =>
+            {
+                parse_pattern(pattern_tokens)?
+            }
+            _ => {
+                return Err(ParseError::new(
+                    "Expected `:` and a pattern after the field",
+                    field_tokens[1..].to_vec(),
+                ))
+            }
+        };
+        fields.push((field, pattern));
+    }
+    Ok(Pattern::Set { fields, pos })
+}
+
+/// Binary operators and the methods they call, from lowest to highest precedence
+///
+/// Operators are left associative, except for `**`. Unary operators bind tighter than all
+/// binary operators except `**`, so `-a ** b` is `-(a ** b)`.
+const BINARY_OPERATORS: &[&[(&str, &str)]] = &[
+    &[("||", "or")],
+    &[("&&", "and")],
+    &[("==", "eq"), ("!=", "ne")],
+    &[("<=", "le"), (">=", "ge"), ("<", "lt"), (">", "gt")],
+    &[("+", "add"), ("-", "sub")],
+    &[("*", "mul"), ("/", "div"), ("%", "mod")],
+    &[("**", "pow")],
+];
+
+const UNARY_OPERATORS: &[(&str, &str)] = &[("-", "neg"), ("!", "not")];
+
+const OPERATOR_CHARS: &[char] = &['+', '-', '*', '/', '%', '=', '!', '<', '>', '&', '|'];
+
+fn is_operator_char(token: &Token) -> bool {
+    matches!(token.r#type, TokenType::Char(c) if OPERATOR_CHARS.contains(&c))
+}
+
+/// Returns the operator starting at `tokens[i]` and the number of tokens it spans
+///
+/// Operators with two characters have to be written without a space between them.
+fn operator_at(tokens: &[Token], i: usize) -> Option<(String, usize)> {
+    let first = match tokens[i].r#type {
+        TokenType::Char(c) if OPERATOR_CHARS.contains(&c) => c,
+        _ => return None,
+    };
+    if let Some(next) = tokens.get(i + 1) {
+        if let TokenType::Char(second) = next.r#type {
+            let adjacent =
+                next.pos.row == tokens[i].pos.row && next.pos.col == tokens[i].pos.col + 1;
+            let op: String = [first, second].iter().collect();
+            let known = BINARY_OPERATORS
+                .iter()
+                .any(|level| level.iter().any(|(name, _)| name == &op));
+            if adjacent && known {
+                return Some((op, 2));
+            }
+        }
+    }
+    Some((first.to_string(), 1))
+}
+
+/// Desugars `val op params` to `val.method(params)`
+fn desugar_operator(val: Value, method: &str, params: Vec<Value>, op: &Token) -> Value {
+    Value::FunCall {
+        fun: Box::new(Value::Ref(Reference::Member {
+            set: Box::new(val),
+            field: Var {
+                name: method.to_string(),
+                pos: op.pos.clone(),
+            },
+            pos: op.pos.clone(),
+        })),
+        params,
+        pos: op.pos.clone(),
+    }
+}
+
+fn parse_operand(tokens: &[Token], level: usize, op: &Token) -> Option<Result<Value, ParseError>> {
+    if tokens.is_empty() {
+        return Some(Err(ParseError::new(
+            "Expected a value next to operator",
+            vec![op.clone()],
+        )));
+    }
+    match parse_operators(tokens, level) {
+        None => Some(Err(ParseError::new(
+            "Cannot parse into a value",
+            tokens.to_vec(),
+        ))),
+        res => res,
+    }
+}
+
+fn parse_operators(tokens: &[Token], level: usize) -> Option<Result<Value, ParseError>> {
+    let ops = match BINARY_OPERATORS.get(level) {
+        Some(ops) => ops,
+        None => return parse_postfix(tokens),
+    };
+    let pow_level = BINARY_OPERATORS.len() - 1;
+
+    if level == pow_level {
+        // Every operator below `**` has been handled, so a leading operator is unary
+        if operator_at(tokens, 0).is_some() {
+            return parse_unary(tokens, &tokens[0]);
+        }
+    }
+
+    // Find the binary operator to split at: the last one, or the first one for `**`
+    let mut split = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Some((op, len)) = operator_at(tokens, i) {
+            let binary = i > 0 && !is_operator_char(&tokens[i - 1]);
+            if binary {
+                if let Some((_, method)) = ops.iter().find(|(name, _)| name == &op) {
+                    split = Some((i, len, *method));
+                    if level == pow_level {
+                        break;
+                    }
+                }
+            }
+            i += len;
+        } else {
+            i += 1;
+        }
+    }
+
+    if let Some((i, len, method)) = split {
+        let op = &tokens[i];
+        let (left_level, right_level) = if level == pow_level {
+            (level + 1, level)
+        } else {
+            (level, level + 1)
+        };
+        let left = match parse_operand(&tokens[..i], left_level, op)? {
+            Ok(val) => val,
+            Err(err) => return Some(Err(err)),
+        };
+        // Unary operators are allowed on the right side of `**`
+        let right = if level == pow_level {
+            parse_unary(&tokens[i + len..], op)
+        } else {
+            parse_operand(&tokens[i + len..], right_level, op)
+        };
+        let right = match right? {
+            Ok(val) => val,
+            Err(err) => return Some(Err(err)),
+        };
+        return Some(Ok(desugar_operator(left, method, vec![right], op)));
+    }
+
+    parse_operators(tokens, level + 1)
+}
+
+/// `'-' Value` or `'!' Value`
+fn parse_unary(tokens: &[Token], op: &Token) -> Option<Result<Value, ParseError>> {
+    if tokens.is_empty() {
+        return parse_operand(tokens, 0, op);
+    }
+    let pow_level = BINARY_OPERATORS.len() - 1;
+    if let Some((op, len)) = operator_at(tokens, 0) {
+        let method = UNARY_OPERATORS
+            .iter()
+            .find(|(name, _)| len == 1 && name == &op);
+        let (_, method) = match method {
+            Some(method) => method,
+            None => {
+                return Some(Err(ParseError::new(
+                    &format!("`{}` cannot start a value", op),
+                    tokens[..len].to_vec(),
+                )))
+            }
+        };
+        let val = match parse_unary(&tokens[1..], &tokens[0])? {
+            Ok(val) => val,
+            Err(err) => return Some(Err(err)),
+        };
+        return Some(Ok(desugar_operator(val, method, Vec::new(), &tokens[0])));
+    }
+    parse_operand(tokens, pow_level, op)
+}
+
+/// A value without any operators, except for method calls and `?`
+fn parse_postfix(tokens: &[Token]) -> Option<Result<Value, ParseError>> {
+    if tokens.len() > 1 {
+        if let TokenType::Char('?') = tokens.last().unwrap().r#type {
+            return Some(
+                Value::parse(&tokens[..tokens.len() - 1])?.map(|val| Value::Propagate {
+                    val: Box::new(val),
+                    pos: tokens.last().unwrap().pos.clone(),
+                }),
+            );
+        }
+    }
+    Literal::parse(tokens)
+        .map(|res| res.map(Value::Lit))
+        .or_else(|| parse_format_str(tokens))
+        .or_else(|| Reference::parse(tokens).map(|res| res.map(Value::Ref)))
+        .or_else(|| parse_parenthesized(tokens))
+        .or_else(|| parse_call(tokens))
+        .or_else(|| parse_call_without_parens(tokens))
+}
+
+/// `'(' Value ')'`
+fn parse_parenthesized(tokens: &[Token]) -> Option<Result<Value, ParseError>> {
+    if tokens.len() != 1 {
+        return None;
+    }
+    if let TokenType::Group {
+        r#type: GroupType::Paren,
+        tokens,
+        ..
+    } = &tokens[0].r#type
+    {
+        if !tokens.is_empty() {
+            return Value::parse(tokens);
+        }
+    }
+    None
+}
+
+/// `Value '(' Parameters ')'`
+fn parse_call(tokens: &[Token]) -> Option<Result<Value, ParseError>> {
+    let (last, fun_tokens) = tokens.split_last()?;
+    if fun_tokens.is_empty() {
+        return None;
+    }
+    let params_tokens = if let TokenType::Group {
+        r#type: GroupType::Paren,
+        tokens,
+        ..
+    } = &last.r#type
+    {
+        tokens
+    } else {
+        return None;
+    };
+
+    let fun = Box::new(match Value::parse(fun_tokens) {
+        Some(Ok(val)) => val,
+        Some(Err(err)) => return Some(Err(err)),
+        None => {
+            return Some(Err(ParseError::new(
+                "Cannot parse into function call",
+                fun_tokens.to_vec(),
+            )))
+        }
+    });
+
+    let chain = params_tokens.split(|token| matches!(token.r#type, TokenType::Char(',')));
+    let mut params = Vec::new();
+    for param in chain {
+        if param.is_empty() {
+            continue;
+        }
+        params.push(match Value::parse(param) {
+            Some(Ok(val)) => val,
+            Some(Err(err)) => return Some(Err(err)),
+            None => {
+                return Some(Err(ParseError::new(
+                    "Cannot parse into function call",
+                    fun_tokens.to_vec(),
+                )))
+            }
+        });
+    }
+
+    Some(Ok(Value::FunCall {
+        fun,
+        params,
+        pos: tokens.into(),
+    }))
+}
+
+/// `Value Literal` or `Value Function`: A call with a single argument and no parenthesis
+fn parse_call_without_parens(tokens: &[Token]) -> Option<Result<Value, ParseError>> {
+    let len = tokens.len();
+    let arg_len = if len > 2 && is_function(&tokens[len - 2..]) {
+        2
+    } else if len > 1 && is_literal_arg(&tokens[len - 1]) {
+        1
+    } else {
+        return None;
+    };
+    let (fun_tokens, arg_tokens) = tokens.split_at(len - arg_len);
+    if !on_same_line(fun_tokens.last().unwrap(), &arg_tokens[0]) {
+        return Some(Err(ParseError::new(SEPARATE_LINES, arg_tokens.to_vec())));
+    }
+
+    let fun = match Value::parse(fun_tokens) {
+        Some(Ok(val)) => val,
+        Some(Err(err)) => return Some(Err(err)),
+        None => {
+            return Some(Err(ParseError::new(
+                "Cannot parse into function call",
+                fun_tokens.to_vec(),
+            )))
+        }
+    };
+    let arg = match Value::parse(arg_tokens)? {
+        Ok(val) => val,
+        Err(err) => return Some(Err(err)),
+    };
+
+    Some(Ok(Value::FunCall {
+        fun: Box::new(fun),
+        params: vec![arg],
+        pos: tokens.into(),
+    }))
+}
+
+const SEPARATE_LINES: &str = "Values on separate lines must be separated by `;`";
+
+/// Words which can't be used as names of variables or members
+pub const KEYWORDS: &[&str] = &[
+    "let", "include", "type", "try", "match", "if", "while", "for", "in", "break", "continue",
+    "return", "Nope", "True", "False",
+];
+
+fn is_name(token: &Token) -> bool {
+    matches!(&token.r#type, TokenType::String(s) if !KEYWORDS.contains(&s.as_str()))
+}
+
+/// `'"' String '{' Value [':' Spec] '}' String '"'`
+fn parse_format_str(tokens: &[Token]) -> Option<Result<Value, ParseError>> {
+    let parts = match tokens {
+        [Token {
+            r#type: TokenType::FormatStr(parts),
+            ..
+        }] => parts,
+        _ => return None,
+    };
+    let mut format_parts = Vec::new();
+    for part in parts {
+        format_parts.push(match part {
+            StrPart::Str(str) => FormatPart::Str(str.clone()),
+            StrPart::Value {
+                tokens: val_tokens,
+                spec,
+            } => {
+                let val = match Value::parse(val_tokens) {
                     Some(Ok(val)) => val,
                     Some(Err(err)) => return Some(Err(err)),
                     None => {
                         return Some(Err(ParseError::new(
-                            "Cannot parse into function call",
-                            fun_tokens.to_vec(),
+                            "Cannot parse into an interpolated value",
+                            val_tokens.to_vec(),
                         )))
                     }
-                });
-                let params = if let TokenType::Group {
-                    r#type: GroupType::Paren,
-                    tokens,
-                } = &tokens.last().unwrap().r#type
-                {
-                    let chain = tokens.split(|token| {
-                        if let TokenType::Char(',') = token.r#type {
-                            true
-                        } else {
-                            false
-                        }
-                    });
-
-                    let mut params = Vec::new();
-                    for param in chain {
-                        if param.is_empty() {
-                            continue;
-                        }
-                        params.push(match Value::parse(&param.to_vec()) {
-                            Some(Ok(val)) => val,
-                            Some(Err(err)) => return Some(Err(err)),
-                            None => {
-                                return Some(Err(ParseError::new(
-                                    "Cannot parse into function call",
-                                    fun_tokens.to_vec(),
-                                )))
-                            }
-                        });
-                    }
-                    params
-                } else {
-                    return None;
                 };
+                FormatPart::Value {
+                    val,
+                    spec: spec.clone(),
+                }
+            }
+        });
+    }
+    Some(Ok(Value::Format {
+        parts: format_parts,
+        pos: tokens.into(),
+    }))
+}
 
-                Some(Ok(Self::FunCall {
-                    fun,
-                    params,
-                    pos: tokens.into(),
-                }))
-            })
+/// Whether the token can be the argument of a call without parenthesis
+fn is_literal_arg(token: &Token) -> bool {
+    match &token.r#type {
+        TokenType::StringLit(..)
+        | TokenType::FormatStr(..)
+        | TokenType::CharLit(..)
+        | TokenType::Number(..) => true,
+        TokenType::Group {
+            r#type: GroupType::Brack,
+            ..
+        } => true,
+        TokenType::String(s) => ["Nope", "True", "False"].contains(&s.as_str()),
+        _ => false,
     }
 }
 
+fn is_function(tokens: &[Token]) -> bool {
+    matches!(
+        tokens,
+        [
+            Token {
+                r#type: TokenType::Group {
+                    r#type: GroupType::Paren,
+                    ..
+                },
+                ..
+            },
+            Token {
+                r#type: TokenType::Group {
+                    r#type: GroupType::Curly,
+                    ..
+                },
+                ..
+            },
+        ]
+    )
+}
+
+fn on_same_line(before: &Token, after: &Token) -> bool {
+    before.end_row() == after.pos.row
+}
+
 impl Parse for Var {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
         if tokens.len() == 1 {
-            if let TokenType::String(var) = &tokens.get(0).unwrap().r#type {
+            if let TokenType::String(var) = &tokens.first().unwrap().r#type {
                 Some(Ok(Self {
                     name: var.to_string(),
                     pos: tokens.into(),
@@ -204,58 +973,331 @@ impl Parse for Var {
 }
 
 impl Parse for Reference {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
 
-        if tokens.len() == 1 {
-            Var::parse(tokens).map(|res| res.map(|var| Reference::Var(var)))
-        } else if tokens.len() >= 3 {
-            if let TokenType::Char('.') = tokens.get(tokens.len() - 2).unwrap().r#type {
-                let set = match Value::parse(&tokens.get(..tokens.len() - 2).unwrap().to_vec()) {
-                    Some(Ok(val)) => Box::new(val),
-                    Some(Err(err)) => return Some(Err(err)),
-                    None => return None,
-                };
-                let field = match Var::parse(&vec![tokens.last().unwrap().clone()]) {
-                    Some(Ok(var)) => var,
-                    Some(Err(err)) => return Some(Err(err)),
-                    None => return None,
-                };
-                Some(Ok(Reference::Member {
-                    set,
-                    field,
-                    pos: tokens.into(),
-                }))
-            } else {
-                None
+        let len = tokens.len();
+        if len == 1 {
+            Var::parse(tokens).map(|res| res.map(Reference::Var))
+        } else if len >= 3 && matches!(tokens[len - 2].r#type, TokenType::Char('.')) {
+            let set = match Value::parse(&tokens[..len - 2]) {
+                Some(Ok(val)) => Box::new(val),
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            };
+            let field = match Var::parse(&tokens[len - 1..]) {
+                Some(Ok(var)) => var,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            };
+            Some(Ok(Reference::Member {
+                set,
+                field,
+                pos: tokens.into(),
+            }))
+        } else if is_name(&tokens[len - 1]) {
+            // The dot is optional when the member is on the same line as the set
+            if !on_same_line(&tokens[len - 2], &tokens[len - 1]) {
+                return Some(Err(ParseError::new(
+                    SEPARATE_LINES,
+                    tokens[len - 1..].to_vec(),
+                )));
             }
+            let set = match Value::parse(&tokens[..len - 1]) {
+                Some(Ok(val)) => Box::new(val),
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    return Some(Err(ParseError::new(
+                        "Cannot parse into a member",
+                        tokens.to_vec(),
+                    )))
+                }
+            };
+            let field = match Var::parse(&tokens[len - 1..])? {
+                Ok(var) => var,
+                Err(err) => return Some(Err(err)),
+            };
+            Some(Ok(Reference::Member {
+                set,
+                field,
+                pos: tokens.into(),
+            }))
         } else {
             None
         }
     }
 }
 
+/// `'type' Variable '=' '(' Arguments ')' '{' { 'let' Variable '=' Value ';' } '}'`
+fn parse_type(tokens: &[Token]) -> Result<Statement, ParseError> {
+    let name = match tokens.get(1) {
+        Some(token) if is_name(token) => Var::parse(std::slice::from_ref(token)).unwrap()?,
+        _ => {
+            return Err(ParseError::new(
+                "Expected the name of the type after `type`",
+                tokens.to_vec(),
+            ))
+        }
+    };
+    if !matches!(
+        tokens.get(2).map(|token| &token.r#type),
+        Some(TokenType::Char('='))
+    ) || tokens.len() != 5
+    {
+        return Err(ParseError::new(
+            "Expected `=`, the fields and a block of members after the name of the type",
+            tokens[1..].to_vec(),
+        ));
+    }
+    let constructor = match Function::parse(&tokens[3..]) {
+        Some(res) => res?,
+        None => {
+            return Err(ParseError::new(
+                "Expected the fields and a block of members after `=`",
+                tokens[3..].to_vec(),
+            ))
+        }
+    };
+
+    let mut members = Vec::new();
+    for statement in constructor.body.0 {
+        match statement {
+            Statement::Assignment {
+                var: Reference::Var(var),
+                val,
+                ..
+            } => {
+                if var.name == "new" {
+                    return Err(ParseError {
+                        msg: "`new` is the constructor of the type and can't be redefined"
+                            .to_string(),
+                        pos: var.pos,
+                    });
+                }
+                members.push((var, val))
+            }
+            // Empty statements
+            Statement::Value(Value::Lit(Literal::Nope)) => (),
+            _ => {
+                return Err(ParseError::new(
+                    "The block of a type can only contain `let name = value` statements",
+                    tokens[4..].to_vec(),
+                ))
+            }
+        }
+    }
+
+    Ok(Statement::Type {
+        name,
+        fields: constructor.args,
+        members,
+        pos: tokens.into(),
+    })
+}
+
+/// `'let' Pattern '=' Value`, where the pattern is a list or set pattern
+fn parse_destructure(tokens: &[Token]) -> Option<Result<Statement, ParseError>> {
+    if !matches!(
+        tokens.get(1).map(|token| &token.r#type),
+        Some(TokenType::Group {
+            r#type: GroupType::Brack | GroupType::Curly,
+            ..
+        })
+    ) {
+        return None;
+    }
+    if !matches!(
+        tokens.get(2).map(|token| &token.r#type),
+        Some(TokenType::Char('='))
+    ) {
+        return Some(Err(ParseError::new(
+            "Expected `=` after the pattern",
+            tokens[1..].to_vec(),
+        )));
+    }
+    let pattern = match parse_pattern(&tokens[1..2]) {
+        Ok(pattern) => pattern,
+        Err(err) => return Some(Err(err)),
+    };
+    let val = match tokens.get(3..).filter(|tokens| !tokens.is_empty()) {
+        Some(val_tokens) => match Value::parse(val_tokens)? {
+            Ok(val) => val,
+            Err(err) => return Some(Err(err)),
+        },
+        None => {
+            return Some(Err(ParseError::new(
+                "Expected a value after `=`",
+                tokens[2..].to_vec(),
+            )))
+        }
+    };
+    Some(Ok(Statement::Destructure {
+        pattern,
+        val,
+        pos: tokens.into(),
+    }))
+}
+
+/// The block at the end of a loop, and the tokens between the keyword and the block
+fn loop_body<'a>(
+    tokens: &'a [Token],
+    keyword: &str,
+) -> Result<(&'a [Token], Statements), ParseError> {
+    let body = match tokens.last().map(|token| &token.r#type) {
+        Some(TokenType::Group {
+            r#type: GroupType::Curly,
+            tokens: body_tokens,
+            ..
+        }) if tokens.len() > 2 => {
+            if body_tokens.is_empty() {
+                Statements(vec![Statement::Value(Value::Lit(Literal::Nope))])
+            } else {
+                match Statements::parse(body_tokens) {
+                    Some(res) => res?,
+                    None => {
+                        return Err(ParseError::new(
+                            "Cannot parse into block",
+                            body_tokens.to_vec(),
+                        ))
+                    }
+                }
+            }
+        }
+        _ => {
+            return Err(ParseError::new(
+                &format!("Expected a block after `{}`", keyword),
+                tokens.to_vec(),
+            ))
+        }
+    };
+    Ok((&tokens[1..tokens.len() - 1], body))
+}
+
+/// `'while' Value Block`
+fn parse_while(tokens: &[Token]) -> Result<Statement, ParseError> {
+    let (cond_tokens, body) = loop_body(tokens, "while")?;
+    let cond = match Value::parse(cond_tokens) {
+        Some(res) => res?,
+        None => {
+            return Err(ParseError::new(
+                "Cannot parse into a condition",
+                cond_tokens.to_vec(),
+            ))
+        }
+    };
+    Ok(Statement::While {
+        cond,
+        body,
+        pos: tokens.into(),
+    })
+}
+
+/// `'for' Pattern 'in' Value Block`
+fn parse_for(tokens: &[Token]) -> Result<Statement, ParseError> {
+    let (head_tokens, body) = loop_body(tokens, "for")?;
+    let r#in = head_tokens
+        .iter()
+        .position(|token| matches!(&token.r#type, TokenType::String(s) if s == "in"));
+    let (pattern_tokens, iterable_tokens) = match r#in {
+        Some(r#in) if r#in > 0 && r#in + 1 < head_tokens.len() => {
+            (&head_tokens[..r#in], &head_tokens[r#in + 1..])
+        }
+        _ => {
+            return Err(ParseError::new(
+                "Expected a pattern, `in` and a value after `for`",
+                tokens.to_vec(),
+            ))
+        }
+    };
+    let pattern = parse_pattern(pattern_tokens)?;
+    let iterable = match Value::parse(iterable_tokens) {
+        Some(res) => res?,
+        None => {
+            return Err(ParseError::new(
+                "Cannot parse into a value",
+                iterable_tokens.to_vec(),
+            ))
+        }
+    };
+    Ok(Statement::For {
+        pattern,
+        iterable,
+        body,
+        pos: tokens.into(),
+    })
+}
+
+/// `'return' [Value]`
+fn parse_return(tokens: &[Token]) -> Result<Statement, ParseError> {
+    let val = match tokens.get(1..).filter(|tokens| !tokens.is_empty()) {
+        Some(val_tokens) => match Value::parse(val_tokens) {
+            Some(res) => res?,
+            None => {
+                return Err(ParseError::new(
+                    "Cannot parse into a value",
+                    val_tokens.to_vec(),
+                ))
+            }
+        },
+        None => Value::Lit(Literal::Nope),
+    };
+    Ok(Statement::Return {
+        val,
+        pos: tokens.into(),
+    })
+}
+
 impl Parse for Statement {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
+        assert!(!tokens.is_empty());
+        if let TokenType::String(s) = &tokens[0].r#type {
+            match s.as_str() {
+                "type" => return Some(parse_type(tokens)),
+                "while" => return Some(parse_while(tokens)),
+                "for" => return Some(parse_for(tokens)),
+                "break" | "continue" if tokens.len() > 1 => {
+                    return Some(Err(ParseError::new(
+                        &format!("Expected nothing after `{}`", s),
+                        tokens[1..].to_vec(),
+                    )))
+                }
+                "break" => return Some(Ok(Self::Break(tokens.into()))),
+                "return" => return Some(parse_return(tokens)),
+                "continue" => return Some(Ok(Self::Continue(tokens.into()))),
+                _ => (),
+            }
+        }
         let out = {
-            if let TokenType::String(s) = &tokens.get(0).unwrap().r#type {
+            if let TokenType::String(s) = &tokens.first().unwrap().r#type {
                 if s == "let" {
-                    let (var, val) = tokens.split_at(
-                        tokens
-                            .iter()
-                            .position(|token| {
-                                if let TokenType::Char('=') = token.r#type {
-                                    true
-                                } else {
-                                    false
-                                }
-                            })
-                            .unwrap(),
-                    );
+                    if let Some(res) = parse_destructure(tokens) {
+                        return Some(res);
+                    }
+                    let eq = match tokens
+                        .iter()
+                        .position(|token| matches!(token.r#type, TokenType::Char('=')))
+                    {
+                        Some(eq) if eq > 1 => eq,
+                        _ => {
+                            return Some(Err(ParseError::new(
+                                "Expected a name and `=` after `let`",
+                                tokens.to_vec(),
+                            )))
+                        }
+                    };
+                    let (var, val) = tokens.split_at(eq);
+                    if val.len() == 1 {
+                        return Some(Err(ParseError::new(
+                            "Expected a value after `=`",
+                            val.to_vec(),
+                        )));
+                    }
                     // let var = Reference::parse(var.get(1..).unwrap().to_vec())?.map_err(|err| Some(err))?;
-                    let var = Reference::parse(&var.get(1..).unwrap().to_vec())?.ok()?;
-                    let val = Value::parse(&val.get(1..).unwrap().to_vec())?.ok()?;
+                    let var = Reference::parse(var.get(1..).unwrap())?.ok()?;
+                    let val = match Value::parse(val.get(1..).unwrap())? {
+                        Ok(val) => val,
+                        Err(err) => return Some(Err(err)),
+                    };
 
                     Some(Ok(Self::Assignment {
                         var,
@@ -270,25 +1312,29 @@ impl Parse for Statement {
             }
         }
         .or_else(|| {
-            if let TokenType::String(s) = &tokens.get(0).unwrap().r#type {
+            if let TokenType::String(s) = &tokens.first().unwrap().r#type {
                 if s == "include" {
                     // TODO: Allow for multiple files per include statement?
-                    if let Some(token) = tokens.get(1) {
-                        if let TokenType::StringLit(str) = &token.r#type {
-                            let file = token
-                                .pos
-                                .file
-                                .parent()
-                                .unwrap()
-                                .join(str)
-                                .canonicalize()
-                                .unwrap();
-                            Some(Ok(Self::Include(file)))
-                        } else {
-                            unimplemented!("Handle error if provided input argument isn't a string")
+                    match tokens.get(1) {
+                        Some(
+                            token @ Token {
+                                r#type: TokenType::StringLit(str),
+                                ..
+                            },
+                        ) => {
+                            let file = token.pos.file.parent().unwrap().join(str);
+                            Some(match file.canonicalize() {
+                                Ok(file) => Ok(Self::Include(file)),
+                                Err(err) => Err(ParseError {
+                                    msg: format!("Cannot include `{}` because: {}", str, err),
+                                    pos: token.pos.clone(),
+                                }),
+                            })
                         }
-                    } else {
-                        unimplemented!("Handle error if include has no argument")
+                        _ => Some(Err(ParseError::new(
+                            "Expected the path of a file after `include`",
+                            tokens.to_vec(),
+                        ))),
                     }
                 } else {
                     None
@@ -297,7 +1343,7 @@ impl Parse for Statement {
                 None
             }
         })
-        .or_else(|| Value::parse(tokens).map(|res| res.map(|val| Statement::Value(val))))
+        .or_else(|| Value::parse(tokens).map(|res| res.map(Statement::Value)))
         .or_else(|| {
             Some(Err(ParseError::new(
                 "Cannot parse into statement",
@@ -309,12 +1355,17 @@ impl Parse for Statement {
 }
 
 impl Parse for Statements {
-    fn parse(tokens: &Vec<Token>) -> Option<Result<Self, ParseError>> {
-        assert!(tokens.len() > 0);
+    fn parse(tokens: &[Token]) -> Option<Result<Self, ParseError>> {
         let mut statements = Vec::new();
         let mut statement = Vec::new();
         for token in tokens.iter() {
             match token.r#type {
+                TokenType::Char(';') if statement.is_empty() => {
+                    return Some(Err(ParseError::new(
+                        "Expected a statement before `;`",
+                        vec![token.clone()],
+                    )))
+                }
                 TokenType::Char(';') => {
                     statements.push(match Statement::parse(&statement) {
                         Some(Ok(s)) => s,
@@ -349,15 +1400,129 @@ impl Parse for Statements {
     }
 }
 
+/// Where a statement is, which decides which control flow statements it can use
+#[derive(Clone, Copy, Default)]
+struct Context {
+    in_loop: bool,
+    in_fun: bool,
+}
+
+/// Checks that `break` and `continue` are only used inside loops, and `return` only inside
+/// functions
+///
+/// Function bodies are checked on their own, since a loop around the definition of a function
+/// isn't around its calls.
+fn check_control_flow(statements: &Statements, ctx: Context) -> Result<(), ParseError> {
+    statements
+        .0
+        .iter()
+        .try_for_each(|statement| check_statement(statement, ctx))
+}
+
+fn check_statement(statement: &Statement, ctx: Context) -> Result<(), ParseError> {
+    let in_loop = Context {
+        in_loop: true,
+        ..ctx
+    };
+    match statement {
+        Statement::Assignment { var, val, .. } => {
+            check_reference(var, ctx)?;
+            check_value(val, ctx)
+        }
+        Statement::Type {
+            fields, members, ..
+        } => {
+            check_params(fields)?;
+            members
+                .iter()
+                .try_for_each(|(_, val)| check_value(val, ctx))
+        }
+        Statement::Destructure { val, .. } => check_value(val, ctx),
+        Statement::While { cond, body, .. } => {
+            check_value(cond, ctx)?;
+            check_control_flow(body, in_loop)
+        }
+        Statement::For { iterable, body, .. } => {
+            check_value(iterable, ctx)?;
+            check_control_flow(body, in_loop)
+        }
+        Statement::Break(pos) if !ctx.in_loop => Err(ParseError {
+            msg: "`break` can only be used inside a loop".to_string(),
+            pos: pos.clone(),
+        }),
+        Statement::Continue(pos) if !ctx.in_loop => Err(ParseError {
+            msg: "`continue` can only be used inside a loop".to_string(),
+            pos: pos.clone(),
+        }),
+        Statement::Return { pos, .. } if !ctx.in_fun => Err(ParseError {
+            msg: "`return` can only be used inside a function".to_string(),
+            pos: pos.clone(),
+        }),
+        Statement::Return { val, .. } | Statement::Value(val) => check_value(val, ctx),
+        Statement::Break(..) | Statement::Continue(..) | Statement::Include(..) => Ok(()),
+    }
+}
+
+/// Default values are evaluated before the body of the function, so they are outside of it
+fn check_params(params: &[Param]) -> Result<(), ParseError> {
+    params
+        .iter()
+        .filter_map(|param| param.default.as_ref())
+        .try_for_each(|default| check_value(default, Context::default()))
+}
+
+fn check_reference(r#ref: &Reference, ctx: Context) -> Result<(), ParseError> {
+    match r#ref {
+        Reference::Var(..) => Ok(()),
+        Reference::Member { set, .. } => check_value(set, ctx),
+    }
+}
+
+fn check_value(val: &Value, ctx: Context) -> Result<(), ParseError> {
+    match val {
+        Value::Lit(Literal::Fun(Callable::Fun(fun))) => {
+            check_params(&fun.args)?;
+            let in_fun = Context {
+                in_loop: false,
+                in_fun: true,
+            };
+            check_control_flow(&fun.body, in_fun)
+        }
+        Value::Lit(..) => Ok(()),
+        Value::Ref(r#ref) => check_reference(r#ref, ctx),
+        Value::FunCall { fun, params, .. } => {
+            check_value(fun, ctx)?;
+            params.iter().try_for_each(|param| check_value(param, ctx))
+        }
+        Value::Try { val, .. } | Value::Propagate { val, .. } => check_value(val, ctx),
+        Value::Format { parts, .. } => parts.iter().try_for_each(|part| match part {
+            FormatPart::Str(..) => Ok(()),
+            FormatPart::Value { val, .. } => check_value(val, ctx),
+        }),
+        Value::Match { val, arms, .. } => {
+            check_value(val, ctx)?;
+            arms.iter().try_for_each(|arm| {
+                if let Some(guard) = &arm.guard {
+                    check_value(guard, ctx)?;
+                }
+                check_control_flow(&arm.body, ctx)
+            })
+        }
+    }
+}
+
 pub fn parse_file(tokens: Vec<Token>) -> Result<Statements, SandError> {
-    Statements::parse(&tokens)
+    let tree = Statements::parse(&tokens)
         .ok_or_else(|| SandError::from(ParseError::new("File cannot be parsed", tokens)))?
-        .map_err(|err| SandError::from(err))
+        .map_err(SandError::from)?;
+    check_control_flow(&tree, Context::default())?;
+    Ok(tree)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
 
     // TODO: Add tests which *should* fail to check that parsing functions fail when they should
 
@@ -466,7 +1631,7 @@ mod tests {
         let tokens = tokenize_str("foo()", FilePos::internal()).unwrap();
         let val = Value::parse(&tokens).unwrap().unwrap();
         match val {
-            Value::FunCall { params, .. } => assert!(params.len() == 0),
+            Value::FunCall { params, .. } => assert!(params.is_empty()),
             _ => panic!(),
         }
     }
@@ -482,6 +1647,166 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_value_try() {
+        let tokens = tokenize_str("try foo()", FilePos::internal()).unwrap();
+        let val = Value::parse(&tokens).unwrap().unwrap();
+        match val {
+            Value::Try { val, .. } => match *val {
+                Value::FunCall { .. } => (),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_value_propagate() {
+        let tokens = tokenize_str("foo()?.bar", FilePos::internal()).unwrap();
+        let val = Value::parse(&tokens).unwrap().unwrap();
+        match val {
+            Value::Ref(Reference::Member { set, .. }) => match *set {
+                Value::Propagate { .. } => (),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    fn parse_value_str(str: &str) -> Option<Result<Value, ParseError>> {
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        Value::parse(&tokens)
+    }
+
+    fn member_names(val: &Value) -> Vec<String> {
+        match val {
+            Value::Ref(Reference::Var(var)) => vec![var.name.clone()],
+            Value::Ref(Reference::Member { set, field, .. }) => {
+                let mut names = member_names(set);
+                names.push(field.name.clone());
+                names
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_optional_dot() {
+        let val = parse_value_str("foo bar").unwrap().unwrap();
+        assert!(member_names(&val) == vec!["foo", "bar"])
+    }
+
+    #[test]
+    fn parse_sugar_optional_dot_chain() {
+        let val = parse_value_str("foo bar.baz qux").unwrap().unwrap();
+        assert!(member_names(&val) == vec!["foo", "bar", "baz", "qux"])
+    }
+
+    #[test]
+    fn parse_sugar_variable_is_member() {
+        // An identifier is always a member, never an argument
+        let val = parse_value_str("print x").unwrap().unwrap();
+        assert!(member_names(&val) == vec!["print", "x"])
+    }
+
+    #[test]
+    fn parse_sugar_number_arg() {
+        let val = parse_value_str("n pow 2").unwrap().unwrap();
+        match val {
+            Value::FunCall { fun, params, .. } => {
+                assert!(member_names(&fun) == vec!["n", "pow"]);
+                assert!(params == vec![Value::Lit(Literal::Num(2.0))])
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_string_arg() {
+        let val = parse_value_str("print \"hi\"").unwrap().unwrap();
+        match val {
+            Value::FunCall { params, .. } => {
+                assert!(params == vec![Value::Lit(Literal::Str("hi".to_string()))])
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_bool_and_list_arg() {
+        let val = parse_value_str("x eq True").unwrap().unwrap();
+        match val {
+            Value::FunCall { params, .. } => {
+                assert!(params == vec![Value::Lit(Literal::Bool(true))])
+            }
+            _ => panic!(),
+        }
+        let val = parse_value_str("foo [1]").unwrap().unwrap();
+        match val {
+            Value::FunCall { params, .. } => {
+                assert!(params == vec![Value::Lit(Literal::List(vec![Literal::Num(1.0)]))])
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_function_arg() {
+        let val = parse_value_str("5.times (n) { n }").unwrap().unwrap();
+        match val {
+            Value::FunCall { fun, params, .. } => {
+                assert!(matches!(*fun, Value::Ref(Reference::Member { .. })));
+                assert!(matches!(params[..], [Value::Lit(Literal::Fun(..))]))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_call_then_member() {
+        let val = parse_value_str("list map (n) { n } len").unwrap().unwrap();
+        match val {
+            Value::Ref(Reference::Member { set, field, .. }) => {
+                assert!(field.name == "len");
+                assert!(matches!(*set, Value::FunCall { .. }))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_separate_lines() {
+        assert!(matches!(parse_value_str("foo\nbar"), Some(Err(..))));
+        assert!(matches!(parse_value_str("foo\n5"), Some(Err(..))));
+        assert!(matches!(parse_value_str("foo\n(n) { n }"), Some(Err(..))));
+    }
+
+    #[test]
+    fn parse_sugar_dot_continuation() {
+        let val = parse_value_str("foo\n    .bar\n    .baz").unwrap().unwrap();
+        assert!(member_names(&val) == vec!["foo", "bar", "baz"])
+    }
+
+    #[test]
+    fn parse_sugar_multiline_group() {
+        // The member is on the same line as the closing parenthesis
+        let val = parse_value_str("foo((n) {\n    n\n}) bar")
+            .unwrap()
+            .unwrap();
+        match val {
+            Value::Ref(Reference::Member { set, field, .. }) => {
+                assert!(field.name == "bar");
+                assert!(matches!(*set, Value::FunCall { .. }))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_sugar_keyword_is_not_member() {
+        assert!(!matches!(parse_value_str("foo let"), Some(Ok(..))))
+    }
+
     #[test]
     fn parse_literal_nope() {
         let tokens = tokenize_str("Nope", FilePos::internal()).unwrap();
@@ -521,7 +1846,13 @@ mod tests {
     fn parse_literal_list() {
         let tokens = tokenize_str("[1, 2, 3]", FilePos::internal()).unwrap();
         let lit = Literal::parse(&tokens).unwrap().unwrap();
-        assert!(lit == Literal::List(vec![Literal::Num(1.0), Literal::Num(2.0), Literal::Num(3.0)]))
+        assert!(
+            lit == Literal::List(vec![
+                Literal::Num(1.0),
+                Literal::Num(2.0),
+                Literal::Num(3.0)
+            ])
+        )
     }
 
     #[test]
@@ -538,20 +1869,38 @@ mod tests {
     fn parse_function() {
         let tokens = tokenize_str("() { foo(); }", FilePos::internal()).unwrap();
         let fun = Function::parse(&tokens).unwrap().unwrap();
-        assert!(fun.args.len() == 0)
+        assert!(fun.args.is_empty())
     }
 
     #[test]
     fn parse_function_complex() {
-        let tokens = tokenize_str(
-            "(foo, bar, baz) { foo(); \"hello\"; }",
-            FilePos::internal()
-        )
-        .unwrap();
+        let tokens =
+            tokenize_str("(foo, bar, baz) { foo(); \"hello\"; }", FilePos::internal()).unwrap();
         let fun = Function::parse(&tokens).unwrap().unwrap();
         assert!(fun.args.len() == 3)
     }
 
+    #[test]
+    fn parse_function_default_and_rest() {
+        let tokens = tokenize_str("(foo, bar = 2, ...baz) { foo }", FilePos::internal()).unwrap();
+        let fun = Function::parse(&tokens).unwrap().unwrap();
+        assert!(fun.args[0].default.is_none() && !fun.args[0].rest);
+        assert!(fun.args[1].default == Some(Value::Lit(Literal::Num(2.0))));
+        assert!(fun.args[2].rest && fun.args[2].pattern.bindings()[0].name == "baz")
+    }
+
+    #[test]
+    fn parse_function_rest_not_last() {
+        let tokens = tokenize_str("(...foo, bar) { foo }", FilePos::internal()).unwrap();
+        assert!(matches!(Function::parse(&tokens), Some(Err(..))))
+    }
+
+    #[test]
+    fn parse_function_default_order() {
+        let tokens = tokenize_str("(foo = 1, bar) { foo }", FilePos::internal()).unwrap();
+        assert!(matches!(Function::parse(&tokens), Some(Err(..))))
+    }
+
     #[test]
     fn parse_variable1() {
         let tokens = tokenize_str("foo", FilePos::internal()).unwrap();
@@ -569,12 +1918,410 @@ mod tests {
     #[test]
     fn parse_variable_bad1() {
         let tokens = tokenize_str("5foo", FilePos::internal()).unwrap();
-        assert!(Var::parse(&tokens) == None)
+        assert!(Var::parse(&tokens).is_none())
     }
 
     #[test]
     fn parse_variable_bad2() {
         let tokens = tokenize_str("fo.o", FilePos::internal()).unwrap();
-        assert!(Var::parse(&tokens) == None)
+        assert!(Var::parse(&tokens).is_none())
+    }
+
+    fn method_name(val: &Value) -> &str {
+        match val {
+            Value::FunCall { fun, .. } => match fun.as_ref() {
+                Value::Ref(Reference::Member { field, .. }) => &field.name,
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    fn operands(val: &Value) -> (&Value, &[Value]) {
+        match val {
+            Value::FunCall { fun, params, .. } => match fun.as_ref() {
+                Value::Ref(Reference::Member { set, .. }) => (set, params),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_operator_precedence() {
+        let val = parse_value_str("a + b * c").unwrap().unwrap();
+        assert!(method_name(&val) == "add");
+        let (left, right) = operands(&val);
+        assert!(member_names(left) == vec!["a"]);
+        assert!(method_name(&right[0]) == "mul");
+    }
+
+    #[test]
+    fn parse_operator_left_associative() {
+        let val = parse_value_str("a - b - c").unwrap().unwrap();
+        let (left, right) = operands(&val);
+        assert!(method_name(left) == "sub");
+        assert!(member_names(&right[0]) == vec!["c"]);
+    }
+
+    #[test]
+    fn parse_operator_pow_right_associative() {
+        let val = parse_value_str("a ** b ** c").unwrap().unwrap();
+        let (left, right) = operands(&val);
+        assert!(member_names(left) == vec!["a"]);
+        assert!(method_name(&right[0]) == "pow");
+    }
+
+    #[test]
+    fn parse_operator_two_chars() {
+        let val = parse_value_str("a <= b").unwrap().unwrap();
+        assert!(method_name(&val) == "le");
+        let val = parse_value_str("a == b && c != d").unwrap().unwrap();
+        assert!(method_name(&val) == "and");
+    }
+
+    #[test]
+    fn parse_operator_separated_chars() {
+        assert!(parse_value_str("a = = b").unwrap().is_err());
+    }
+
+    #[test]
+    fn parse_operator_unary() {
+        let val = parse_value_str("!a").unwrap().unwrap();
+        assert!(method_name(&val) == "not");
+        let val = parse_value_str("a * -b").unwrap().unwrap();
+        let (_, right) = operands(&val);
+        assert!(method_name(&right[0]) == "neg");
+    }
+
+    #[test]
+    fn parse_operator_leading_binary() {
+        for (str, col, op) in [
+            ("** 2", 0, "**"),
+            ("<= 3", 0, "<="),
+            ("let x = == 1", 8, "=="),
+            ("f(&& x)", 2, "&&"),
+            ("a == == b", 5, "=="),
+            ("a && || b", 5, "||"),
+            ("a * / b", 4, "/"),
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            let err = parse_file(tokens).unwrap_err();
+            assert_eq!(err.pos.col, col, "{}", str);
+            assert!(
+                err.msg.ends_with(&format!("`{}` cannot start a value", op)),
+                "{}: {}",
+                str,
+                err.msg
+            );
+        }
+    }
+
+    #[test]
+    fn parse_operator_binds_looser_than_methods() {
+        let val = parse_value_str("a foo + b").unwrap().unwrap();
+        let (left, _) = operands(&val);
+        assert!(member_names(left) == vec!["a", "foo"]);
+    }
+
+    #[test]
+    fn parse_operator_missing_operand() {
+        assert!(parse_value_str("a +").unwrap().is_err());
+    }
+
+    #[test]
+    fn parse_format_str() {
+        let val = parse_value_str(r#""{a + 1} and {b:<3}""#).unwrap().unwrap();
+        match val {
+            Value::Format { parts, .. } => {
+                assert!(parts.len() == 3);
+                assert!(
+                    matches!(&parts[0], FormatPart::Value { val, spec: None } if method_name(val) == "add")
+                );
+                assert!(parts[1] == FormatPart::Str(" and ".to_string()));
+                assert!(
+                    matches!(&parts[2], FormatPart::Value { spec: Some(spec), .. } if spec == "<3")
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_format_str_bad_value() {
+        assert!(parse_value_str(r#""{a +}""#).unwrap().is_err());
+    }
+
+    #[test]
+    fn parse_sugar_format_str_arg() {
+        let val = parse_value_str(r#"print "{a}""#).unwrap().unwrap();
+        assert!(
+            matches!(val, Value::FunCall { params, .. } if matches!(params[0], Value::Format { .. }))
+        );
+    }
+
+    fn parse_match_arms(str: &str) -> Vec<MatchArm> {
+        match parse_value_str(str).unwrap().unwrap() {
+            Value::Match { arms, .. } => arms,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_match() {
+        let arms = parse_match_arms(r#"match n { 0 => "zero", -1 => "minus one", _ => "other" }"#);
+        assert!(arms.len() == 3);
+        assert!(matches!(&arms[0].pattern, Pattern::Lit { lit: Literal::Num(n), .. } if *n == 0.0));
+        assert!(
+            matches!(&arms[1].pattern, Pattern::Lit { lit: Literal::Num(n), .. } if *n == -1.0)
+        );
+        assert!(matches!(arms[2].pattern, Pattern::Wildcard(..)));
+    }
+
+    #[test]
+    fn parse_match_patterns() {
+        let arms = parse_match_arms(
+            "match v { [] => 0, [x, ...rest] => 1, { a, b: [_] } => 2, n if n > 2 => { n; n }, }",
+        );
+        assert!(arms.len() == 4);
+        assert!(
+            matches!(&arms[0].pattern, Pattern::List { items, rest: None, .. } if items.is_empty())
+        );
+        assert!(
+            matches!(&arms[1].pattern, Pattern::List { items, rest: Some(rest), .. } if items.len() == 1 && matches!(rest.as_ref(), Pattern::Bind(var) if var.name == "rest"))
+        );
+        match &arms[2].pattern {
+            Pattern::Set { fields, .. } => {
+                assert!(fields[0].0.name == "a" && matches!(fields[0].1, Pattern::Bind(..)));
+                assert!(fields[1].0.name == "b" && matches!(fields[1].1, Pattern::List { .. }));
+            }
+            _ => panic!(),
+        }
+        assert!(arms[3].guard.is_some());
+        assert!(arms[3].body.0.len() == 2);
+    }
+
+    #[test]
+    fn parse_match_bool_not_exhaustive() {
+        let err = parse_value_str("match b { True => 1 }")
+            .unwrap()
+            .unwrap_err();
+        assert!(err.msg == "Match is not exhaustive, `False` is not covered");
+        let err = parse_value_str("match b { True => 1, False if x => 2 }")
+            .unwrap()
+            .unwrap_err();
+        assert!(err.msg == "Match is not exhaustive, `False` is not covered");
+        assert!(parse_value_str("match b { False => 0, True => 1 }")
+            .unwrap()
+            .is_ok());
+        assert!(parse_value_str("match b { True => 1, _ => 0 }")
+            .unwrap()
+            .is_ok());
+    }
+
+    #[test]
+    fn parse_match_bad() {
+        assert!(parse_value_str("match x { 1 2 }").unwrap().is_err());
+        assert!(parse_value_str("match x { 1 => }").unwrap().is_err());
+        assert!(parse_value_str("match x { a + 1 => 2 }").unwrap().is_err());
+        assert!(parse_value_str("match x { [...a, b] => 2 }")
+            .unwrap()
+            .is_err());
+        let err = parse_value_str("match x { [a, a] => 2 }")
+            .unwrap()
+            .unwrap_err();
+        assert!(err.msg == "`a` is bound more than once in the pattern");
+        assert!(err.pos.col == 14);
+    }
+
+    #[test]
+    fn parse_statement_destructure() {
+        let tokens =
+            tokenize_str("let [a, { b, c: [_] }, ...rest] = x", FilePos::internal()).unwrap();
+        match Statement::parse(&tokens).unwrap().unwrap() {
+            Statement::Destructure { pattern, .. } => {
+                let names: Vec<&str> = pattern
+                    .bindings()
+                    .iter()
+                    .map(|var| var.name.as_str())
+                    .collect();
+                assert!(names == vec!["a", "b", "rest"]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_statement_destructure_bad() {
+        let tokens = tokenize_str("let [a, a] = x", FilePos::internal()).unwrap();
+        let err = Statement::parse(&tokens).unwrap().unwrap_err();
+        assert!(err.pos.col == 8);
+        let tokens = tokenize_str("let [a] x", FilePos::internal()).unwrap();
+        assert!(Statement::parse(&tokens).unwrap().is_err());
+    }
+
+    #[test]
+    fn parse_function_destructure() {
+        let tokens = tokenize_str("([a, b], { x } = y, ...[c]) {}", FilePos::internal()).unwrap();
+        let fun = Function::parse(&tokens).unwrap().unwrap();
+        assert!(matches!(fun.args[0].pattern, Pattern::List { .. }));
+        assert!(
+            matches!(fun.args[1].pattern, Pattern::Set { .. }) && fun.args[1].default.is_some()
+        );
+        assert!(matches!(fun.args[2].pattern, Pattern::List { .. }) && fun.args[2].rest);
+    }
+
+    #[test]
+    fn parse_function_destructure_duplicate() {
+        let tokens = tokenize_str("([a, b], { b }) {}", FilePos::internal()).unwrap();
+        let err = Function::parse(&tokens).unwrap().unwrap_err();
+        assert!(err.msg == "Duplicate parameter name");
+    }
+
+    #[test]
+    fn parse_statement_type() {
+        let str = "type Point = (x, y = 0) { let len = () { 0 }; let zero = 0; }";
+        let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+        match Statement::parse(&tokens).unwrap().unwrap() {
+            Statement::Type {
+                name,
+                fields,
+                members,
+                ..
+            } => {
+                assert!(name.name == "Point");
+                assert!(fields.len() == 2);
+                let names: Vec<&str> = members.iter().map(|(var, _)| var.name.as_str()).collect();
+                assert!(names == vec!["len", "zero"]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_statement_type_bad() {
+        for str in [
+            "type Point (x) {}",
+            "type = (x) {}",
+            "type Point = (x) { 5 }",
+            "type Point = (x) { let Foo.bar = 1 }",
+            "type Point = (x) { let new = () {} }",
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            assert!(Statement::parse(&tokens).unwrap().is_err(), "{}", str);
+        }
+    }
+
+    #[test]
+    fn parse_statement_while() {
+        let tokens = tokenize_str("while i < 10 { let i = i + 1 }", FilePos::internal()).unwrap();
+        match Statement::parse(&tokens).unwrap().unwrap() {
+            Statement::While { cond, body, .. } => {
+                assert!(method_name(&cond) == "lt");
+                assert!(body.0.len() == 1);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_statement_for() {
+        let tokens = tokenize_str("for [a, b] in pairs { a }", FilePos::internal()).unwrap();
+        match Statement::parse(&tokens).unwrap().unwrap() {
+            Statement::For {
+                pattern, iterable, ..
+            } => {
+                let names: Vec<&str> = pattern
+                    .bindings()
+                    .iter()
+                    .map(|var| var.name.as_str())
+                    .collect();
+                assert!(names == vec!["a", "b"]);
+                assert!(member_names(&iterable) == vec!["pairs"]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn parse_statement_loop_bad() {
+        for str in [
+            "while {}",
+            "while True",
+            "for x {}",
+            "for in xs {}",
+            "for x in {}",
+            "break 5",
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            assert!(Statement::parse(&tokens).unwrap().is_err(), "{}", str);
+        }
+    }
+
+    #[test]
+    fn parse_break_outside_loop() {
+        for (str, ok) in [
+            ("while True { break }", true),
+            (
+                "for x in xs { match x { 1 => { continue }, _ => Nope } }",
+                true,
+            ),
+            ("break", false),
+            ("match 1 { _ => { continue } }", false),
+            ("while True { let f = () { break } }", false),
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            assert!(parse_file(tokens).is_ok() == ok, "{}", str);
+        }
+    }
+
+    #[test]
+    fn parse_return_outside_function() {
+        for (str, ok) in [
+            ("let f = () { return 5 }", true),
+            ("let f = () { return }", true),
+            (
+                "let f = (x) { while True { match x { 1 => { return x }, _ => Nope } } }",
+                true,
+            ),
+            ("return 5", false),
+            ("while True { return }", false),
+            ("let f = (x = () { return 1 }) { x }", true),
+            ("let f = (x = match 1 { _ => { return 1 } }) { x }", false),
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            assert!(parse_file(tokens).is_ok() == ok, "{}", str);
+        }
+    }
+
+    #[test]
+    fn parse_include_errors() {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("main.sand");
+        for (str, ok) in [
+            ("include \"std.sand\"", true),
+            ("include \"missing.sand\"", false),
+            ("include 5", false),
+            ("include", false),
+        ] {
+            let tokens = tokenize_str(str, FilePos::new(&file, 1, 1)).unwrap();
+            let res = Statement::parse(&tokens).unwrap();
+            assert!(res.is_ok() == ok, "{}", str);
+        }
+    }
+
+    #[test]
+    fn parse_incomplete() {
+        for (str, col, msg) in [
+            ("let x =", 6, "Expected a value after `=`"),
+            (";;", 0, "Expected a statement before `;`"),
+            ("let x = 1;;", 10, "Expected a statement before `;`"),
+            ("let f = () { ; }", 13, "Expected a statement before `;`"),
+        ] {
+            let tokens = tokenize_str(str, FilePos::internal()).unwrap();
+            let err = parse_file(tokens).unwrap_err();
+            assert_eq!(err.pos.col, col, "{}", str);
+            assert!(err.msg.ends_with(msg), "{}", str);
+        }
+        assert!(parse_file(Vec::new()).is_ok());
     }
 }
@@ -0,0 +1,246 @@
+//! The profiler of `sand run --profile`
+//!
+//! Functions are told apart by where they are defined, and intrinsics by the set and name they are
+//! defined as, like `Num.add`. Inclusive time is the time spent in a call and everything it calls,
+//! and exclusive time the time spent in the call itself. The inclusive time of recursive calls only
+//! counts the outermost call, so it is never more than the time the program ran.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::FilePos;
+
+/// What calls are counted towards
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Callee {
+    /// A function, or the constructor of a type, by where it is defined
+    Fun(FilePos),
+    /// An intrinsic, by the set and name it is defined as, or the name it is called by if it
+    /// isn't a member of a set
+    Intrinsic(String),
+}
+
+/// What the calls to a callee took
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// The name the callee was first called by
+    pub name: String,
+    pub calls: u64,
+    pub inclusive: Duration,
+    pub exclusive: Duration,
+}
+
+/// A call which hasn't returned yet
+#[derive(Debug)]
+struct Active {
+    callee: Callee,
+    start: Instant,
+    /// The time spent in the calls made from this one
+    children: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    stats: HashMap<Callee, Stats>,
+    active: Vec<Active>,
+    /// The exclusive time of every call stack, by the labels of its callees, outermost first
+    stacks: HashMap<Vec<String>, Duration>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts timing a call
+    pub fn enter(&mut self, callee: Callee, name: Option<&str>) {
+        self.stats.entry(callee.clone()).or_insert_with(|| Stats {
+            name: name.unwrap_or("<function>").to_string(),
+            ..Stats::default()
+        });
+        self.active.push(Active {
+            callee,
+            start: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    /// Stops timing the innermost call
+    pub fn exit(&mut self) {
+        let call = match self.active.pop() {
+            Some(call) => call,
+            None => return,
+        };
+        let elapsed = call.start.elapsed();
+        let exclusive = elapsed.saturating_sub(call.children);
+        if let Some(parent) = self.active.last_mut() {
+            parent.children += elapsed;
+        }
+        let recursive = self.active.iter().any(|other| other.callee == call.callee);
+        let stats = self.stats.get_mut(&call.callee).unwrap();
+        stats.calls += 1;
+        stats.exclusive += exclusive;
+        if !recursive {
+            stats.inclusive += elapsed;
+        }
+
+        let stack = self
+            .active
+            .iter()
+            .map(|active| &active.callee)
+            .chain(Some(&call.callee))
+            .map(|callee| self.label(callee))
+            .collect();
+        *self.stacks.entry(stack).or_default() += exclusive;
+    }
+
+    fn label(&self, callee: &Callee) -> String {
+        match callee {
+            Callee::Fun(pos) => format!("{} ({})", self.stats[callee].name, pos),
+            Callee::Intrinsic(name) => name.clone(),
+        }
+    }
+
+    /// The callees with what their calls took, the one with the most exclusive time first
+    pub fn stats(&self) -> Vec<(String, &Stats)> {
+        let mut stats: Vec<_> = self
+            .stats
+            .iter()
+            .map(|(callee, stats)| (self.label(callee), stats))
+            .collect();
+        stats.sort_by(|(a_label, a), (b_label, b)| {
+            (b.exclusive, b.calls, a_label).cmp(&(a.exclusive, a.calls, b_label))
+        });
+        stats
+    }
+
+    /// The call stacks in the folded format of flamegraph tools, with their exclusive time in
+    /// microseconds
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, time)| format!("{} {}", stack.join(";"), time.as_micros()))
+            .collect();
+        lines.sort();
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+}
+
+impl fmt::Display for Profiler {
+    /// A table of the callees, the one with the most exclusive time first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>14} {:>14}  callee",
+            "calls", "inclusive", "exclusive"
+        )?;
+        for (label, stats) in self.stats() {
+            writeln!(
+                f,
+                "{:>10} {:>14} {:>14}  {}",
+                stats.calls,
+                format!("{:.3?}", stats.inclusive),
+                format!("{:.3?}", stats.exclusive),
+                label
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::thread;
+
+    use crate::interpreter::interpret_main;
+    use crate::intrinsics::init_scope;
+    use crate::parser::parse_file;
+    use crate::tokenizer::tokenize_str;
+
+    use super::*;
+
+    fn stats<'a>(profiler: &'a Profiler, label: &str) -> &'a Stats {
+        let stats = profiler.stats();
+        stats
+            .into_iter()
+            .find(|(other, _)| other == label)
+            .map(|(_, stats)| stats)
+            .unwrap()
+    }
+
+    #[test]
+    fn profile_times() {
+        let mut profiler = Profiler::new();
+        let outer = Callee::Intrinsic("outer".to_string());
+        let inner = Callee::Intrinsic("inner".to_string());
+        profiler.enter(outer.clone(), Some("outer"));
+        profiler.enter(inner.clone(), Some("inner"));
+        profiler.enter(inner, Some("inner"));
+        thread::sleep(Duration::from_millis(20));
+        profiler.exit();
+        profiler.exit();
+        profiler.exit();
+
+        let (outer, inner) = (stats(&profiler, "outer"), stats(&profiler, "inner"));
+        assert_eq!((outer.calls, inner.calls), (1, 2));
+        assert!(outer.exclusive < Duration::from_millis(20));
+        assert!(outer.inclusive >= Duration::from_millis(20));
+        // Only the outer of the recursive calls counts towards the inclusive time
+        assert!(inner.inclusive <= outer.inclusive);
+        assert!(inner.exclusive >= Duration::from_millis(20));
+        assert_eq!(profiler.stats()[0].0, "inner");
+
+        let folded = profiler.folded();
+        let stacks: Vec<_> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(stacks, ["outer", "outer;inner", "outer;inner;inner"]);
+    }
+
+    #[test]
+    fn profile_program() {
+        let str = "let count = (n) { match n { 0 => 0, n => 1 + count(n - 1) } };
+let main = (args) { count(3) };";
+        let file = Path::new("test.sand");
+        let tree = parse_file(tokenize_str(str, FilePos::new(file, 1, 1)).unwrap()).unwrap();
+        let mut scope = init_scope();
+        scope.state.borrow_mut().profiler = Some(Profiler::new());
+        assert_eq!(interpret_main(tree, &mut scope).unwrap(), 3);
+
+        let profiler = scope.state.borrow_mut().profiler.take().unwrap();
+        assert_eq!(stats(&profiler, "count (test.sand:1:13)").calls, 4);
+        assert_eq!(stats(&profiler, "main (test.sand:2:12)").calls, 1);
+        assert_eq!(stats(&profiler, "Num.add").calls, 3);
+        assert_eq!(stats(&profiler, "Num.sub").calls, 3);
+        // `count(3)` is a tail call, which replaces the call to `main` on the stack
+        let folded = profiler.folded();
+        assert!(folded.contains("\nmain (test.sand:2:12) "));
+        assert!(folded.contains("\ncount (test.sand:1:13);count (test.sand:1:13);Num.add "));
+        assert!(profiler
+            .to_string()
+            .starts_with("     calls      inclusive"));
+    }
+
+    #[test]
+    fn profile_intrinsics_by_set() {
+        let str =
+            r#"let main = (args) { 1 + 2; "a" + "b"; 1.add(2); True || False; (try 1).or(2) };"#;
+        let file = Path::new("test.sand");
+        let tree = parse_file(tokenize_str(str, FilePos::new(file, 1, 1)).unwrap()).unwrap();
+        let mut scope = init_scope();
+        scope.state.borrow_mut().profiler = Some(Profiler::new());
+        interpret_main(tree, &mut scope).unwrap();
+
+        let profiler = scope.state.borrow_mut().profiler.take().unwrap();
+        // Intrinsics with the same name in different sets are counted apart
+        assert_eq!(stats(&profiler, "Num.add").calls, 2);
+        assert_eq!(stats(&profiler, "Str.add").calls, 1);
+        assert_eq!(stats(&profiler, "Bool.or").calls, 1);
+        assert_eq!(stats(&profiler, "Any.or").calls, 1);
+        assert!(profiler.stats().iter().all(|(label, _)| label != "add"));
+    }
+}
@@ -0,0 +1,286 @@
+//! The test runner of `sand test`
+//!
+//! Every top-level function of a file named `test_*` is a test. Each one runs in a fresh scope
+//! in which the file was interpreted, and fails if anything goes wrong, like a `Main.assert_eq`
+//! of values which are not equal.
+
+use std::collections::HashMap;
+
+use crate::coverage::Coverage;
+use crate::interpreter::{repr, Interpret, InterpretingError, Limits, Scope};
+use crate::intrinsics::{init_scope_with, Capabilities};
+use crate::types::*;
+use crate::FilePos;
+
+/// A test function of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Test {
+    pub name: String,
+    pub pos: FilePos,
+}
+
+/// Finds the test functions of a file, in the order they are defined
+pub fn discover(tree: &Statements) -> Vec<Test> {
+    tree.0
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Assignment {
+                var: Reference::Var(var),
+                val: Value::Lit(Literal::Fun(Callable::Fun(..))),
+                ..
+            } if var.name.starts_with("test_") => Some(Test {
+                name: var.name.clone(),
+                pos: var.pos.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Interprets the file in a new scope, and calls the test in it
+///
+/// What runs is added to the coverage, if there is one.
+pub fn run_test(
+    tree: &Statements,
+    test: &Test,
+    limits: &Limits,
+    capabilities: Capabilities,
+    coverage: &mut Option<Coverage>,
+) -> Result<(), InterpretingError> {
+    let mut scope = init_scope_with(capabilities);
+    scope.state.borrow_mut().limits = limits.clone();
+    scope.state.borrow_mut().coverage = coverage.take();
+    let res = call_test(tree, test, &mut scope);
+    *coverage = scope.state.borrow_mut().coverage.take();
+    res
+}
+
+fn call_test(tree: &Statements, test: &Test, scope: &mut Scope) -> Result<(), InterpretingError> {
+    tree.clone().interpret(scope)?;
+    let call = Value::FunCall {
+        fun: Box::new(Value::Ref(Reference::Var(Var {
+            name: test.name.clone(),
+            pos: test.pos.clone(),
+        }))),
+        params: Vec::new(),
+        pos: test.pos.clone(),
+    };
+    call.interpret(scope)?;
+    Ok(())
+}
+
+/// The differences between two values, as lines of what was expected prefixed by `-` and what
+/// was found instead prefixed by `+`
+///
+/// Lists, instances and sets are compared item by item, and strings spanning several lines line
+/// by line, so only the parts which differ are shown.
+pub fn diff(
+    expected: &Literal,
+    actual: &Literal,
+    scope: &mut Scope,
+    pos: &FilePos,
+) -> Result<Vec<String>, InterpretingError> {
+    let mut lines = Vec::new();
+    diff_at("", expected, actual, scope, pos, &mut lines)?;
+    Ok(lines)
+}
+
+fn diff_at(
+    path: &str,
+    expected: &Literal,
+    actual: &Literal,
+    scope: &mut Scope,
+    pos: &FilePos,
+    lines: &mut Vec<String>,
+) -> Result<(), InterpretingError> {
+    match (expected, actual) {
+        _ if expected == actual => {}
+        (Literal::List(expected), Literal::List(actual)) => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                let path = format!("{}[{}]", path, i);
+                diff_at(&path, expected, actual, scope, pos, lines)?;
+            }
+            for (i, expected) in expected.iter().enumerate().skip(actual.len()) {
+                let val = repr(expected, scope, pos)?;
+                lines.push(format!("- {}[{}]: {}", path, i, val));
+            }
+            for (i, actual) in actual.iter().enumerate().skip(expected.len()) {
+                let val = repr(actual, scope, pos)?;
+                lines.push(format!("+ {}[{}]: {}", path, i, val));
+            }
+        }
+        (
+            Literal::Instance {
+                type_name: expected_type,
+                fields: expected,
+            },
+            Literal::Instance {
+                type_name: actual_type,
+                fields: actual,
+            },
+        ) if expected_type == actual_type => {
+            diff_fields(path, expected, actual, scope, pos, lines)?
+        }
+        (Literal::Set(expected), Literal::Set(actual)) => {
+            diff_fields(path, expected, actual, scope, pos, lines)?
+        }
+        (Literal::Str(expected), Literal::Str(actual))
+            if expected.contains('\n') || actual.contains('\n') =>
+        {
+            let expected: Vec<_> = expected.split('\n').collect();
+            let actual: Vec<_> = actual.split('\n').collect();
+            for i in 0..expected.len().max(actual.len()) {
+                let (expected, actual) = (expected.get(i), actual.get(i));
+                if expected == actual {
+                    continue;
+                }
+                if let Some(expected) = expected {
+                    lines.push(format!("- {}line {}: {:?}", label(path), i + 1, expected));
+                }
+                if let Some(actual) = actual {
+                    lines.push(format!("+ {}line {}: {:?}", label(path), i + 1, actual));
+                }
+            }
+        }
+        _ => {
+            lines.push(format!("- {}{}", label(path), repr(expected, scope, pos)?));
+            lines.push(format!("+ {}{}", label(path), repr(actual, scope, pos)?));
+        }
+    }
+    Ok(())
+}
+
+fn diff_fields(
+    path: &str,
+    expected: &HashMap<String, Literal>,
+    actual: &HashMap<String, Literal>,
+    scope: &mut Scope,
+    pos: &FilePos,
+    lines: &mut Vec<String>,
+) -> Result<(), InterpretingError> {
+    let mut names: Vec<_> = expected.keys().chain(actual.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let path = match path {
+            "" => name.clone(),
+            path => format!("{}.{}", path, name),
+        };
+        match (expected.get(name), actual.get(name)) {
+            (Some(expected), Some(actual)) => diff_at(&path, expected, actual, scope, pos, lines)?,
+            (Some(expected), None) => {
+                lines.push(format!("- {}: {}", path, repr(expected, scope, pos)?))
+            }
+            (None, Some(actual)) => {
+                lines.push(format!("+ {}: {}", path, repr(actual, scope, pos)?))
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+fn label(path: &str) -> String {
+    match path {
+        "" => String::new(),
+        path => format!("{}: ", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::intrinsics::init_scope;
+    use crate::parser::parse_file;
+    use crate::tokenizer::tokenize_str;
+
+    use super::*;
+
+    fn parse(str: &str) -> Statements {
+        parse_file(tokenize_str(str, FilePos::internal()).unwrap()).unwrap()
+    }
+
+    fn run(tree: &Statements) -> Vec<(String, Result<(), InterpretingError>)> {
+        discover(tree)
+            .iter()
+            .map(|test| {
+                let result = run_test(
+                    tree,
+                    test,
+                    &Limits::default(),
+                    Capabilities::default(),
+                    &mut None,
+                );
+                (test.name.clone(), result)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn discover_tests() {
+        let tree = parse("let test_a = () {}; let b = () {}; let test_c = 1; let test_d = () {};");
+        let names: Vec<_> = discover(&tree).into_iter().map(|test| test.name).collect();
+        assert_eq!(names, ["test_a", "test_d"]);
+    }
+
+    #[test]
+    fn run_tests_in_fresh_scopes() {
+        let tree = parse(
+            "let n = 1;
+            let test_set = () { let n = 2; Main.assert_eq(n, 2) };
+            let test_fresh = () { Main.assert_eq(n, 1) };
+            let test_fail = () { Main.assert(n > 1) };
+            let test_uncaught = () { try Main.assert_eq(1, 2) };",
+        );
+        let results = run(&tree);
+        assert!(results[0].1.is_ok());
+        assert!(results[1].1.is_ok());
+        let err = results[2].1.as_ref().unwrap_err();
+        assert_eq!(err.msg, "Assertion failed: the condition is False");
+        assert!(results[3].1.is_err());
+    }
+
+    #[test]
+    fn run_std_tests() {
+        let path = Path::new("std_test.sand");
+        let source = fs::read_to_string(path).unwrap();
+        let tree = parse_file(tokenize_str(&source, FilePos::new(path, 1, 1)).unwrap()).unwrap();
+        for (name, result) in run(&tree) {
+            assert!(result.is_ok(), "{} failed: {:?}", name, result);
+        }
+    }
+
+    #[test]
+    fn diff_values() {
+        let mut scope = init_scope();
+        let pos = FilePos::internal();
+        let str = |str: &str| Literal::Str(str.to_string());
+        let list = |items: &[f64]| Literal::List(items.iter().map(|n| Literal::Num(*n)).collect());
+        let mut diff = |expected: &Literal, actual: &Literal| {
+            diff(expected, actual, &mut scope, &pos).unwrap()
+        };
+
+        assert!(diff(&list(&[1.0, 2.0]), &list(&[1.0, 2.0])).is_empty());
+        assert_eq!(diff(&str("a"), &Literal::Num(1.0)), ["- \"a\"", "+ 1"]);
+        assert_eq!(
+            diff(&list(&[1.0, 2.0, 3.0]), &list(&[1.0, 4.0])),
+            ["- [1]: 2", "+ [1]: 4", "- [2]: 3"]
+        );
+        assert_eq!(
+            diff(&str("a\nb\nc"), &str("a\nc")),
+            ["- line 2: \"b\"", "+ line 2: \"c\"", "- line 3: \"c\""]
+        );
+        let point = |y: f64| Literal::Instance {
+            type_name: "Point".to_string(),
+            fields: vec![
+                ("x".to_string(), Literal::Num(1.0)),
+                ("y".to_string(), Literal::Num(y)),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        assert_eq!(diff(&point(2.0), &point(3.0)), ["- y: 2", "+ y: 3"]);
+    }
+}
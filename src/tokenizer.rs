@@ -22,14 +22,26 @@ impl From<char> for GroupType {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
-    StringLit(String), // "hello"
-    CharLit(char),     // 'c'
-    Number(f64),       // 69
-    String(String),    // foo
-    Char(char),        // +
+    StringLit(String),       // "hello"
+    CharLit(char),           // 'c'
+    Number(f64),             // 69
+    String(String),          // foo
+    Char(char),              // +
+    FormatStr(Vec<StrPart>), // "value: {n}"
     Group {
         r#type: GroupType,
         tokens: Vec<Token>,
+        end: FilePos, // Position of the closing parenthesis
+    },
+}
+
+/// A part of a string with interpolated values
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrPart {
+    Str(String),
+    Value {
+        tokens: Vec<Token>,
+        spec: Option<String>, // The format specifier after ':'
     },
 }
 
@@ -39,6 +51,24 @@ pub struct Token {
     pub pos: FilePos,
 }
 
+impl Token {
+    /// The row the token ends on
+    pub fn end_row(&self) -> usize {
+        match &self.r#type {
+            TokenType::Group { end, .. } => end.row,
+            _ => self.pos.row,
+        }
+    }
+}
+
+/// A `#` comment, which runs to the end of the line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The text of the comment, starting with `#`
+    pub text: String,
+    pub pos: FilePos,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct TokenError {
     pub msg: String,
@@ -60,7 +90,59 @@ impl TokenError {
     }
 }
 
+/// Reads the source of an interpolated value up to the closing '}'
+///
+/// The source is split from its format specifier at the last ':' which isn't nested in
+/// brackets or strings.
+fn interpolation_source(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    pos: &FilePos,
+) -> Result<(String, Option<String>), TokenError> {
+    let mut source = String::new();
+    let mut spec_start = None;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in chars.by_ref() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' => depth -= 1,
+                '}' if depth == 0 => {
+                    let spec = spec_start.map(|start| source.split_off(start));
+                    let spec = spec.map(|spec: String| spec[1..].to_string());
+                    return Ok((source, spec));
+                }
+                '}' => depth -= 1,
+                ':' if depth == 0 => spec_start = Some(source.len()),
+                _ => (),
+            }
+        }
+        source.push(c);
+    }
+    Err(TokenError::new("Mismatched `{` in string", pos))
+}
+
 pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
+    tokenize_with_comments(str, pos).map(|(tokens, _)| tokens)
+}
+
+/// Like [`tokenize_str`], but also returns the comments, which tools that rewrite the source
+/// need to keep
+pub fn tokenize_with_comments(
+    str: &str,
+    pos: FilePos,
+) -> Result<(Vec<Token>, Vec<Comment>), TokenError> {
+    let mut comments = Vec::new();
     let mut chars = str.chars().peekable();
 
     let mut row = pos.row;
@@ -72,7 +154,7 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
     while let Some(c) = chars.next() {
         let pos = FilePos::new(&pos.file, row, col);
         let token = match c {
-            ' ' | '\t' => {
+            ' ' | '\t' | '\r' => {
                 col += 1;
                 continue;
             }
@@ -82,13 +164,16 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                 continue;
             }
             '#' => {
+                let mut text = String::from(c);
                 while let Some(char) = chars.peek() {
                     if char != &'\n' {
-                        chars.next();
+                        text.push(chars.next().unwrap());
+                        col += 1;
                     } else {
                         break;
                     }
                 }
+                comments.push(Comment { text, pos });
                 continue;
             }
             '(' | '[' | '{' => {
@@ -96,6 +181,7 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                     r#type: TokenType::Group {
                         r#type: GroupType::from(c),
                         tokens: Vec::new(),
+                        end: pos.clone(),
                     },
                     pos,
                 });
@@ -103,11 +189,17 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                 continue;
             }
             ')' | ']' | '}' => {
-                let group = group_stack
+                let mut group = group_stack
                     .pop()
                     .ok_or(TokenError::new("Mismatched parenthesis", &pos))?;
-                if let TokenType::Group { ref r#type, .. } = group.r#type {
+                if let TokenType::Group {
+                    ref r#type,
+                    ref mut end,
+                    ..
+                } = group.r#type
+                {
                     if r#type == &GroupType::from(c) {
+                        *end = pos;
                         group
                     } else {
                         return Err(TokenError {
@@ -125,14 +217,15 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                 let mut number_str = String::from(c);
                 let mut trail_dot = false;
                 while let Some(char) = chars.peek() {
-                    if char >= &'0' && char <= &'9' {
+                    if char.is_ascii_digit() {
                         number_str.push(chars.next().unwrap());
                         col += 1;
                     } else if char == &'.' {
                         chars.next().unwrap(); // Consume '.'
                         if let Some(char) = chars.peek() {
-                            if char >= &'0' && char <= &'9' {
+                            if char.is_ascii_digit() {
                                 number_str.push('.');
+                                col += 1;
                             } else {
                                 trail_dot = true;
                                 let r#type = TokenType::Number(
@@ -140,7 +233,10 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                                         .parse()
                                         .map_err(|_err| TokenError::new("Bad int", &pos))?,
                                 );
-                                let token = Token { r#type, pos: pos.clone() };
+                                let token = Token {
+                                    r#type,
+                                    pos: pos.clone(),
+                                };
                                 if group_stack.is_empty() {
                                     tokens.push(token);
                                 } else {
@@ -163,7 +259,9 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                     }
                 }
                 if trail_dot {
+                    col += 1;
                     let r#type = TokenType::Char('.');
+                    let pos = FilePos::new(&pos.file, row, col);
                     Token { r#type, pos }
                 } else {
                     let r#type = TokenType::Number(
@@ -177,10 +275,7 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
             'a'..='z' | 'A'..='Z' | '_' => {
                 let mut str = String::from(c);
                 while let Some(char) = chars.peek() {
-                    if (char >= &'a' && char <= &'z')
-                        || (char >= &'A' && char <= &'Z')
-                        || char == &'_'
-                    {
+                    if char.is_ascii_lowercase() || char.is_ascii_uppercase() || char == &'_' {
                         str.push(chars.next().unwrap());
                         col += 1;
                     } else {
@@ -191,36 +286,77 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                 Token { r#type, pos }
             }
             '"' => {
+                let mut parts = Vec::new();
                 let mut str = String::new();
                 let mut matched = false;
                 while let Some(c) = chars.next() {
-                    if c == '"' {
-                        matched = true;
-                        break;
-                    } else if c == '\\' {
-                        let ch = if let Some(c) = chars.next() {
-                            match c {
-                                'n' => '\n',
-                                't' => '\t',
-                                'r' => '\r',
-                                '"' => '"',
-                                _ => {
-                                    return Err(TokenError {
-                                        msg: "Unknown escaped character".to_string(),
-                                        pos,
-                                    })
+                    col += 1;
+                    match c {
+                        '"' => {
+                            matched = true;
+                            break;
+                        }
+                        '\\' => {
+                            let ch = if let Some(c) = chars.next() {
+                                col += 1;
+                                match c {
+                                    'n' => '\n',
+                                    't' => '\t',
+                                    'r' => '\r',
+                                    '"' | '\\' | '{' | '}' => c,
+                                    _ => {
+                                        return Err(TokenError {
+                                            msg: "Unknown escaped character".to_string(),
+                                            pos,
+                                        })
+                                    }
                                 }
+                            } else {
+                                return Err(TokenError {
+                                    msg: "Expected an escaped character but found nothing"
+                                        .to_string(),
+                                    pos,
+                                });
+                            };
+                            str.push(ch);
+                        }
+                        '{' => {
+                            let value_pos = FilePos::new(&pos.file, row, col + 1);
+                            let (source, spec) = interpolation_source(&mut chars, &value_pos)?;
+                            if source.trim().is_empty() {
+                                return Err(TokenError::new(
+                                    "Expected a value in the interpolation",
+                                    &value_pos,
+                                ));
                             }
-                        } else {
-                            return Err(TokenError {
-                                msg: "Expected an escaped character but found nothing".to_string(),
-                                pos,
-                            });
-                        };
-                        str.push(ch);
-                    } else {
-                        str.push(c);
-                        col += 1;
+                            let tokens = tokenize_str(&source, value_pos)?;
+                            for c in source.chars().chain(
+                                spec.iter()
+                                    .flat_map(|spec| std::iter::once(':').chain(spec.chars())),
+                            ) {
+                                if c == '\n' {
+                                    row += 1;
+                                    col = 0;
+                                } else {
+                                    col += 1;
+                                }
+                            }
+                            col += 1; // Closing '}'
+                            parts.push(StrPart::Str(std::mem::take(&mut str)));
+                            parts.push(StrPart::Value { tokens, spec });
+                        }
+                        '}' => {
+                            return Err(TokenError::new(
+                                "Unmatched `}` in string, escape it with `\\}`",
+                                &FilePos::new(&pos.file, row, col),
+                            ))
+                        }
+                        '\n' => {
+                            row += 1;
+                            col = 0;
+                            str.push(c);
+                        }
+                        c => str.push(c),
                     }
                 }
                 if !matched {
@@ -229,15 +365,26 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                         pos,
                     });
                 }
-                Token {
-                    r#type: TokenType::StringLit(str),
-                    pos,
+                if parts.is_empty() {
+                    Token {
+                        r#type: TokenType::StringLit(str),
+                        pos,
+                    }
+                } else {
+                    parts.push(StrPart::Str(str));
+                    parts.retain(|part| part != &StrPart::Str(String::new()));
+                    Token {
+                        r#type: TokenType::FormatStr(parts),
+                        pos,
+                    }
                 }
             }
             '\'' => {
                 let ch = if let Some(char) = chars.next() {
+                    col += 1;
                     if char == '\\' {
                         if let Some(char) = chars.next() {
+                            col += 1;
                             match char {
                                 'n' => '\n',
                                 't' => '\t',
@@ -267,6 +414,7 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
                 };
 
                 if let Some(char) = chars.next() {
+                    col += 1;
                     if char == '\'' {
                         Token {
                             r#type: TokenType::CharLit(ch),
@@ -311,7 +459,175 @@ pub fn tokenize_str(str: &str, pos: FilePos) -> Result<Vec<Token>, TokenError> {
         });
     }
 
-    Ok(tokens)
+    Ok((tokens, comments))
+}
+
+/// Where some text is in the source, in bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+}
+
+/// Text between tokens which doesn't change what the program means
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// What a token of the lossless stream is, where groups are split into their brackets
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceKind {
+    /// Any token but a group
+    Token(TokenType),
+    Open(GroupType),
+    Close(GroupType),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceToken {
+    pub kind: SourceKind,
+    pub text: String,
+    pub pos: FilePos,
+    pub span: Span,
+    /// The whitespace and comments between the previous token and this one
+    pub leading: Vec<Trivia>,
+}
+
+/// Every token of a source with the trivia between them, which together are the whole source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceTokens {
+    pub tokens: Vec<SourceToken>,
+    /// The whitespace and comments after the last token
+    pub trailing: Vec<Trivia>,
+}
+
+impl SourceTokens {
+    /// Writes the source back out, exactly as it was tokenized
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        for token in &self.tokens {
+            token
+                .leading
+                .iter()
+                .for_each(|trivia| source.push_str(&trivia.text));
+            source.push_str(&token.text);
+        }
+        self.trailing
+            .iter()
+            .for_each(|trivia| source.push_str(&trivia.text));
+        source
+    }
+}
+
+/// Like [`tokenize_with_comments`], but keeps everything in the source, with where it is
+///
+/// Groups are flattened into their opening bracket, their tokens and their closing bracket.
+pub fn tokenize_lossless(str: &str, pos: FilePos) -> Result<SourceTokens, TokenError> {
+    let (tokens, comments) = tokenize_with_comments(str, pos.clone())?;
+
+    // The starts of the lines, to turn positions into offsets
+    let mut lines = vec![0];
+    lines.extend(str.match_indices('\n').map(|(i, _)| i + 1));
+    let offset = |at: &FilePos| {
+        let line = lines[at.row - pos.row];
+        let col = at.col - if at.row == pos.row { pos.col } else { 1 };
+        str[line..]
+            .char_indices()
+            .nth(col)
+            .map_or(str.len(), |(i, _)| line + i)
+    };
+
+    let mut starts = Vec::new();
+    flatten(tokens, &mut starts);
+    let comments = comments.into_iter().map(|comment| {
+        let len = comment.text.len();
+        (comment.pos, None, Some(len))
+    });
+    let mut starts: Vec<(usize, FilePos, Option<SourceKind>, Option<usize>)> = starts
+        .into_iter()
+        .map(|(pos, kind)| (pos, Some(kind), None))
+        .chain(comments)
+        .map(|(pos, kind, len)| (offset(&pos), pos, kind, len))
+        .collect();
+    starts.sort_by_key(|(offset, ..)| *offset);
+
+    let trivia = |kind, offset: usize, end: usize| Trivia {
+        kind,
+        text: str[offset..end].to_string(),
+        span: Span {
+            offset,
+            len: end - offset,
+        },
+    };
+    let mut source_tokens = Vec::new();
+    let mut pending = Vec::new();
+    let first = starts.first().map_or(str.len(), |(offset, ..)| *offset);
+    if first > 0 {
+        pending.push(trivia(TriviaKind::Whitespace, 0, first));
+    }
+    for i in 0..starts.len() {
+        let next = starts.get(i + 1).map_or(str.len(), |(offset, ..)| *offset);
+        let (start, pos, kind, len) = starts[i].clone();
+        let end = match (&kind, len) {
+            (_, Some(len)) => start + len,
+            (Some(SourceKind::Token(..)), None) => start + str[start..next].trim_end().len(),
+            // Brackets are always a single byte
+            _ => start + 1,
+        };
+        match kind {
+            Some(kind) => source_tokens.push(SourceToken {
+                kind,
+                text: str[start..end].to_string(),
+                pos,
+                span: Span {
+                    offset: start,
+                    len: end - start,
+                },
+                leading: std::mem::take(&mut pending),
+            }),
+            None => pending.push(trivia(TriviaKind::Comment, start, end)),
+        }
+        if end < next {
+            pending.push(trivia(TriviaKind::Whitespace, end, next));
+        }
+    }
+    Ok(SourceTokens {
+        tokens: source_tokens,
+        trailing: pending,
+    })
+}
+
+/// The tokens in the order they start in, with groups split into their brackets
+fn flatten(tokens: Vec<Token>, starts: &mut Vec<(FilePos, SourceKind)>) {
+    for token in tokens {
+        match token.r#type {
+            TokenType::Group {
+                r#type,
+                tokens,
+                end,
+            } => {
+                starts.push((token.pos, SourceKind::Open(r#type.clone())));
+                flatten(tokens, starts);
+                starts.push((end, SourceKind::Close(r#type)));
+            }
+            r#type => starts.push((token.pos, SourceKind::Token(r#type))),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -322,14 +638,14 @@ mod tests {
     fn tokenize_string_lit() {
         let tokens = tokenize_str("\"hello\"", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::StringLit("hello".to_string()))
+        assert!(tokens.first().unwrap().r#type == TokenType::StringLit("hello".to_string()))
     }
 
     #[test]
     fn tokenize_string_lit_newline() {
         let tokens = tokenize_str("\"he\\nllo\"", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::StringLit("he\nllo".to_string()))
+        assert!(tokens.first().unwrap().r#type == TokenType::StringLit("he\nllo".to_string()))
     }
 
     #[test]
@@ -339,14 +655,14 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::StringLit("he\"llo".to_string()))
+        assert!(tokens.first().unwrap().r#type == TokenType::StringLit("he\"llo".to_string()))
     }
 
     #[test]
     fn tokenize_char_lit() {
         let tokens = tokenize_str("'c'", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::CharLit('c'))
+        assert!(tokens.first().unwrap().r#type == TokenType::CharLit('c'))
     }
 
     #[test]
@@ -356,7 +672,7 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::CharLit('\n'))
+        assert!(tokens.first().unwrap().r#type == TokenType::CharLit('\n'))
     }
 
     #[test]
@@ -366,14 +682,14 @@ mod tests {
             Err(err) => panic!("{}", err),
         };
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::CharLit('\''))
+        assert!(tokens.first().unwrap().r#type == TokenType::CharLit('\''))
     }
 
     #[test]
     fn tokenize_number_int() {
         let tokens = tokenize_str("5", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::Number(5.0))
+        assert!(tokens.first().unwrap().r#type == TokenType::Number(5.0))
     }
 
     #[test]
@@ -381,7 +697,7 @@ mod tests {
         let tokens = tokenize_str("5.0", FilePos::internal()).unwrap();
         println!("Tokens: {:?}", tokens);
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::Number(5.0))
+        assert!(tokens.first().unwrap().r#type == TokenType::Number(5.0))
     }
 
     #[test]
@@ -389,7 +705,7 @@ mod tests {
         let tokens = tokenize_str("5. ", FilePos::internal()).unwrap();
         println!("Tokens: {:?}", tokens);
         assert!(tokens.len() == 2);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::Number(5.0));
+        assert!(tokens.first().unwrap().r#type == TokenType::Number(5.0));
         assert!(tokens.get(1).unwrap().r#type == TokenType::Char('.'));
     }
 
@@ -397,21 +713,21 @@ mod tests {
     fn tokenize_string() {
         let tokens = tokenize_str("foo", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::String("foo".to_string()))
+        assert!(tokens.first().unwrap().r#type == TokenType::String("foo".to_string()))
     }
 
     #[test]
     fn tokenize_string_complex() {
         let tokens = tokenize_str("fo_o", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::String("fo_o".to_string()))
+        assert!(tokens.first().unwrap().r#type == TokenType::String("fo_o".to_string()))
     }
 
     #[test]
     fn tokenize_char() {
         let tokens = tokenize_str(".", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
-        assert!(tokens.get(0).unwrap().r#type == TokenType::Char('.'))
+        assert!(tokens.first().unwrap().r#type == TokenType::Char('.'))
     }
 
     #[test]
@@ -419,11 +735,135 @@ mod tests {
         let tokens = tokenize_str("()", FilePos::internal()).unwrap();
         assert!(tokens.len() == 1);
         assert!(
-            tokens.get(0).unwrap().r#type
+            tokens.first().unwrap().r#type
                 == TokenType::Group {
                     r#type: GroupType::Paren,
                     tokens: Vec::new(),
+                    end: FilePos::new(&FilePos::internal().file, 0, 1),
                 }
         )
     }
+
+    #[test]
+    fn tokenize_format_str() {
+        let tokens = tokenize_str("\"a {b} c\"", FilePos::internal()).unwrap();
+        assert!(tokens.len() == 1);
+        match &tokens[0].r#type {
+            TokenType::FormatStr(parts) => {
+                assert!(parts.len() == 3);
+                assert!(parts[0] == StrPart::Str("a ".to_string()));
+                assert!(matches!(
+                    &parts[1],
+                    StrPart::Value { tokens, spec: None }
+                        if tokens.len() == 1 && tokens[0].pos.col == 4
+                ));
+                assert!(parts[2] == StrPart::Str(" c".to_string()));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn tokenize_format_str_spec() {
+        let tokens = tokenize_str(r#""{f("x", [1]):>8.2}""#, FilePos::internal()).unwrap();
+        match &tokens[0].r#type {
+            TokenType::FormatStr(parts) => {
+                assert!(matches!(
+                    &parts[..],
+                    [StrPart::Value { tokens, spec: Some(spec) }]
+                        if tokens.len() == 2 && spec == ">8.2"
+                ));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn tokenize_format_str_escaped_braces() {
+        let tokens = tokenize_str("\"\\{a\\}\"", FilePos::internal()).unwrap();
+        assert!(tokens[0].r#type == TokenType::StringLit("{a}".to_string()))
+    }
+
+    #[test]
+    fn tokenize_format_str_bad() {
+        assert!(tokenize_str("\"{a\"", FilePos::internal()).is_err());
+        assert!(tokenize_str("\"a}\"", FilePos::internal()).is_err());
+        assert!(tokenize_str("\"{ }\"", FilePos::internal()).is_err());
+    }
+
+    #[test]
+    fn tokenize_string_lit_position() {
+        let tokens = tokenize_str("\"a\\nb\" c", FilePos::internal()).unwrap();
+        assert!(tokens[1].pos.col == 7)
+    }
+
+    #[test]
+    fn tokenize_positions_after_literals() {
+        let tokens = tokenize_str("5.times 1.5 x '\\n' y", FilePos::internal()).unwrap();
+        let cols: Vec<usize> = tokens.iter().map(|token| token.pos.col).collect();
+        assert_eq!(cols, vec![0, 1, 2, 8, 12, 14, 19]);
+    }
+
+    #[test]
+    fn tokenize_comments() {
+        let (tokens, comments) =
+            tokenize_with_comments("# a\nx # b\n", FilePos::internal()).unwrap();
+        assert!(tokens.len() == 1);
+        assert!(comments.len() == 2);
+        assert!(comments[0].text == "# a" && comments[0].pos.row == 0);
+        assert!(comments[1].text == "# b" && comments[1].pos.col == 3);
+    }
+
+    #[test]
+    fn tokenize_lossless_round_trip() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut sources = vec![
+            String::new(),
+            "  # only a comment\n".to_string(),
+            "x\r\ny".to_string(),
+            "let s = \"a\\n{x:>3} é {\"{y}\"}\";\t# ünïcode\r\nf(5.times, 1.5, '\\'')\n\n"
+                .to_string(),
+        ];
+        sources.push(std::fs::read_to_string(root.join("std.sand")).unwrap());
+        for entry in std::fs::read_dir(root.join("examples")).unwrap() {
+            sources.push(std::fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+        for source in sources {
+            let tokens = tokenize_lossless(&source, FilePos::internal()).unwrap();
+            assert_eq!(tokens.to_source(), source);
+            for token in &tokens.tokens {
+                let span = token.span;
+                assert_eq!(&source[span.offset..span.end()], token.text);
+                if let SourceKind::Token(r#type) = &token.kind {
+                    let retokenized = tokenize_str(&token.text, token.pos.clone()).unwrap();
+                    assert_eq!(&retokenized[0].r#type, r#type, "{}", token.text);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tokenize_lossless_trivia() {
+        let source = "# a\nf(x) # b\n";
+        let tokens = tokenize_lossless(source, FilePos::new(std::path::Path::new("a"), 1, 1));
+        let tokens = tokens.unwrap();
+        let texts: Vec<&str> = tokens
+            .tokens
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["f", "(", "x", ")"]);
+        assert_eq!(tokens.tokens[0].leading[0].kind, TriviaKind::Comment);
+        assert_eq!(tokens.tokens[0].leading[1].text, "\n");
+        assert_eq!(tokens.tokens[2].span, Span { offset: 6, len: 1 });
+        let trailing: Vec<&str> = tokens.trailing.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(trailing, vec![" ", "# b", "\n"]);
+
+        // The `\r` of CRLF line endings is whitespace, not a token
+        let tokens = tokenize_lossless("x\r\ny\r\n", FilePos::internal()).unwrap();
+        let texts: Vec<&str> = tokens.tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["x", "y"]);
+        assert_eq!(tokens.tokens[1].leading[0].kind, TriviaKind::Whitespace);
+        assert_eq!(tokens.tokens[1].leading[0].text, "\r\n");
+    }
 }
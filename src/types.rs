@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -21,18 +22,24 @@ impl TypeError {
     }
 }
 
+pub type IntrinsicFn = dyn Fn(&mut Scope) -> Result<Literal, InterpretingError>;
+
 // TODO: Add a position field for every type
 // TODO: Make members not public, or is this a bad idea?
 #[derive(Clone)]
 pub struct Intrinsic {
     pub args: Vec<Var>,
-    pub fun_interpret: Rc<dyn Fn(&mut Scope) -> Result<Literal, InterpretingError>>,
+    pub fun_interpret: Rc<IntrinsicFn>,
+    /// The set and member it is defined as, like `Num.add`, which tells intrinsics with the same
+    /// member name apart
+    pub name: Option<String>,
 }
 
 impl fmt::Debug for Intrinsic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Intrinsic")
             .field("args", &self.args)
+            .field("name", &self.name)
             .finish()
     }
 }
@@ -47,20 +54,68 @@ impl PartialEq for Intrinsic {
 pub enum Callable {
     Fun(Function),
     Intr(Intrinsic),
+    /// A method with `self` bound to its receiver, like `5.add`
+    Method {
+        fun: Box<Callable>,
+        receiver: Box<Literal>,
+    },
+    /// Creates an instance of a user-defined type
+    Constructor(Constructor),
 }
 
 impl Callable {
-    pub fn get_args(&self) -> Vec<Var> {
+    pub fn get_args(&self) -> Vec<Param> {
         match self {
             Self::Fun(fun) => fun.args.clone(),
-            Self::Intr(intr) => intr.args.clone(),
+            Self::Intr(intr) => intr.args.iter().cloned().map(Param::new).collect(),
+            Self::Method { fun, .. } => fun.get_args(),
+            Self::Constructor(constructor) => constructor.args.clone(),
+        }
+    }
+
+    /// Where the callable was defined, if it was defined in sand code
+    pub fn get_pos(&self) -> Option<FilePos> {
+        match self {
+            Self::Fun(fun) => Some(fun.pos.clone()),
+            Self::Intr(..) => None,
+            Self::Method { fun, .. } => fun.get_pos(),
+            Self::Constructor(constructor) => Some(constructor.pos.clone()),
+        }
+    }
+}
+
+/// `type Name = (fields) { members }`: The parameters of the constructor are the fields of
+/// the instances it creates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constructor {
+    pub type_name: String,
+    pub args: Vec<Param>,
+    pub pos: FilePos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    /// A variable, or a pattern destructuring the argument
+    pub pattern: Pattern,
+    /// Used when the call doesn't provide a value for the parameter
+    pub default: Option<Value>,
+    /// `...var`: Collects all remaining arguments into a list
+    pub rest: bool,
+}
+
+impl Param {
+    pub fn new(var: Var) -> Self {
+        Self {
+            pattern: Pattern::Bind(var),
+            default: None,
+            rest: false,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function {
-    pub args: Vec<Var>,
+    pub args: Vec<Param>,
     pub body: Statements,
     pub pos: FilePos,
 }
@@ -75,9 +130,33 @@ pub enum Literal {
     List(Vec<Literal>),
     Fun(Callable),
     Set(HashMap<String, Literal>),
+    /// A value of a user-defined type, whose methods are in the set named `type_name`
+    Instance {
+        type_name: String,
+        fields: HashMap<String, Literal>,
+    },
+    /// An error value, as produced by `try` when the tried value fails
+    Err(Box<Literal>),
 }
 
+#[allow(clippy::wrong_self_convention)]
 impl Literal {
+    /// The name of the set holding the methods of this type of literal
+    pub fn type_name(&self) -> &str {
+        match self {
+            Self::Nope => "Nope",
+            Self::Str(..) => "Str",
+            Self::Char(..) => "Char",
+            Self::Num(..) => "Num",
+            Self::Bool(..) => "Bool",
+            Self::List(..) => "List",
+            Self::Fun(..) => "Fun",
+            Self::Set(..) => "Set",
+            Self::Instance { type_name, .. } => type_name,
+            Self::Err(..) => "Err",
+        }
+    }
+
     pub fn as_nope(self) -> Result<(), TypeError> {
         match self {
             Self::Nope => Ok(()),
@@ -92,6 +171,13 @@ impl Literal {
         }
     }
 
+    pub fn as_char(self) -> Result<char, TypeError> {
+        match self {
+            Self::Char(char) => Ok(char),
+            _ => Err(TypeError::new("Literal is not a char", &FilePos::temp())),
+        }
+    }
+
     pub fn as_num(self) -> Result<f64, TypeError> {
         match self {
             Self::Num(num) => Ok(num),
@@ -122,7 +208,7 @@ impl Literal {
     pub fn as_list(self) -> Result<Vec<Literal>, TypeError> {
         match self {
             Self::List(list) => Ok(list),
-            _ => Err(TypeError::new("Literal is not a boolean", &FilePos::temp())),
+            _ => Err(TypeError::new("Literal is not a list", &FilePos::temp())),
         }
     }
 
@@ -142,6 +228,148 @@ impl Literal {
             _ => Err(TypeError::new("Literal is not a set", &FilePos::temp())),
         }
     }
+
+    pub fn as_err(self) -> Result<Literal, TypeError> {
+        match self {
+            Self::Err(err) => Ok(*err),
+            _ => Err(TypeError::new("Literal is not an error", &FilePos::temp())),
+        }
+    }
+}
+
+impl From<()> for Literal {
+    fn from(_: ()) -> Self {
+        Self::Nope
+    }
+}
+
+impl From<String> for Literal {
+    fn from(str: String) -> Self {
+        Self::Str(str)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(str: &str) -> Self {
+        Self::Str(str.to_string())
+    }
+}
+
+impl From<char> for Literal {
+    fn from(char: char) -> Self {
+        Self::Char(char)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(num: f64) -> Self {
+        Self::Num(num)
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(num: i64) -> Self {
+        Self::Num(num as f64)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(bool: bool) -> Self {
+        Self::Bool(bool)
+    }
+}
+
+impl<T: Into<Literal>> From<Vec<T>> for Literal {
+    fn from(list: Vec<T>) -> Self {
+        Self::List(list.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Literal>> From<HashMap<String, T>> for Literal {
+    fn from(set: HashMap<String, T>) -> Self {
+        Self::Set(set.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl From<Callable> for Literal {
+    fn from(fun: Callable) -> Self {
+        Self::Fun(fun)
+    }
+}
+
+impl TryFrom<Literal> for () {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_nope()
+    }
+}
+
+impl TryFrom<Literal> for String {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_str()
+    }
+}
+
+impl TryFrom<Literal> for char {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_char()
+    }
+}
+
+impl TryFrom<Literal> for f64 {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_num()
+    }
+}
+
+impl TryFrom<Literal> for i64 {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_int().map(|int| int as i64)
+    }
+}
+
+impl TryFrom<Literal> for bool {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_bool()
+    }
+}
+
+impl<T: TryFrom<Literal, Error = TypeError>> TryFrom<Literal> for Vec<T> {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_list()?.into_iter().map(T::try_from).collect()
+    }
+}
+
+impl<T: TryFrom<Literal, Error = TypeError>> TryFrom<Literal> for HashMap<String, T> {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_set()?
+            .into_iter()
+            .map(|(k, v)| Ok((k, T::try_from(v)?)))
+            .collect()
+    }
+}
+
+impl TryFrom<Literal> for Callable {
+    type Error = TypeError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        lit.as_fun()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -153,6 +381,154 @@ pub enum Value {
         params: Vec<Value>,
         pos: FilePos,
     },
+    /// `try val`: Evaluates to an error value instead of failing
+    Try {
+        val: Box<Value>,
+        pos: FilePos,
+    },
+    /// `val?`: Raises the contained value if `val` is an error value
+    Propagate {
+        val: Box<Value>,
+        pos: FilePos,
+    },
+    /// `"value: {val}"`: A string with interpolated values
+    Format {
+        parts: Vec<FormatPart>,
+        pos: FilePos,
+    },
+    /// `match val { pattern => body, ... }`: Evaluates the body of the first matching arm
+    Match {
+        val: Box<Value>,
+        arms: Vec<MatchArm>,
+        pos: FilePos,
+    },
+}
+
+impl Value {
+    /// Where the value starts, unless it is a literal, which doesn't keep its position
+    pub fn get_pos(&self) -> Option<&FilePos> {
+        match self {
+            Self::Lit(..) => None,
+            Self::Ref(Reference::Var(var)) => Some(&var.pos),
+            Self::Ref(Reference::Member { set, pos, .. }) => set.get_pos().or(Some(pos)),
+            Self::FunCall { fun, pos, .. } => fun.get_pos().or(Some(pos)),
+            Self::Propagate { val, pos } => val.get_pos().or(Some(pos)),
+            Self::Try { pos, .. } | Self::Format { pos, .. } | Self::Match { pos, .. } => Some(pos),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPart {
+    Str(String),
+    Value { val: Value, spec: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    /// `pattern if guard => body`: The arm only matches if the guard is true
+    pub guard: Option<Value>,
+    pub body: Statements,
+}
+
+/// The shape of a value, which binds the parts of the value it matches to variables
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`: Matches anything
+    Wildcard(FilePos),
+    /// `name`: Matches anything and binds it to `name`
+    Bind(Var),
+    /// `5`, `"foo"`, `True`: Matches values equal to the literal
+    Lit { lit: Literal, pos: FilePos },
+    /// `[head, ...tail]`: Matches lists with at least as many items as patterns, or exactly
+    /// as many without a rest pattern
+    List {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+        pos: FilePos,
+    },
+    /// `{ x, y: pattern }`: Matches sets which have all of the fields
+    Set {
+        fields: Vec<(Var, Pattern)>,
+        pos: FilePos,
+    },
+}
+
+impl Pattern {
+    pub fn get_pos(&self) -> &FilePos {
+        match self {
+            Self::Wildcard(pos) => pos,
+            Self::Bind(var) => &var.pos,
+            Self::Lit { pos, .. } | Self::List { pos, .. } | Self::Set { pos, .. } => pos,
+        }
+    }
+
+    /// The variables bound by the pattern, in order
+    pub fn bindings(&self) -> Vec<&Var> {
+        match self {
+            Self::Wildcard(..) | Self::Lit { .. } => Vec::new(),
+            Self::Bind(var) => vec![var],
+            Self::List { items, rest, .. } => items
+                .iter()
+                .chain(rest.iter().map(|rest| rest.as_ref()))
+                .flat_map(Pattern::bindings)
+                .collect(),
+            Self::Set { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+        }
+    }
+}
+
+/// How the pattern is written, with `_` for literals which can't be in patterns
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard(..) => write!(f, "_"),
+            Self::Bind(var) => write!(f, "{}", var.name),
+            Self::Lit { lit, .. } => match lit {
+                Literal::Nope => write!(f, "Nope"),
+                Literal::Bool(true) => write!(f, "True"),
+                Literal::Bool(false) => write!(f, "False"),
+                Literal::Num(n) => write!(f, "{}", n),
+                Literal::Str(s) => write!(f, "{:?}", s),
+                Literal::Char(c) => write!(f, "{:?}", c),
+                _ => write!(f, "_"),
+            },
+            Self::List { items, rest, .. } => {
+                let mut items: Vec<String> = items.iter().map(Pattern::to_string).collect();
+                if let Some(rest) = rest {
+                    items.push(format!("...{}", rest));
+                }
+                write!(f, "[{}]", items.join(", "))
+            }
+            Self::Set { fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(var, pattern)| match pattern {
+                        Pattern::Bind(bind) if bind.name == var.name => var.name.clone(),
+                        _ => format!("{}: {}", var.name, pattern),
+                    })
+                    .collect();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// How the parameter is written, leaving out the value of a default
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rest {
+            write!(f, "...{}", self.pattern)
+        } else if self.default.is_some() {
+            write!(f, "{} = …", self.pattern)
+        } else {
+            write!(f, "{}", self.pattern)
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -187,9 +563,63 @@ pub enum Statement {
         val: Value,
         pos: FilePos,
     },
+    /// `type Name = (fields) { let member = val; ... }`: Declares a set of methods for
+    /// instances created by its constructor `Name.new`
+    Type {
+        name: Var,
+        fields: Vec<Param>,
+        members: Vec<(Var, Value)>,
+        pos: FilePos,
+    },
+    /// `let [a, ...rest] = val`: Binds the parts of the value matched by the pattern
+    Destructure {
+        pattern: Pattern,
+        val: Value,
+        pos: FilePos,
+    },
+    /// `while cond { body }`: Repeats the body as long as the condition is true
+    While {
+        cond: Value,
+        body: Statements,
+        pos: FilePos,
+    },
+    /// `for pattern in iterable { body }`: Repeats the body for every item of a list or
+    /// character of a string, binding it to the pattern
+    For {
+        pattern: Pattern,
+        iterable: Value,
+        body: Statements,
+        pos: FilePos,
+    },
+    /// `break`: Stops the innermost loop
+    Break(FilePos),
+    /// `continue`: Skips to the next iteration of the innermost loop
+    Continue(FilePos),
+    /// `return val`: Stops the innermost function, which evaluates to `val`, or Nope without it
+    Return {
+        val: Value,
+        pos: FilePos,
+    },
     Value(Value),
     Include(PathBuf),
 }
 
+impl Statement {
+    pub fn get_pos(&self) -> Option<&FilePos> {
+        match self {
+            Self::Assignment { pos, .. }
+            | Self::Type { pos, .. }
+            | Self::Destructure { pos, .. }
+            | Self::While { pos, .. }
+            | Self::For { pos, .. }
+            | Self::Return { pos, .. }
+            | Self::Break(pos)
+            | Self::Continue(pos) => Some(pos),
+            Self::Value(val) => val.get_pos(),
+            Self::Include(..) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Statements(pub Vec<Statement>);
@@ -0,0 +1,139 @@
+//! Runs sand programs through the binary and compares what they print and exit with to the files
+//! next to them
+//!
+//! Every `tests/golden/<name>.sand` is run with `sand <file> run`, and its stdout, stderr and
+//! exit code are compared to `<name>.stdout`, `<name>.stderr` and `<name>.exit`. The programs in
+//! `examples/` are checked the same way, against the files in `tests/examples/`.
+//!
+//! Run with `SAND_BLESS=1` to write what the programs do now to the expected files instead.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A program, and where the files with what it should do are
+struct Case {
+    program: PathBuf,
+    expected: PathBuf,
+}
+
+fn cases_of(programs: &str, expected: &str) -> Vec<Case> {
+    let mut cases: Vec<_> = fs::read_dir(programs)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sand"))
+        .map(|program| Case {
+            expected: Path::new(expected).join(program.file_stem().unwrap()),
+            program,
+        })
+        .collect();
+    cases.sort_by(|a, b| a.program.cmp(&b.program));
+    cases
+}
+
+/// Runs the case, returning how it differs from what was expected
+fn check(case: &Case, bless: bool) -> Vec<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_sand"))
+        .arg(&case.program)
+        .arg("run")
+        .output()
+        .unwrap();
+    let actual = [
+        (
+            "stdout",
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ),
+        (
+            "stderr",
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ),
+        ("exit", format!("{}\n", output.status.code().unwrap_or(-1))),
+    ];
+
+    let mut failures = Vec::new();
+    for (ext, actual) in actual.iter() {
+        let path = case.expected.with_extension(ext);
+        if bless {
+            fs::write(&path, actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| match *ext {
+            "exit" => "0\n".to_string(),
+            _ => String::new(),
+        });
+        if &expected != actual {
+            failures.push(format!(
+                "{} differs from {}\n--- expected:\n{}--- actual:\n{}",
+                ext,
+                path.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+    failures
+}
+
+#[test]
+fn golden() {
+    let bless = env::var_os("SAND_BLESS").is_some();
+    let mut cases = cases_of("tests/golden", "tests/golden");
+    cases.extend(cases_of("examples", "tests/examples"));
+    assert!(!cases.is_empty());
+
+    let failures: Vec<_> = cases
+        .iter()
+        .flat_map(|case| {
+            check(case, bless)
+                .into_iter()
+                .map(move |failure| format!("==== {}: {}", case.program.display(), failure))
+        })
+        .collect();
+    if !failures.is_empty() {
+        panic!(
+            "Programs did something else than expected, rerun with SAND_BLESS=1 if that's \
+             right\n\n{}",
+            failures.join("\n")
+        );
+    }
+}
+
+#[test]
+fn files_named_like_subcommands() {
+    let dir = env::temp_dir().join(format!("sand-file-names-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for name in [
+        "l",
+        "p",
+        "ru",
+        "do",
+        "tes",
+        "test.sand",
+        "tests.sand",
+        "run",
+    ] {
+        let program = dir.join(name);
+        fs::write(&program, "let main = (args) { 3 };").unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_sand"))
+            .arg(&program)
+            .arg("run")
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(3),
+            "{}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sand"))
+        .args(["examples/primes.sand", "rnu"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown subcommand `rnu`"));
+}